@@ -0,0 +1,186 @@
+//! Compares the bitboard `Board` against the original `[[Cell; WIDTH]; HEIGHT]`
+//! implementation, both with the hidden buffer on top of the visible rows.
+//! Run with `cargo bench`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use tetrust::client::board::{Board, Cell, BUFFER_HEIGHT, DEFAULT_HEIGHT, DEFAULT_WIDTH as WIDTH};
+use tetrust::client::randomizer::Randomizer;
+use tetrust::client::tetromino::{Tetromino, TetrominoType};
+
+/// Every row of the board, hidden buffer included.
+const HEIGHT: usize = BUFFER_HEIGHT + DEFAULT_HEIGHT;
+
+/// The board as it was before the bitboard rewrite, kept as a baseline.
+#[derive(Clone)]
+struct ArrayBoard {
+    cells: [[Cell; WIDTH]; HEIGHT],
+}
+
+impl ArrayBoard {
+    fn from_board(board: &Board) -> Self {
+        let mut cells = [[Cell::Empty; WIDTH]; HEIGHT];
+        for (y, row) in cells.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                *cell = board.get(x, y);
+            }
+        }
+        Self { cells }
+    }
+
+    fn collides(&self, piece: &Tetromino, test_x: i32, test_y: i32) -> bool {
+        for y in 0..4 {
+            for x in 0..4 {
+                if piece.cells[y][x] {
+                    let board_x = test_x + x as i32;
+                    let board_y = test_y + y as i32;
+
+                    if board_x < 0
+                        || board_x >= WIDTH as i32
+                        || board_y < 0
+                        || board_y >= HEIGHT as i32
+                    {
+                        return true;
+                    }
+
+                    if self.cells[board_y as usize][board_x as usize] == Cell::Filled {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    fn clear_full_lines(&mut self) -> usize {
+        let mut lines_cleared = 0;
+        let mut y = HEIGHT;
+        while y > 0 {
+            if self.cells[y - 1].iter().all(|&cell| cell == Cell::Filled) {
+                for row in (1..y).rev() {
+                    self.cells[row] = self.cells[row - 1];
+                }
+                self.cells[0] = [Cell::Empty; WIDTH];
+                lines_cleared += 1;
+            } else {
+                y -= 1;
+            }
+        }
+        lines_cleared
+    }
+}
+
+// A ragged stack half the visible height with a few full rows, filled from
+// a fixed seed.
+fn sample_board() -> Board {
+    let mut board = Board::new();
+    let mut randomizer = Randomizer::new(7);
+    for y in HEIGHT - DEFAULT_HEIGHT / 2..HEIGHT {
+        let gap = randomizer.next_piece() as usize;
+        for x in 0..WIDTH {
+            if y % 4 == 0 || x != gap {
+                board.set(x, y, Cell::Filled);
+            }
+        }
+    }
+    board
+}
+
+fn all_pieces() -> Vec<Tetromino> {
+    let mut pieces = Vec::new();
+    for tetromino_type in TetrominoType::ALL {
+        let mut piece = Tetromino::new(tetromino_type);
+        for _ in 0..4 {
+            pieces.push(piece.clone());
+            piece.rotate_clockwise();
+        }
+    }
+    pieces
+}
+
+fn collision_sweep(
+    pieces: &[Tetromino],
+    positions: &[(i32, i32)],
+    collides: impl Fn(&Tetromino, i32, i32) -> bool,
+) {
+    let mut hits = 0;
+    for piece in pieces {
+        for &(x, y) in positions {
+            hits += collides(piece, x, y) as u32;
+        }
+    }
+    black_box(hits);
+}
+
+fn bench(name: &str, iterations: u32, mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:<32} {:>10.1} ns/iter",
+        name,
+        elapsed.as_nanos() as f64 / iterations as f64
+    );
+    elapsed
+}
+
+fn main() {
+    let board = sample_board();
+    let array_board = ArrayBoard::from_board(&board);
+    let pieces = all_pieces();
+    let positions: Vec<(i32, i32)> = (-2..WIDTH as i32)
+        .flat_map(|x| (-2..HEIGHT as i32).map(move |y| (x, y)))
+        .collect();
+
+    // Both implementations must agree before their speed means anything.
+    for piece in &pieces {
+        for &(x, y) in &positions {
+            assert_eq!(
+                board.collides(piece, x, y),
+                array_board.collides(piece, x, y),
+                "collision mismatch for {:?} at ({}, {})",
+                piece.tetromino_type,
+                x,
+                y
+            );
+        }
+    }
+    let mut cleared = board.clone();
+    let mut array_cleared = array_board.clone();
+    assert_eq!(cleared.clear_full_lines(), array_cleared.clear_full_lines());
+    for (y, row) in array_cleared.cells.iter().enumerate() {
+        for (x, &cell) in row.iter().enumerate() {
+            assert_eq!(cleared.get(x, y), cell);
+        }
+    }
+
+    let iterations = 200;
+    let array = bench("collision sweep (array)", iterations, || {
+        collision_sweep(&pieces, &positions, |piece, x, y| {
+            black_box(&array_board).collides(piece, x, y)
+        })
+    });
+    let bits = bench("collision sweep (bitboard)", iterations, || {
+        collision_sweep(&pieces, &positions, |piece, x, y| {
+            black_box(&board).collides(piece, x, y)
+        })
+    });
+    println!(
+        "  speedup: {:.1}x",
+        array.as_secs_f64() / bits.as_secs_f64()
+    );
+
+    let iterations = 100_000;
+    let array = bench("line clear (array)", iterations, || {
+        black_box(black_box(array_board.clone()).clear_full_lines());
+    });
+    let bits = bench("line clear (bitboard)", iterations, || {
+        black_box(black_box(board.clone()).clear_full_lines());
+    });
+    println!(
+        "  speedup: {:.1}x",
+        array.as_secs_f64() / bits.as_secs_f64()
+    );
+}
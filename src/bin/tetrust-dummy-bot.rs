@@ -0,0 +1,36 @@
+//! Minimal Tetris Bot Protocol bot for testing the frontend end to end.
+//! Reads one JSON message per line on stdin and answers on stdout.
+
+use std::io::{stdin, stdout, BufRead, Write};
+use tetrust::client::tbp::{BotMessage, DummyBot, FrontendMessage};
+
+fn reply(message: &BotMessage) -> std::io::Result<()> {
+    let mut out = stdout().lock();
+    writeln!(out, "{}", serde_json::to_string(message)?)?;
+    out.flush()
+}
+
+fn main() -> std::io::Result<()> {
+    let mut bot = DummyBot::new();
+    reply(&DummyBot::info())?;
+
+    for line in stdin().lock().lines() {
+        let message: FrontendMessage = match serde_json::from_str(&line?) {
+            Ok(message) => message,
+            Err(err) => {
+                reply(&BotMessage::Error {
+                    reason: err.to_string(),
+                })?;
+                continue;
+            }
+        };
+        let quit = matches!(message, FrontendMessage::Quit);
+        if let Some(response) = bot.handle(message) {
+            reply(&response)?;
+        }
+        if quit {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,16 @@
+//! Headless lobby server for online play: `tetrust-server [port]`.
+
+use tetrust::client::net::DEFAULT_PORT;
+use tetrust::server::Server;
+
+fn main() -> std::io::Result<()> {
+    let port = match std::env::args().nth(1) {
+        Some(port) => port.parse().map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "port must be a number")
+        })?,
+        None => DEFAULT_PORT,
+    };
+    let server = Server::bind(("0.0.0.0", port))?;
+    println!("Lobby server listening on {}", server.local_addr()?);
+    server.run()
+}
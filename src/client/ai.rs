@@ -0,0 +1,435 @@
+use crate::client::board::Board;
+use crate::client::game::Game;
+use crate::client::input::{Controller, Input};
+use crate::client::rules::{self, RotationSystem};
+use crate::client::search::{self, find_placements, Placement};
+use crate::client::tetromino::Tetromino;
+use std::collections::VecDeque;
+
+/// Relative importance of each board feature. Penalties are negative.
+#[derive(Clone, Copy)]
+pub struct Weights {
+    pub aggregate_height: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+    pub wells: f64,
+    pub lines: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            aggregate_height: -0.51,
+            holes: -0.36,
+            bumpiness: -0.18,
+            wells: -0.1,
+            lines: 0.76,
+        }
+    }
+}
+
+/// Scores a board after a placement has locked and `lines_cleared` rows
+/// were removed. Higher is better.
+pub fn evaluate(board: &Board, lines_cleared: usize, weights: &Weights) -> f64 {
+    let heights = board.column_heights();
+
+    let aggregate_height: usize = heights.iter().sum();
+
+    let holes = board.holes();
+
+    let bumpiness: usize = heights.windows(2).map(|w| w[0].abs_diff(w[1])).sum();
+
+    // The walls count as full-height columns.
+    let wall = board.height();
+    let mut wells = 0;
+    for (x, &height) in heights.iter().enumerate() {
+        let left = if x == 0 { wall } else { heights[x - 1] };
+        let right = heights.get(x + 1).copied().unwrap_or(wall);
+        wells += left.min(right).saturating_sub(height);
+    }
+
+    weights.aggregate_height * aggregate_height as f64
+        + weights.holes * holes as f64
+        + weights.bumpiness * bumpiness as f64
+        + weights.wells * wells as f64
+        + weights.lines * lines_cleared as f64
+}
+
+/// Every placement reachable this turn, with a leading `Input::Hold` on
+/// those that use the hold piece (or the next piece if hold is empty).
+pub fn generate_moves(game: &Game) -> Vec<Placement> {
+    let swapped = game.can_hold().then(|| match game.held_piece() {
+        Some(held_type) => Tetromino::new(held_type),
+        None => game.next_piece().clone(),
+    });
+    generate_placements(
+        game.rules().rotation,
+        game.board(),
+        game.current_piece(),
+        game.piece_position(),
+        swapped.as_ref(),
+    )
+}
+
+/// Board-level move generator: placements for `current` starting at `from`,
+/// plus placements for `swapped` from spawn behind an `Input::Hold`.
+pub fn generate_placements(
+    rotation: RotationSystem,
+    board: &Board,
+    current: &Tetromino,
+    from: (i32, i32),
+    swapped: Option<&Tetromino>,
+) -> Vec<Placement> {
+    let mut moves = find_placements(rotation, board, current, from.0, from.1, true);
+
+    if let Some(swapped) = swapped {
+        let (spawn_x, spawn_y) = rules::spawn_position(board, swapped);
+        for mut placement in find_placements(rotation, board, swapped, spawn_x, spawn_y, true) {
+            placement.inputs.insert(0, Input::Hold);
+            moves.push(placement);
+        }
+    }
+
+    moves
+}
+
+/// Returns the highest scoring of `placements` on `board`.
+pub fn choose(board: &Board, placements: Vec<Placement>, weights: &Weights) -> Option<Placement> {
+    placements
+        .into_iter()
+        .map(|placement| {
+            let mut after = board.clone();
+            after.place(&placement.piece, placement.x, placement.y);
+            let lines_cleared = after.clear_full_lines();
+            (evaluate(&after, lines_cleared, weights), placement)
+        })
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, placement)| placement)
+}
+
+/// Picks the highest scoring placement for the current piece and returns the
+/// inputs that perform it.
+pub fn best_move(game: &Game, weights: &Weights) -> Option<Vec<Input>> {
+    choose(game.board(), generate_moves(game), weights).map(|placement| placement.inputs)
+}
+
+/// Where the current piece is, and which piece it is.
+#[derive(Clone, PartialEq)]
+struct PieceState {
+    pieces_dealt: u64,
+    piece: Tetromino,
+    x: i32,
+    y: i32,
+}
+
+impl PieceState {
+    fn of(game: &Game) -> Self {
+        let (x, y) = game.piece_position();
+        Self {
+            pieces_dealt: game.pieces_dealt(),
+            piece: game.current_piece().clone(),
+            x,
+            y,
+        }
+    }
+
+    // The state `game` is in once `input` is applied, or `None` if the
+    // piece locks.
+    fn after(mut self, game: &Game, input: Input) -> Option<Self> {
+        match input {
+            Input::HardDrop => return None,
+            Input::Hold if game.can_hold() => {
+                self.piece = match game.held_piece() {
+                    Some(held_type) => Tetromino::new(held_type),
+                    None => {
+                        self.pieces_dealt += 1;
+                        game.next_piece().clone()
+                    }
+                };
+                (self.x, self.y) = rules::spawn_position(game.board(), &self.piece);
+            }
+            _ => {
+                let rotation = game.rules().rotation;
+                if let Some((piece, x, y)) =
+                    search::step(rotation, game.board(), &self.piece, self.x, self.y, input)
+                {
+                    (self.piece, self.x, self.y) = (piece, x, y);
+                }
+            }
+        }
+        Some(self)
+    }
+}
+
+/// Inputs worked out for the current piece, played one per frame. Gravity
+/// or a lock can move the piece between two inputs, so each input is only
+/// handed out while the piece is where the plan left it.
+#[derive(Default)]
+pub struct Plan {
+    inputs: VecDeque<Input>,
+    expected: Option<PieceState>,
+}
+
+impl Plan {
+    pub fn new(game: &Game, inputs: Vec<Input>) -> Self {
+        Self {
+            inputs: inputs.into(),
+            expected: Some(PieceState::of(game)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// Whether the piece in `game` is still the one, and where, the next
+    /// input expects.
+    pub fn is_current(&self, game: &Game) -> bool {
+        self.expected
+            .as_ref()
+            .is_some_and(|expected| *expected == PieceState::of(game))
+    }
+
+    /// The next input, or `None` once the plan is used up or out of date.
+    pub fn next(&mut self, game: &Game) -> Option<Input> {
+        if !self.is_current(game) {
+            self.clear();
+        }
+        let input = self.inputs.pop_front()?;
+        self.expected = PieceState::of(game).after(game, input);
+        Some(input)
+    }
+
+    pub fn clear(&mut self) {
+        self.inputs.clear();
+        self.expected = None;
+    }
+}
+
+/// Feeds a `Game` one input at a time so the bot's play can be watched.
+pub struct AiPlayer {
+    weights: Weights,
+    plan: Plan,
+}
+
+impl AiPlayer {
+    pub fn new(weights: Weights) -> Self {
+        Self {
+            weights,
+            plan: Plan::default(),
+        }
+    }
+}
+
+impl Controller for AiPlayer {
+    fn next_input(&mut self, game: &Game) -> Option<Input> {
+        if let Some(input) = self.plan.next(game) {
+            return Some(input);
+        }
+        self.plan = Plan::new(game, best_move(game, &self.weights)?);
+        self.plan.next(game)
+    }
+
+    fn reset(&mut self) {
+        self.plan.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::attack::ClearKind;
+    use crate::client::board::{Cell, DEFAULT_HEIGHT, DEFAULT_WIDTH};
+    use crate::client::game::GameMode;
+    use crate::client::rules::RuleSet;
+    use crate::client::tetromino::TetrominoType;
+    use crate::client::timing::Timing;
+
+    // A game on the standard board under guideline rules, without delays.
+    fn game() -> Game {
+        let mut game = Game::with_rules(3, RuleSet::GUIDELINE);
+        game.set_timing(Timing::INSTANT);
+        game.start(GameMode::WatchAi);
+        game
+    }
+
+    // A standard board with `rows` at the bottom, `X` for a filled cell.
+    fn board(rows: &[&str]) -> Board {
+        let mut board = Board::with_size(DEFAULT_WIDTH, DEFAULT_HEIGHT);
+        let top = board.height() - rows.len();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == 'X' {
+                    board.set(x, top + y, Cell::Filled);
+                }
+            }
+        }
+        board
+    }
+
+    // Placements for a freshly spawned `kind`, and for `swapped` behind a
+    // hold.
+    fn placements(
+        board: &Board,
+        kind: TetrominoType,
+        swapped: Option<TetrominoType>,
+    ) -> Vec<Placement> {
+        let piece = Tetromino::new(kind);
+        let swapped = swapped.map(Tetromino::new);
+        let from = rules::spawn_position(board, &piece);
+        generate_placements(RotationSystem::Srs, board, &piece, from, swapped.as_ref())
+    }
+
+    // The first input of a plan that needs more than one.
+    fn start_long_plan(game: &mut Game, ai: &mut AiPlayer) {
+        loop {
+            let input = ai.next_input(game).unwrap();
+            game.apply_input(input);
+            if !ai.plan.is_empty() {
+                return;
+            }
+        }
+    }
+
+    #[test]
+    fn a_plan_goes_stale_when_gravity_moves_the_piece() {
+        let mut game = game();
+        let mut plan = Plan::new(&game, vec![Input::Left, Input::RotateCw, Input::HardDrop]);
+        let input = plan.next(&game).unwrap();
+        game.apply_input(input);
+        assert!(plan.is_current(&game));
+
+        game.tick();
+        assert!(!plan.is_current(&game));
+        assert_eq!(plan.next(&game), None);
+    }
+
+    #[test]
+    fn does_not_carry_a_plan_over_to_the_next_piece() {
+        let mut game = game();
+        let mut ai = AiPlayer::new(Weights::default());
+        start_long_plan(&mut game, &mut ai);
+
+        // As a tick with no lock delay would.
+        game.hard_drop();
+        let replanned = best_move(&game, &Weights::default()).unwrap();
+        assert_eq!(ai.next_input(&game), Some(replanned[0]));
+    }
+
+    #[test]
+    fn follows_its_plan_while_the_piece_stays_put() {
+        let mut game = game();
+        let mut ai = AiPlayer::new(Weights::default());
+        let plan = best_move(&game, &Weights::default()).unwrap();
+        for &planned in &plan {
+            let input = ai.next_input(&game).unwrap();
+            assert_eq!(input, planned);
+            game.apply_input(input);
+        }
+    }
+
+    #[test]
+    fn every_column_and_orientation_is_generated_once() {
+        use TetrominoType::*;
+        let empty = board(&[]);
+        // Rotations that cover the same cells are one placement.
+        for (kind, count) in [(O, 9), (I, 17), (S, 17), (Z, 17), (T, 34), (J, 34), (L, 34)] {
+            assert_eq!(placements(&empty, kind, None).len(), count, "{:?}", kind);
+        }
+
+        let moves = placements(&empty, T, Some(O));
+        assert_eq!(moves.len(), 34 + 9);
+        let held = moves.iter().filter(|m| m.inputs[0] == Input::Hold);
+        assert!(held.map(|m| m.piece.tetromino_type).eq([O; 9]));
+
+        // On a game, the hold slot is empty so the next piece is offered.
+        let game = game();
+        let next = game.next_piece().tetromino_type;
+        let moves = generate_moves(&game);
+        assert!(moves
+            .iter()
+            .filter(|m| m.inputs[0] == Input::Hold)
+            .all(|m| m.piece.tetromino_type == next));
+    }
+
+    #[test]
+    fn slots_under_an_overhang_are_reached_with_a_spin() {
+        let slot = board(&["XXX.......", "XX...XXXXX", "XXX.XXXXXX"]);
+        let bottom = slot.height() as i32 - 1;
+        let cells = vec![
+            (2, bottom - 1),
+            (3, bottom - 1),
+            (3, bottom),
+            (4, bottom - 1),
+        ];
+        let moves = placements(&slot, TetrominoType::T, None);
+        let spin = moves
+            .iter()
+            .find(|m| m.cells() == cells)
+            .expect("the slot is reachable");
+        assert!(spin.inputs.contains(&Input::SoftDrop));
+
+        // Playing the inputs fills the slot, clearing both rows.
+        let mut game = game();
+        game.load_position(slot.clone(), None, &[TetrominoType::T]);
+        for &input in &spin.inputs {
+            game.apply_input(input);
+        }
+        assert_eq!(game.lines(), 2);
+        assert!(matches!(
+            game.last_clear(),
+            ClearKind::TSpin(2) | ClearKind::TSpinMini(2)
+        ));
+
+        // The shortest sequences a player could drop it with cannot.
+        let piece = Tetromino::new(TetrominoType::T);
+        let (x, y) = rules::spawn_position(&slot, &piece);
+        let dropped = find_placements(RotationSystem::Srs, &slot, &piece, x, y, false);
+        assert!(dropped.iter().all(|m| m.cells() != cells));
+    }
+
+    #[test]
+    fn evaluation_weighs_each_feature() {
+        // Heights 2, 0, 3 and 1 on a 4-wide board, with a hole at the
+        // bottom of the third column.
+        let mut board = Board::with_size(4, 4);
+        let bottom = board.height() - 1;
+        for (x, y) in [(0, 0), (0, 1), (2, 1), (2, 2), (3, 0)] {
+            board.set(x, bottom - y, Cell::Filled);
+        }
+        let only = |weight: usize| {
+            let mut weights = [0.0; 5];
+            weights[weight] = 1.0;
+            let [aggregate_height, holes, bumpiness, wells, lines] = weights;
+            evaluate(
+                &board,
+                2,
+                &Weights {
+                    aggregate_height,
+                    holes,
+                    bumpiness,
+                    wells,
+                    lines,
+                },
+            )
+        };
+        // Wells: two in the second column, and two between the last column
+        // and the wall.
+        assert_eq!([0, 1, 2, 3, 4].map(only), [6.0, 1.0, 7.0, 4.0, 2.0]);
+
+        let empty = Board::with_size(4, 4);
+        assert_eq!(evaluate(&empty, 0, &Weights::default()), 0.0);
+        assert!(evaluate(&board, 0, &Weights::default()) < 0.0);
+    }
+
+    #[test]
+    fn chooses_the_placement_that_clears_the_most() {
+        let well = board(&["XXXXXXXXX.", "XXXXXXXXX.", "XXXXXXXXX.", "XXXXXXXXX."]);
+        let bottom = well.height() as i32 - 1;
+        let moves = placements(&well, TetrominoType::I, None);
+        let best = choose(&well, moves, &Weights::default()).unwrap();
+        let column: Vec<(i32, i32)> = (bottom - 3..=bottom).map(|y| (9, y)).collect();
+        assert_eq!(best.cells(), column);
+        assert!(choose(&well, Vec::new(), &Weights::default()).is_none());
+    }
+}
@@ -0,0 +1,245 @@
+use crate::client::board::{Board, Cell};
+use crate::client::tetromino::{Tetromino, TetrominoType};
+use serde::{Deserialize, Serialize};
+
+/// What a single lock achieved, used for attack, scoring and display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClearKind {
+    None,
+    Single,
+    Double,
+    Triple,
+    Tetris,
+    /// T-spin where only one front corner is occupied; carries lines cleared.
+    TSpinMini(u8),
+    /// Full T-spin; carries lines cleared (0-3).
+    TSpin(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+impl ClearKind {
+    pub fn new(lines: usize, spin: Spin) -> Self {
+        match (spin, lines) {
+            (Spin::Full, lines) => ClearKind::TSpin(lines as u8),
+            (Spin::Mini, lines) => ClearKind::TSpinMini(lines as u8),
+            (Spin::None, 0) => ClearKind::None,
+            (Spin::None, 1) => ClearKind::Single,
+            (Spin::None, 2) => ClearKind::Double,
+            (Spin::None, 3) => ClearKind::Triple,
+            (Spin::None, _) => ClearKind::Tetris,
+        }
+    }
+
+    pub fn lines(self) -> usize {
+        match self {
+            ClearKind::None => 0,
+            ClearKind::Single => 1,
+            ClearKind::Double => 2,
+            ClearKind::Triple => 3,
+            ClearKind::Tetris => 4,
+            ClearKind::TSpinMini(lines) | ClearKind::TSpin(lines) => lines as usize,
+        }
+    }
+
+    /// Tetrises and line-clearing T-spins keep a back-to-back chain going.
+    pub fn is_difficult(self) -> bool {
+        match self {
+            ClearKind::Tetris => true,
+            ClearKind::TSpinMini(lines) | ClearKind::TSpin(lines) => lines > 0,
+            _ => false,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ClearKind::None => "",
+            ClearKind::Single => "Single",
+            ClearKind::Double => "Double",
+            ClearKind::Triple => "Triple",
+            ClearKind::Tetris => "Tetris",
+            ClearKind::TSpinMini(0) => "T-Spin Mini",
+            ClearKind::TSpinMini(1) => "T-Spin Mini Single",
+            ClearKind::TSpinMini(_) => "T-Spin Mini Double",
+            ClearKind::TSpin(0) => "T-Spin",
+            ClearKind::TSpin(1) => "T-Spin Single",
+            ClearKind::TSpin(2) => "T-Spin Double",
+            ClearKind::TSpin(_) => "T-Spin Triple",
+        }
+    }
+}
+
+/// Bonus lines for each step of a combo, indexed by combo count.
+const COMBO_TABLE: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+const PERFECT_CLEAR_BONUS: u32 = 10;
+
+/// Lines of garbage sent by a clear, following the guideline attack table.
+/// `combo` counts consecutive clearing locks before this one.
+pub fn attack(kind: ClearKind, combo: u32, back_to_back: bool, perfect_clear: bool) -> u32 {
+    if kind.lines() == 0 {
+        return 0;
+    }
+
+    let base = match kind {
+        ClearKind::None | ClearKind::Single => 0,
+        ClearKind::Double => 1,
+        ClearKind::Triple => 2,
+        ClearKind::Tetris => 4,
+        ClearKind::TSpinMini(lines) => lines as u32 - 1,
+        ClearKind::TSpin(lines) => lines as u32 * 2,
+    };
+    let b2b_bonus = (back_to_back && kind.is_difficult()) as u32;
+    let combo_bonus = COMBO_TABLE[(combo as usize).min(COMBO_TABLE.len() - 1)];
+    let pc_bonus = if perfect_clear {
+        PERFECT_CLEAR_BONUS
+    } else {
+        0
+    };
+
+    base + b2b_bonus + combo_bonus + pc_bonus
+}
+
+/// Classifies a T piece about to lock at (`x`, `y`) with the three-corner
+/// rule. Only meaningful when the piece's last successful move was a rotation.
+pub fn detect_spin(board: &Board, piece: &Tetromino, x: i32, y: i32) -> Spin {
+    if piece.tetromino_type != TetrominoType::T {
+        return Spin::None;
+    }
+
+    let minos: Vec<(i32, i32)> = piece.minos().map(|(dx, dy)| (x + dx, y + dy)).collect();
+    let neighbours = |&(cx, cy): &(i32, i32)| {
+        minos
+            .iter()
+            .filter(|&&(mx, my)| (mx - cx).abs() + (my - cy).abs() == 1)
+            .count()
+    };
+    let Some(&(cx, cy)) = minos.iter().find(|mino| neighbours(mino) == 3) else {
+        return Spin::None;
+    };
+
+    // The nub points away from the one side of the centre with no mino.
+    let (back_x, back_y) = [(0, 1), (0, -1), (1, 0), (-1, 0)]
+        .into_iter()
+        .find(|&(dx, dy)| !minos.contains(&(cx + dx, cy + dy)))
+        .unwrap_or((0, 1));
+
+    let occupied = |px: i32, py: i32| {
+        px < 0
+            || px >= board.width() as i32
+            || py >= board.height() as i32
+            || (py >= 0 && board.get(px as usize, py as usize) == Cell::Filled)
+    };
+    let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+    let filled = corners
+        .iter()
+        .filter(|&&(dx, dy)| occupied(cx + dx, cy + dy))
+        .count();
+    if filled < 3 {
+        return Spin::None;
+    }
+
+    // Front corners are on the opposite side from the back.
+    let front_filled = corners
+        .iter()
+        .filter(|&&(dx, dy)| dx * back_x + dy * back_y < 0)
+        .filter(|&&(dx, dy)| occupied(cx + dx, cy + dy))
+        .count();
+    if front_filled == 2 {
+        Spin::Full
+    } else {
+        Spin::Mini
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 10x20 board with `rows` at the bottom, `X` for a filled cell.
+    fn board(rows: &[&str]) -> Board {
+        let mut board = Board::new();
+        let top = board.height() - rows.len();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == 'X' {
+                    board.set(x, top + y, Cell::Filled);
+                }
+            }
+        }
+        board
+    }
+
+    // A T pointing down; its centre is at (2, 1) of the box.
+    fn t_down() -> Tetromino {
+        let mut piece = Tetromino::new(TetrominoType::T);
+        piece.rotate_clockwise();
+        piece.rotate_clockwise();
+        piece
+    }
+
+    #[test]
+    fn detects_spins_by_the_three_corner_rule() {
+        let y = Board::new().height() as i32 - 3;
+        let double = board(&["XXXX......", "XXX...XXXX", "XXXX.XXXXX"]);
+        assert_eq!(detect_spin(&double, &t_down(), 2, y), Spin::Full);
+
+        // Only one of the corners in front of the nub is filled.
+        let mini = board(&["XXXX.XXXXX", "XXX...XXXX", "XXXX..XXXX"]);
+        assert_eq!(detect_spin(&mini, &t_down(), 2, y), Spin::Mini);
+
+        let open = board(&["..........", "XXX...XXXX", "XXXX..XXXX"]);
+        assert_eq!(detect_spin(&open, &t_down(), 2, y), Spin::None);
+
+        let o = Tetromino::new(TetrominoType::O);
+        assert_eq!(detect_spin(&double, &o, 2, y), Spin::None);
+    }
+
+    #[test]
+    fn classifies_clears() {
+        assert_eq!(ClearKind::new(0, Spin::None), ClearKind::None);
+        assert_eq!(ClearKind::new(2, Spin::None), ClearKind::Double);
+        assert_eq!(ClearKind::new(4, Spin::None), ClearKind::Tetris);
+        assert_eq!(ClearKind::new(0, Spin::Full), ClearKind::TSpin(0));
+        assert_eq!(ClearKind::new(1, Spin::Mini), ClearKind::TSpinMini(1));
+        assert_eq!(ClearKind::TSpin(2).label(), "T-Spin Double");
+        assert_eq!(ClearKind::TSpin(3).lines(), 3);
+        assert!(ClearKind::Tetris.is_difficult());
+        assert!(ClearKind::TSpinMini(1).is_difficult());
+        assert!(!ClearKind::TSpin(0).is_difficult());
+        assert!(!ClearKind::Triple.is_difficult());
+    }
+
+    #[test]
+    fn follows_the_guideline_attack_table() {
+        let plain = |kind| attack(kind, 0, false, false);
+        assert_eq!(plain(ClearKind::Single), 0);
+        assert_eq!(plain(ClearKind::Double), 1);
+        assert_eq!(plain(ClearKind::Triple), 2);
+        assert_eq!(plain(ClearKind::Tetris), 4);
+        assert_eq!(plain(ClearKind::TSpinMini(1)), 0);
+        assert_eq!(plain(ClearKind::TSpinMini(2)), 1);
+        assert_eq!(plain(ClearKind::TSpin(1)), 2);
+        assert_eq!(plain(ClearKind::TSpin(2)), 4);
+        assert_eq!(plain(ClearKind::TSpin(3)), 6);
+
+        // Back-to-back only counts for difficult clears.
+        assert_eq!(attack(ClearKind::Tetris, 0, true, false), 5);
+        assert_eq!(attack(ClearKind::TSpin(2), 0, true, false), 5);
+        assert_eq!(attack(ClearKind::Triple, 0, true, false), 2);
+
+        assert_eq!(attack(ClearKind::Single, 2, false, false), 1);
+        assert_eq!(attack(ClearKind::Single, 11, false, false), 5);
+        assert_eq!(attack(ClearKind::Single, 50, false, false), 5);
+        assert_eq!(attack(ClearKind::Double, 0, false, true), 11);
+
+        // Nothing is sent without a line cleared, whatever the combo.
+        assert_eq!(attack(ClearKind::None, 5, true, true), 0);
+        assert_eq!(attack(ClearKind::TSpin(0), 5, true, false), 0);
+    }
+}
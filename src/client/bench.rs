@@ -0,0 +1,131 @@
+//! Headless bot benchmarks: seeded games played as fast as the bot can
+//! manage, with the results a bot author cares about.
+
+use crate::client::config::Config;
+use crate::client::game::{Game, GameMode, GameState, TopOut};
+use crate::client::input::Controller;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Pieces per game when no limit is given.
+pub const DEFAULT_PIECES: u64 = 1000;
+
+/// How long a controller may go without an input before gravity drops the
+/// piece, as it would in a real game.
+const IDLE_TICK: Duration = Duration::from_millis(500);
+
+/// How one benchmark game went.
+pub struct BenchResult {
+    pub seed: u64,
+    pub pieces: u64,
+    pub lines: u32,
+    pub score: u32,
+    pub attack: u32,
+    pub top_out: Option<TopOut>,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    pub fn pieces_per_second(&self) -> f64 {
+        self.pieces as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "seed {}: {} pieces, {} lines, {} attack, score {}, {:.2}s ({:.0} pieces/s)",
+            self.seed,
+            self.pieces,
+            self.lines,
+            self.attack,
+            self.score,
+            self.elapsed.as_secs_f64(),
+            self.pieces_per_second()
+        )?;
+        if let Some(reason) = self.top_out {
+            write!(f, ", {}", reason.label())?;
+        }
+        Ok(())
+    }
+}
+
+/// Plays one `mode` game from `seed`, set up by `config`, with
+/// `controller` until it tops out, the controller fails or `pieces` pieces
+/// have been placed. Line clear and entry delays run instantly rather than
+/// in real time, and the lock delay not at all, as bots hard drop every
+/// piece.
+pub fn play(
+    controller: &mut dyn Controller,
+    config: &Config,
+    mode: GameMode,
+    seed: u64,
+    pieces: u64,
+) -> BenchResult {
+    let mut game = Game::with_config(seed, config, mode);
+    game.start(mode);
+    controller.reset();
+
+    let start = Instant::now();
+    let mut last_move = Instant::now();
+    while game.get_state() == GameState::Playing && placed(&game) < pieces {
+        if !game.piece_active() {
+            game.frame();
+            continue;
+        }
+        match controller.next_input(&game) {
+            None if controller.error().is_some() => break,
+            Some(input) => {
+                game.apply_input(input);
+                last_move = Instant::now();
+            }
+            // An external bot may still be thinking.
+            None if last_move.elapsed() < IDLE_TICK => thread::sleep(Duration::from_millis(1)),
+            None => {
+                game.tick();
+                last_move = Instant::now();
+            }
+        }
+    }
+
+    BenchResult {
+        seed,
+        pieces: placed(&game),
+        lines: game.lines(),
+        score: game.score(),
+        attack: game.attack_sent(),
+        top_out: game.top_out_reason(),
+        elapsed: start.elapsed(),
+    }
+}
+
+// Every piece dealt so far has been placed, except the active one and any
+// in the hold.
+fn placed(game: &Game) -> u64 {
+    game.pieces_dealt() - 1 - game.held_piece().is_some() as u64
+}
+
+/// Totals over a benchmark run.
+pub fn summary(results: &[BenchResult]) -> String {
+    let pieces: u64 = results.iter().map(|result| result.pieces).sum();
+    let lines: u32 = results.iter().map(|result| result.lines).sum();
+    let attack: u32 = results.iter().map(|result| result.attack).sum();
+    let elapsed: Duration = results.iter().map(|result| result.elapsed).sum();
+    let top_outs = results
+        .iter()
+        .filter(|result| result.top_out.is_some())
+        .count();
+    format!(
+        "{} game{}: {} pieces, {} lines, {} attack ({:.3} attack/piece), {} topped out, {:.0} pieces/s",
+        results.len(),
+        if results.len() == 1 { "" } else { "s" },
+        pieces,
+        lines,
+        attack,
+        attack as f64 / pieces.max(1) as f64,
+        top_outs,
+        pieces as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+    )
+}
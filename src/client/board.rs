@@ -1,47 +1,446 @@
-pub const WIDTH: usize = 10;
-pub const HEIGHT: usize = 20;
+use crate::client::tetromino::Tetromino;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, PartialEq)]
+/// Size of the standard playfield.
+pub const DEFAULT_WIDTH: usize = 10;
+pub const DEFAULT_HEIGHT: usize = 20;
+
+/// Hidden rows above the visible field. Pieces spawn here, and a stack
+/// can grow into it without topping out.
+pub const BUFFER_HEIGHT: usize = 20;
+
+/// Limits for custom visible sizes: pieces spawn in a 4x4 box, and a row
+/// has to fit in a `Row` with room for a piece hanging over the right wall.
+pub const MIN_WIDTH: usize = 4;
+pub const MAX_WIDTH: usize = Row::BITS as usize - 4;
+pub const MIN_HEIGHT: usize = 4;
+pub const MAX_HEIGHT: usize = 100;
+
+/// Rows the largest board has, hidden buffer included.
+const MAX_ROWS: usize = BUFFER_HEIGHT + MAX_HEIGHT;
+
+/// One board row, with bit `x` set when column `x` is filled.
+pub type Row = u64;
+
+/// Cell-by-cell view of a board for the renderer, which overlays the ghost
+/// and active piece on top of the stored stack. Indexed `[y][x]`.
+pub type CellGrid = Vec<Vec<Cell>>;
+
+/// Parses a `WIDTHxHEIGHT` board size and checks it against the limits.
+pub fn parse_size(size: &str) -> Result<(usize, usize), String> {
+    let parsed = size
+        .split_once(['x', 'X'])
+        .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)));
+    let Some((width, height)) = parsed else {
+        return Err(format!("board size {:?} is not WIDTHxHEIGHT", size));
+    };
+    if !(MIN_WIDTH..=MAX_WIDTH).contains(&width) {
+        return Err(format!(
+            "board width must be {} to {}, not {}",
+            MIN_WIDTH, MAX_WIDTH, width
+        ));
+    }
+    if !(MIN_HEIGHT..=MAX_HEIGHT).contains(&height) {
+        return Err(format!(
+            "board height must be {} to {}, not {}",
+            MIN_HEIGHT, MAX_HEIGHT, height
+        ));
+    }
+    Ok((width, height))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Cell {
     Empty,
     Filled,
     Preview,
+    /// The board editor's cursor.
+    Cursor,
+    /// Warns that the stack is close to the top of the visible field.
+    Danger,
 }
 
 impl Cell {
-    pub fn to_string(&self) -> &str {
+    pub fn to_string(self) -> &'static str {
         match self {
             Cell::Empty => "⬜",
             Cell::Filled => "⬛",
             Cell::Preview => "🟦",
+            Cell::Cursor => "🟨",
+            Cell::Danger => "🟥",
         }
     }
 }
 
-#[derive(Clone)]
+/// The locked stack, stored as one bitmask per row so that collision checks
+/// and line clears are a handful of bitwise operations.
+///
+/// Row 0 is the top of the hidden buffer; the visible field is the bottom
+/// `visible_height()` rows. The rows live inline, sized for the largest
+/// board, so the AI and search can clone boards without allocating.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "StoredBoard", into = "StoredBoard")]
 pub struct Board {
-    cells: [[Cell; WIDTH]; HEIGHT],
+    width: usize,
+    buffer: usize,
+    height: usize,
+    /// Every column outside the board, for testing a shifted piece row.
+    walls: Row,
+    /// Every row above this one is empty, so scans of the stack can start
+    /// here instead of at the top of the buffer. Emptying cells leaves it
+    /// where it is, so it can be lower than the highest filled row.
+    top: usize,
+    /// Rows from `height` on are unused and stay empty.
+    rows: [Row; MAX_ROWS],
+}
+
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width
+            && self.buffer == other.buffer
+            && self.height == other.height
+            && self.rows == other.rows
+    }
+}
+
+/// How a board is saved and sent: only the rows it uses.
+#[derive(Serialize, Deserialize)]
+struct StoredBoard {
+    width: usize,
+    buffer: usize,
+    rows: Vec<Row>,
+}
+
+impl From<Board> for StoredBoard {
+    fn from(board: Board) -> Self {
+        Self {
+            width: board.width,
+            buffer: board.buffer,
+            rows: board.rows[..board.height].to_vec(),
+        }
+    }
+}
+
+impl TryFrom<StoredBoard> for Board {
+    type Error = String;
+
+    fn try_from(stored: StoredBoard) -> Result<Self, String> {
+        if !(1..=MAX_WIDTH).contains(&stored.width) {
+            return Err(format!("board width {} is not supported", stored.width));
+        }
+        if stored.rows.len() > MAX_ROWS || stored.buffer > stored.rows.len() {
+            return Err(format!(
+                "board height {} is not supported",
+                stored.rows.len()
+            ));
+        }
+        let mut board = Self::with_size(stored.width, 0);
+        board.buffer = stored.buffer;
+        board.height = stored.rows.len();
+        board.rows[..board.height].copy_from_slice(&stored.rows);
+        board.top = stored
+            .rows
+            .iter()
+            .position(|&row| row != 0)
+            .unwrap_or(board.height);
+        Ok(board)
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Board {
+    /// An empty standard board: 10x20 visible under the hidden buffer.
     pub fn new() -> Self {
+        Self::with_size(DEFAULT_WIDTH, DEFAULT_HEIGHT)
+    }
+
+    /// An empty board with `visible_height` rows showing under the buffer.
+    pub fn with_size(width: usize, visible_height: usize) -> Self {
+        assert!(
+            width <= MAX_WIDTH && visible_height <= MAX_HEIGHT,
+            "board size {}x{} is over the limits",
+            width,
+            visible_height
+        );
+        let full_row = Row::MAX >> (Row::BITS as usize - width);
         Self {
-            cells: [[Cell::Empty; WIDTH]; HEIGHT],
+            width,
+            buffer: BUFFER_HEIGHT,
+            height: BUFFER_HEIGHT + visible_height,
+            walls: !full_row,
+            top: BUFFER_HEIGHT + visible_height,
+            rows: [0; MAX_ROWS],
         }
     }
 
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// All rows, hidden buffer included.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn visible_height(&self) -> usize {
+        self.height - self.buffer
+    }
+
+    /// Rows of hidden buffer, which is also the index of the first visible row.
+    pub fn buffer_height(&self) -> usize {
+        self.buffer
+    }
+
+    /// An empty board of the same size.
+    pub fn cleared(&self) -> Self {
+        Self::with_size(self.width(), self.visible_height())
+    }
+
+    /// A row with every column filled.
+    pub fn full_row(&self) -> Row {
+        !self.walls
+    }
+
+    /// Whether (`x`, `y`) is a cell of the board.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width() && (y as usize) < self.height()
+    }
+
     pub fn get(&self, x: usize, y: usize) -> Cell {
-        self.cells[y][x]
+        if self.rows[y] & 1 << x != 0 {
+            Cell::Filled
+        } else {
+            Cell::Empty
+        }
     }
 
+    /// Fills or empties a cell. Only `Cell::Filled` is stored; `Preview` and
+    /// `Cursor` are rendering overlays and clear the cell like `Empty`.
     pub fn set(&mut self, x: usize, y: usize, cell: Cell) {
-        self.cells[y][x] = cell;
+        if cell == Cell::Filled {
+            self.rows[y] |= 1 << x;
+            self.top = self.top.min(y);
+        } else {
+            self.rows[y] &= !(1 << x);
+        }
+    }
+
+    pub fn row(&self, y: usize) -> Row {
+        self.rows[y]
+    }
+
+    /// Renderer adapter: expands the visible rows' bitmasks into a grid of
+    /// cells, so grid row 0 is board row `buffer_height()`.
+    pub fn to_cells(&self) -> CellGrid {
+        let mut grid = vec![vec![Cell::Empty; self.width()]; self.visible_height()];
+        for (y, row) in grid.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                *cell = self.get(x, self.buffer + y);
+            }
+        }
+        grid
+    }
+
+    /// Returns true if `piece` placed with its top-left corner at (`x`, `y`)
+    /// would leave the board or overlap a filled cell. The top of the buffer
+    /// is a ceiling like the walls and floor.
+    pub fn collides(&self, piece: &Tetromino, x: i32, y: i32) -> bool {
+        // Past these columns every cell of the 4x4 box is outside a wall.
+        // Within them a shifted piece row still fits in a `Row`, so the
+        // right wall is just more filled bits.
+        if x <= -4 || x >= self.width as i32 {
+            return true;
+        }
+        let left = (-x).max(0) as u32;
+        let right = x.max(0) as u32;
+        // Cells left of column 0 are the bits the right shift drops.
+        let dropped: Row = (1 << left) - 1;
+        for (dy, &mask) in piece.row_masks().iter().enumerate() {
+            if mask == 0 {
+                continue;
+            }
+            let mask = mask as Row;
+            let board_y = y + dy as i32;
+            if mask & dropped != 0 || board_y < 0 || board_y >= self.height as i32 {
+                return true;
+            }
+            if (mask >> left << right) & (self.walls | self.rows[board_y as usize]) != 0 {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Writes the piece's minos into the board, ignoring any that fall
+    /// outside it.
+    pub fn place(&mut self, piece: &Tetromino, x: i32, y: i32) {
+        for (dx, dy) in piece.minos() {
+            let board_x = x + dx;
+            let board_y = y + dy;
+            if self.contains(board_x, board_y) {
+                self.rows[board_y as usize] |= 1 << board_x;
+                self.top = self.top.min(board_y as usize);
+            }
+        }
     }
 
     pub fn clear_line(&mut self, y: usize) {
-        for row in (1..=y).rev() {
-            self.cells[row] = self.cells[row - 1];
+        self.rows.copy_within(0..y, 1);
+        self.rows[0] = 0;
+        if y >= self.top {
+            self.top = (self.top + 1).min(self.height);
+        }
+    }
+
+    /// Indices of the rows that are completely filled, top to bottom.
+    pub fn full_rows(&self) -> Vec<usize> {
+        let full = self.full_row();
+        (self.top..self.height)
+            .filter(|&y| self.rows[y] == full)
+            .collect()
+    }
+
+    /// Removes every filled row and returns how many were cleared.
+    pub fn clear_full_lines(&mut self) -> usize {
+        let full = self.full_row();
+        let mut write = self.height;
+        for y in (self.top..self.height).rev() {
+            if self.rows[y] != full {
+                write -= 1;
+                self.rows[write] = self.rows[y];
+            }
+        }
+        // Each cleared row leaves one empty row behind at the top of the
+        // stack; the rows above it were empty already.
+        let cleared = write - self.top;
+        self.rows[self.top..write].fill(0);
+        self.top = write;
+        cleared
+    }
+
+    /// Pushes `lines` garbage rows in from the bottom, each full except for
+    /// column `hole`. Returns true if filled cells were pushed off the top.
+    pub fn add_garbage(&mut self, lines: usize, hole: usize) -> bool {
+        let height = self.height();
+        let lines = lines.min(height);
+        let overflowed = self.rows[self.top.min(lines)..lines]
+            .iter()
+            .any(|&row| row != 0);
+        self.rows.copy_within(lines..height, 0);
+        let garbage = self.full_row() & !(1 << hole);
+        self.rows[height - lines..height].fill(garbage);
+        if lines > 0 {
+            self.top = self.top.saturating_sub(lines).min(height - lines);
         }
-        self.cells[0] = [Cell::Empty; WIDTH];
+        overflowed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows[self.top..self.height].iter().all(|&row| row == 0)
+    }
+
+    /// Height of each column, measured from the floor to its highest filled cell.
+    pub fn column_heights(&self) -> Vec<usize> {
+        let mut heights = vec![0; self.width()];
+        let mut seen: Row = 0;
+        for (y, &row) in self.rows[..self.height].iter().enumerate().skip(self.top) {
+            let mut new = row & !seen;
+            while new != 0 {
+                heights[new.trailing_zeros() as usize] = self.height - y;
+                new &= new - 1;
+            }
+            seen |= row;
+        }
+        heights
+    }
+
+    /// Empty cells with a filled cell somewhere above them.
+    pub fn holes(&self) -> usize {
+        let mut covered: Row = 0;
+        let mut holes = 0;
+        for &row in &self.rows[self.top..self.height] {
+            holes += (covered & !row).count_ones() as usize;
+            covered |= row;
+        }
+        holes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::tetromino::TetrominoType;
+
+    #[test]
+    fn collides_with_walls_floor_and_ceiling() {
+        let board = Board::with_size(MAX_WIDTH, DEFAULT_HEIGHT);
+        // The vertical I sits in column 2 of its box.
+        let mut piece = Tetromino::new(TetrominoType::I);
+        piece.rotate_clockwise();
+        let bottom = board.height() as i32 - 4;
+        assert!(!board.collides(&piece, -2, bottom));
+        assert!(board.collides(&piece, -3, bottom));
+        assert!(!board.collides(&piece, MAX_WIDTH as i32 - 3, bottom));
+        assert!(board.collides(&piece, MAX_WIDTH as i32 - 2, bottom));
+        assert!(board.collides(&piece, 0, bottom + 1));
+        assert!(board.collides(&piece, 0, -1));
+        assert!(board.collides(&piece, i32::MIN, 0));
+        assert!(board.collides(&piece, i32::MAX, 0));
+    }
+
+    #[test]
+    fn collides_with_the_stack() {
+        let mut board = Board::new();
+        let floor = board.height() - 1;
+        board.set(4, floor, Cell::Filled);
+        let piece = Tetromino::new(TetrominoType::O);
+        // The O fills columns 1 and 2 of the top two rows of its box.
+        assert!(board.collides(&piece, 2, floor as i32 - 1));
+        assert!(!board.collides(&piece, 0, floor as i32 - 1));
+    }
+
+    #[test]
+    fn skips_empty_rows_without_losing_the_stack() {
+        let mut board = Board::new();
+        let floor = board.height() - 1;
+        for x in 0..DEFAULT_WIDTH {
+            board.set(x, floor, Cell::Filled);
+        }
+        board.set(0, floor - 1, Cell::Filled);
+        board.set(1, floor - 3, Cell::Filled);
+        assert_eq!(board.full_rows(), vec![floor]);
+        assert_eq!(board.clear_full_lines(), 1);
+        assert_eq!(board.column_heights()[..3], [1, 3, 0]);
+        assert_eq!(board.holes(), 2);
+
+        assert!(!board.add_garbage(2, 5));
+        assert_eq!(board.column_heights()[..3], [3, 5, 2]);
+        assert_eq!(board.holes(), 2);
+
+        board.clear_line(floor);
+        board.clear_line(floor);
+        board.clear_line(floor);
+        assert_eq!(board.column_heights()[..3], [0, 2, 0]);
+        board.set(1, floor - 1, Cell::Empty);
+        assert!(board.is_empty());
+        assert!(board == Board::new());
+    }
+
+    #[test]
+    fn serializes_only_the_rows_in_use() {
+        let mut board = Board::with_size(12, 30);
+        board.set(3, 45, Cell::Filled);
+        let json = serde_json::to_value(&board).unwrap();
+        assert_eq!(json["rows"].as_array().unwrap().len(), board.height());
+        let back: Board = serde_json::from_value(json).unwrap();
+        assert!(back == board);
+
+        let wide = r#"{"width":65,"buffer":20,"rows":[0]}"#;
+        assert!(serde_json::from_str::<Board>(wide).is_err());
     }
 }
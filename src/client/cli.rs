@@ -0,0 +1,434 @@
+//! Command-line options, so scripts can start exactly the scenario they
+//! want instead of going through the title menu. The options are merged
+//! with the config file and the `TETRUST_*` variables into the one
+//! `Config` the game runs with.
+
+use crate::client::bench;
+use crate::client::config::{self, Config, Values};
+use crate::client::game::GameMode;
+use crate::client::layout::Glyphs;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+Usage: tetrust [OPTIONS]
+
+Options:
+      --mode MODE      Start MODE instead of the title screen: marathon,
+                       finesse, puzzles, editor, watch-ai, watch-bot, versus,
+                       host, join, lobby or spectate
+      --seed N         Seed the piece sequence of the first game
+      --fumen DATA     Start a marathon game from a fumen diagram
+      --config FILE    Read settings from FILE instead of the settings
+                       saved from the game (~/.tetrust/config)
+      --renderer NAME  terminal (default), or headless to print a summary
+                       of a --replay without drawing it
+      --glyphs NAME    Board cells: auto (default), wide or compact
+      --record FILE    Record the game to a replay file
+      --replay FILE    Play back a replay file
+      --bench GAMES    Play GAMES seeded games with the bot chosen by --mode
+                       (watch-ai or watch-bot) and print the results
+      --pieces N       Pieces per benchmark game (default 1000)
+  -h, --help           Print this help
+  -V, --version        Print the version
+
+A config file has one `name = value` per line, for the settings board,
+clear_delay, are, glyphs, bot, spectate and home. The matching TETRUST_*
+variable, such as TETRUST_BOARD, overrides each, and --glyphs overrides
+both. Lines starting with # are comments. The bot setting is a command
+line, so quote a path to the bot that has spaces in it.
+
+The rules setting picks a preset: guideline (default), classic or tgm.
+The settings rotation (srs, nintendo, arika), randomizer (bag, classic,
+history, uniform), gravity (guideline, classic, tgm), lock_delay (frames),
+hold (on, off), preview (0-6), scoring (guideline, classic, tgm), ghost
+(on, off) and lock_out (on, off) change one rule of it. Finesse, puzzles,
+watch-bot and matches against other players always use guideline rules.
+Online matches are also always played on a 10x20 board with the default
+delays, so both players get the same game.
+";
+
+/// `--mode` names.
+const MODES: [(&str, GameMode); 11] = [
+    ("marathon", GameMode::Marathon),
+    ("finesse", GameMode::Finesse),
+    ("puzzles", GameMode::Puzzle),
+    ("editor", GameMode::Editor),
+    ("watch-ai", GameMode::WatchAi),
+    ("watch-bot", GameMode::WatchBot),
+    ("versus", GameMode::Versus),
+    ("host", GameMode::HostOnline),
+    ("join", GameMode::JoinOnline),
+    ("lobby", GameMode::Lobby),
+    ("spectate", GameMode::Spectate),
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Renderer {
+    /// The full-screen terminal interface.
+    #[default]
+    Terminal,
+    /// No interface; only results are printed.
+    Headless,
+}
+
+pub enum Command {
+    Run(Options),
+    Help,
+    Version,
+}
+
+#[derive(Default)]
+pub struct Options {
+    pub mode: Option<GameMode>,
+    pub seed: Option<u64>,
+    pub fumen: Option<String>,
+    pub config: Option<PathBuf>,
+    pub renderer: Renderer,
+    pub glyphs: Option<String>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub bench: Option<u32>,
+    pub pieces: Option<u64>,
+}
+
+impl Options {
+    /// Pieces per benchmark game.
+    pub fn pieces(&self) -> u64 {
+        self.pieces.unwrap_or(bench::DEFAULT_PIECES)
+    }
+
+    /// The settings to run with: the config file, or the saved settings if
+    /// none was given, then the `TETRUST_*` variables, then `--glyphs`.
+    pub fn config(&self) -> Result<Config, String> {
+        self.config_from(|name| std::env::var(name).ok())
+    }
+
+    // `config` with the environment variables looked up by `env`.
+    fn config_from(&self, env: impl Fn(&str) -> Option<String>) -> Result<Config, String> {
+        let mut values = match &self.config {
+            Some(path) => load_config(path)?,
+            None => {
+                let home =
+                    env(&config::variable("home")).map_or_else(config::default_home, PathBuf::from);
+                let path = home.join("config");
+                if path.is_file() {
+                    load_config(&path)?
+                } else {
+                    Values::new()
+                }
+            }
+        };
+        for name in config::NAMES {
+            if let Some(value) = env(&config::variable(name)) {
+                values.insert(name.to_string(), value);
+            }
+        }
+        if let Some(glyphs) = &self.glyphs {
+            values.insert("glyphs".to_string(), glyphs.clone());
+        }
+        Config::from_values(values)
+    }
+}
+
+/// Parses the arguments after the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // Both `--seed 7` and `--seed=7`.
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                (flag.to_string(), Some(value.to_string()))
+            }
+            _ => (arg, None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "--mode" => options.mode = Some(parse_mode(&value()?)?),
+            "--seed" => options.seed = Some(parse_number(&flag, &value()?)?),
+            "--fumen" => options.fumen = Some(value()?),
+            "--config" => options.config = Some(value()?.into()),
+            "--renderer" => {
+                options.renderer = match value()?.as_str() {
+                    "terminal" => Renderer::Terminal,
+                    "headless" => Renderer::Headless,
+                    other => {
+                        return Err(format!(
+                            "renderer must be terminal or headless, not {:?}",
+                            other
+                        ))
+                    }
+                }
+            }
+            "--glyphs" => {
+                let name = value()?;
+                Glyphs::parse(&name)?;
+                options.glyphs = Some(name);
+            }
+            "--record" => options.record = Some(value()?.into()),
+            "--replay" => options.replay = Some(value()?.into()),
+            "--bench" => options.bench = Some(parse_number(&flag, &value()?)?),
+            "--pieces" => options.pieces = Some(parse_number(&flag, &value()?)?),
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+    }
+    check(&options)?;
+    Ok(Command::Run(options))
+}
+
+// Rejects combinations that cannot all be honoured.
+fn check(options: &Options) -> Result<(), String> {
+    if options.replay.is_some() {
+        if options.mode.is_some() || options.fumen.is_some() || options.bench.is_some() {
+            return Err("--replay cannot be combined with --mode, --fumen or --bench".to_string());
+        }
+        if options.record.is_some() {
+            return Err("--replay cannot be recorded".to_string());
+        }
+    }
+    if options.bench.is_some() {
+        if !matches!(
+            options.mode,
+            None | Some(GameMode::WatchAi | GameMode::WatchBot)
+        ) {
+            return Err("--bench plays watch-ai or watch-bot".to_string());
+        }
+        if options.fumen.is_some() || options.record.is_some() {
+            return Err("--bench cannot be combined with --fumen or --record".to_string());
+        }
+    } else if options.pieces.is_some() {
+        return Err("--pieces needs --bench".to_string());
+    }
+    if options.fumen.is_some() && !matches!(options.mode, None | Some(GameMode::Marathon)) {
+        return Err("--fumen starts a marathon game".to_string());
+    }
+    if options.renderer == Renderer::Headless && options.replay.is_none() && options.bench.is_none()
+    {
+        return Err("the headless renderer needs --replay or --bench".to_string());
+    }
+    Ok(())
+}
+
+fn parse_mode(name: &str) -> Result<GameMode, String> {
+    MODES
+        .iter()
+        .find(|(mode_name, _)| *mode_name == name)
+        .map(|&(_, mode)| mode)
+        .ok_or_else(|| {
+            let names: Vec<&str> = MODES.iter().map(|(name, _)| *name).collect();
+            format!(
+                "unknown mode {:?}; expected one of {}",
+                name,
+                names.join(", ")
+            )
+        })
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("{} must be a whole number, not {:?}", flag, value))
+}
+
+// Reads the settings in a config file.
+fn load_config(path: &Path) -> Result<Values, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+    let mut values = Values::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let at = || format!("{} line {}", path.display(), number + 1);
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("{}: expected name = value", at()))?;
+        let name = name.trim().to_lowercase();
+        if !config::NAMES.contains(&name.as_str()) {
+            return Err(format!(
+                "{}: unknown setting {:?}; expected one of {}",
+                at(),
+                name,
+                config::NAMES.join(", ")
+            ));
+        }
+        values.insert(name, value.trim().to_string());
+    }
+    Ok(values)
+}
+
+/// Writes `settings`, as `(name, value)` pairs, to the config file at
+/// `path`. Earlier lines for the same settings are replaced; comments and
+/// other settings are kept.
+pub fn save_config(path: &Path, settings: &[(&str, String)]) -> io::Result<()> {
+    let existing = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let mut lines: Vec<String> = existing
+        .lines()
+        .filter(|line| {
+            let name = line
+                .split_once('=')
+                .map(|(name, _)| name.trim().to_lowercase());
+            !settings
+                .iter()
+                .any(|(setting, _)| name.as_deref() == Some(*setting))
+        })
+        .map(str::to_string)
+        .collect();
+    for (name, value) in settings {
+        lines.push(format!("{} = {}", name, value));
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, lines.join("\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Command, String> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn options(args: &[&str]) -> Options {
+        match parse_args(args) {
+            Ok(Command::Run(options)) => options,
+            Ok(_) => panic!("{:?} asked for help or the version", args),
+            Err(err) => panic!("{:?} was refused: {}", args, err),
+        }
+    }
+
+    fn error(args: &[&str]) -> String {
+        match parse_args(args) {
+            Err(err) => err,
+            Ok(_) => panic!("{:?} was accepted", args),
+        }
+    }
+
+    // A config file of its own for each test.
+    fn config_file(name: &str, text: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("tetrust-cli-{}-{}", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn options_parse_with_separate_or_inline_values() {
+        let options = options(&["--mode", "watch-ai", "--seed=42", "--bench", "3"]);
+        assert!(options.mode == Some(GameMode::WatchAi));
+        assert_eq!(options.seed, Some(42));
+        assert_eq!(options.bench, Some(3));
+        assert_eq!(options.pieces(), bench::DEFAULT_PIECES);
+        assert!(matches!(parse_args(&["-h"]), Ok(Command::Help)));
+        assert!(matches!(
+            parse_args(&["--seed", "1", "--help"]),
+            Ok(Command::Help)
+        ));
+        assert!(matches!(parse_args(&["-V"]), Ok(Command::Version)));
+        assert!(matches!(parse_args(&["--version"]), Ok(Command::Version)));
+    }
+
+    #[test]
+    fn bad_arguments_are_refused() {
+        assert_eq!(error(&["--speed", "3"]), "unknown argument: --speed");
+        assert_eq!(error(&["marathon"]), "unknown argument: marathon");
+        assert_eq!(error(&["--seed"]), "--seed needs a value");
+        assert_eq!(error(&["--mode"]), "--mode needs a value");
+        assert!(error(&["--mode", "zen"]).starts_with("unknown mode \"zen\"; expected one of"));
+        assert_eq!(
+            error(&["--seed", "-1"]),
+            "--seed must be a whole number, not \"-1\""
+        );
+        assert_eq!(
+            error(&["--bench=lots"]),
+            "--bench must be a whole number, not \"lots\""
+        );
+        assert_eq!(
+            error(&["--bench", "2", "--mode", "versus"]),
+            "--bench plays watch-ai or watch-bot"
+        );
+        assert_eq!(error(&["--pieces", "10"]), "--pieces needs --bench");
+    }
+
+    #[test]
+    fn config_files_skip_comments_and_blank_lines() {
+        let path = config_file(
+            "comments",
+            "# Settings\n\n  BOARD = 12x22  \nglyphs=compact\n   # indented comment\n",
+        );
+        let values = load_config(&path);
+        fs::remove_file(path).unwrap();
+        let values = values.unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values["board"], "12x22");
+        assert_eq!(values["glyphs"], "compact");
+    }
+
+    #[test]
+    fn config_files_refuse_unknown_settings() {
+        let path = config_file("unknown", "board = 10x20\n\nspeed = 3\n");
+        let err = load_config(&path).unwrap_err();
+        let missing = config_file("missing", "board 10x20\n");
+        let missing_err = load_config(&missing).unwrap_err();
+        let at = format!("{} line 3: unknown setting \"speed\"", path.display());
+        assert!(err.starts_with(&at), "{}", err);
+        assert_eq!(
+            missing_err,
+            format!("{} line 1: expected name = value", missing.display())
+        );
+        fs::remove_file(path).unwrap();
+        fs::remove_file(missing).unwrap();
+    }
+
+    #[test]
+    fn variables_override_the_file_and_glyphs_override_both() {
+        let path = config_file(
+            "precedence",
+            "board = 12x22\nclear_delay = 5\nglyphs = wide\n",
+        );
+        let mut options = options(&["--glyphs", "compact"]);
+        options.config = Some(path.clone());
+        let env = |name: &str| match name {
+            "TETRUST_BOARD" => Some("8x16".to_string()),
+            "TETRUST_GLYPHS" => Some("auto".to_string()),
+            _ => None,
+        };
+        let config = options.config_from(env);
+        fs::remove_file(path).unwrap();
+        let config = config.unwrap();
+        assert_eq!(config.board_size, (8, 16));
+        assert_eq!(config.timing.clear_delay, 5);
+        assert_eq!(config.glyphs, Some(Glyphs::Compact));
+    }
+
+    #[test]
+    fn the_saved_config_is_found_through_the_home_variable() {
+        let home = std::env::temp_dir().join(format!("tetrust-cli-home-{}", std::process::id()));
+        fs::create_dir_all(&home).unwrap();
+        fs::write(home.join("config"), "board = 6x12\n").unwrap();
+        let variable = home.display().to_string();
+        let config = Options::default()
+            .config_from(|name| (name == "TETRUST_HOME").then(|| variable.clone()));
+        fs::remove_dir_all(&home).unwrap();
+        let config = config.unwrap();
+        assert_eq!(config.board_size, (6, 12));
+        assert_eq!(config.home, home);
+    }
+}
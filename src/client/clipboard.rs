@@ -0,0 +1,21 @@
+//! Copying text out of the game through the terminal, which works over SSH
+//! where no clipboard library could reach the player's machine.
+
+/// Asks the terminal to put `text` on the system clipboard (OSC 52).
+pub fn copy(text: &str) {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in text.as_bytes().chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+            bits | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    print!("\x1B]52;c;{}\x07", encoded);
+}
@@ -0,0 +1,135 @@
+//! Settings, gathered once at startup from the config file, the `TETRUST_*`
+//! variables and the command line, then handed to whatever needs them in
+//! the scene `Context`. Nothing reads the environment after that; the
+//! settings screen changes the `Config` instead.
+
+use crate::client::board::{self, DEFAULT_HEIGHT, DEFAULT_WIDTH};
+use crate::client::layout::Glyphs;
+use crate::client::rules::RuleSet;
+use crate::client::spectate::DEFAULT_SPECTATE_PORT;
+use crate::client::tbp;
+use crate::client::timing::Timing;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Every setting, by the name a config file uses. Each can also be given
+/// as the matching `TETRUST_*` variable, e.g. `TETRUST_BOARD` for `board`.
+pub const NAMES: [&str; 17] = [
+    "board",
+    "clear_delay",
+    "are",
+    "glyphs",
+    "bot",
+    "spectate",
+    "home",
+    "rules",
+    "rotation",
+    "randomizer",
+    "gravity",
+    "lock_delay",
+    "hold",
+    "preview",
+    "scoring",
+    "ghost",
+    "lock_out",
+];
+
+/// Setting values by name, as a config file writes them.
+pub type Values = BTreeMap<String, String>;
+
+/// The environment variable for the setting `name`.
+pub fn variable(name: &str) -> String {
+    format!("TETRUST_{}", name.to_uppercase())
+}
+
+/// `~/.tetrust`, falling back to the working directory.
+pub fn default_home() -> PathBuf {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".tetrust"))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The values given, kept so the settings screen can show and save them.
+    values: Values,
+    /// Visible size of the board in new games.
+    pub board_size: (usize, usize),
+    pub timing: Timing,
+    /// Rules for the modes that do not impose their own.
+    pub rules: RuleSet,
+    /// The glyph set to always draw with, or `None` to use whichever fits.
+    pub glyphs: Option<Glyphs>,
+    /// Program and arguments that start the bot for "Watch Bot".
+    pub bot: Vec<String>,
+    /// Port to publish games to spectators on, if enabled.
+    pub spectate: Option<u16>,
+    /// Where saves, scores, puzzles, crash reports and settings are kept.
+    pub home: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::from_values(Values::new()).expect("the defaults are valid")
+    }
+}
+
+impl Config {
+    /// Parses `values`. Settings left out keep their defaults.
+    pub fn from_values(values: Values) -> Result<Self, String> {
+        let get = |name: &str| values.get(name).map(String::as_str);
+        let board_size = match get("board") {
+            Some(size) => board::parse_size(size)?,
+            None => (DEFAULT_WIDTH, DEFAULT_HEIGHT),
+        };
+        let timing = Timing::from_settings(get)?;
+        let rules = RuleSet::from_settings(get)?;
+        let glyphs = match get("glyphs") {
+            Some(name) => Glyphs::parse(name)?,
+            None => None,
+        };
+        let bot = match get("bot") {
+            Some(command) => tbp::split_command(command)?,
+            None => tbp::default_bot_command(),
+        };
+        // An empty value turns spectating on at the default port.
+        let spectate = match get("spectate").map(str::trim) {
+            None => None,
+            Some("") => Some(DEFAULT_SPECTATE_PORT),
+            Some(port) => Some(
+                port.parse()
+                    .map_err(|_| "spectate must be a port".to_string())?,
+            ),
+        };
+        let home = get("home").map_or_else(default_home, PathBuf::from);
+        Ok(Self {
+            board_size,
+            timing,
+            rules,
+            glyphs,
+            bot,
+            spectate,
+            home,
+            values,
+        })
+    }
+
+    /// The value the setting `name` was given, if any.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Changes one setting. An invalid value leaves the config as it was.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let mut values = self.values.clone();
+        values.insert(name.to_string(), value.to_string());
+        *self = Self::from_values(values)?;
+        Ok(())
+    }
+
+    /// The file the settings screen saves to.
+    pub fn path(&self) -> PathBuf {
+        self.home.join("config")
+    }
+}
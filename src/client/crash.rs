@@ -0,0 +1,102 @@
+//! What the game leaves behind when it panics: the terminal restored before
+//! the panic message prints, and a report with the game and the engine
+//! inputs that led up to the crash, so it can be reproduced.
+
+use crate::client::game::Game;
+use crate::client::spectate::Delta;
+use crate::client::terminal;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Deltas kept for a crash report; older ones are forgotten.
+pub const RECENT_DELTAS: usize = 256;
+
+/// The message and location of the last panic, set by the hook.
+static LAST_PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Serialize)]
+struct CrashReport<'a> {
+    version: &'static str,
+    panic: Option<String>,
+    /// Deltas applied to the game before the crash, oldest first.
+    recent: &'a VecDeque<Delta>,
+    game: &'a Game,
+}
+
+/// Restores the terminal before the default hook prints the panic, which
+/// would otherwise be lost on the alternate screen, and remembers the
+/// message for the crash report.
+pub fn install_hook() {
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        terminal::restore();
+        if let Ok(mut last) = LAST_PANIC.lock() {
+            *last = Some(info.to_string());
+        }
+        default(info);
+    }));
+}
+
+/// Writes a report of the crash in `game` to the data directory `dir` and
+/// returns where. Call after catching the panic.
+pub fn write_report(dir: &Path, game: &Game, recent: &VecDeque<Delta>) -> io::Result<PathBuf> {
+    let report = CrashReport {
+        version: env!("CARGO_PKG_VERSION"),
+        panic: LAST_PANIC.lock().ok().and_then(|last| last.clone()),
+        recent,
+        game,
+    };
+    let when = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let path = dir.join(format!("crash-{}.json", when));
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(
+        &path,
+        serde_json::to_vec_pretty(&report).map_err(io::Error::from)?,
+    )?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::board::{DEFAULT_HEIGHT, DEFAULT_WIDTH};
+    use crate::client::game::GameMode;
+    use crate::client::input::Input;
+
+    #[test]
+    fn a_report_holds_the_panic_and_what_led_up_to_it() {
+        let dir = std::env::temp_dir().join(format!("tetrust-crash-{}", std::process::id()));
+        let mut game = Game::with_size(5, DEFAULT_WIDTH, DEFAULT_HEIGHT);
+        game.start(GameMode::Marathon);
+        let recent: VecDeque<Delta> = [
+            Delta::Input(Input::Left),
+            Delta::Tick,
+            Delta::Garbage(2),
+            Delta::Input(Input::HardDrop),
+        ]
+        .into();
+        // As the hook records it.
+        *LAST_PANIC.lock().unwrap() = Some("panicked at src/client/game.rs: boom".to_string());
+
+        let path = write_report(&dir, &game, &recent).unwrap();
+        let text = fs::read_to_string(&path);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(path.parent(), Some(dir.as_path()));
+        let report: serde_json::Value = serde_json::from_str(&text.unwrap()).unwrap();
+        assert_eq!(report["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(report["panic"], "panicked at src/client/game.rs: boom");
+        assert_eq!(report["recent"], serde_json::to_value(&recent).unwrap());
+        assert_eq!(report["recent"].as_array().map(Vec::len), Some(4));
+        assert_eq!(report["game"], serde_json::to_value(&game).unwrap());
+    }
+}
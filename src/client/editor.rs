@@ -0,0 +1,437 @@
+//! Board editor for building starting positions: paint the stack, choose
+//! the queue and hold piece, then play the position or save it as a puzzle
+//! or fumen.
+
+use crate::client::board::{Board, Cell};
+use crate::client::clipboard;
+use crate::client::config::Config;
+use crate::client::fumen::{self, FumenError, Page, Queue};
+use crate::client::game::{self, Game, GameMode};
+use crate::client::layout::{Area, Glyphs, Layout, Panel, TooSmall};
+use crate::client::menu::{self, Entry, TextInput};
+use crate::client::play;
+use crate::client::puzzle::{self, Goal, Puzzle};
+use crate::client::scene::{Context, Scene, Transition};
+use crate::client::tetromino::TetrominoType;
+use crossterm::event::{
+    DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent, MouseButton, MouseEvent,
+    MouseEventKind,
+};
+use crossterm::execute;
+use std::io::stdout;
+
+const HELP: [&str; 9] = [
+    "Arrows: move cursor",
+    "Space: paint  Tab: fill/erase",
+    "Mouse: left paints, right erases",
+    "I O T L J S Z: add to queue",
+    "Shift+letter: set hold",
+    "Backspace: drop last piece",
+    "Delete: clear hold  X: clear board",
+    "Enter: play  W: save puzzle",
+    "F: copy fumen  G: load fumen  U: load puzzle",
+];
+
+pub struct Editor {
+    board: Board,
+    cursor: (usize, usize),
+    /// What painting puts down: `Cell::Filled` or `Cell::Empty`.
+    brush: Cell,
+    /// The first piece to play followed by the rest of the queue.
+    queue: Vec<TetrominoType>,
+    hold: Option<TetrominoType>,
+    notice: Option<String>,
+}
+
+impl Editor {
+    /// An empty `width` by `height` board.
+    pub fn new(width: usize, height: usize) -> Self {
+        let board = Board::with_size(width, height);
+        Self {
+            // The bottom row, below the hidden buffer.
+            cursor: (width / 2, board.height() - 1),
+            board,
+            brush: Cell::Filled,
+            queue: Vec::new(),
+            hold: None,
+            notice: None,
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn queue(&self) -> &[TetrominoType] {
+        &self.queue
+    }
+
+    pub fn hold(&self) -> Option<TetrominoType> {
+        self.hold
+    }
+
+    /// Shows `message` under the editor until the next key press.
+    pub fn notify(&mut self, message: impl Into<String>) {
+        self.notice = Some(message.into());
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        self.notice = None;
+        let (x, y) = self.cursor;
+        match key.code {
+            KeyCode::Left => self.cursor.0 = x.saturating_sub(1),
+            KeyCode::Right => self.cursor.0 = (x + 1).min(self.board.width() - 1),
+            KeyCode::Up => self.cursor.1 = (y - 1).max(self.board.buffer_height()),
+            KeyCode::Down => self.cursor.1 = (y + 1).min(self.board.height() - 1),
+            KeyCode::Char(' ') => self.board.set(x, y, self.brush),
+            KeyCode::Tab => {
+                self.brush = match self.brush {
+                    Cell::Filled => Cell::Empty,
+                    _ => Cell::Filled,
+                }
+            }
+            KeyCode::Backspace => {
+                self.queue.pop();
+            }
+            KeyCode::Delete => self.hold = None,
+            KeyCode::Char('x') => self.board = self.board.cleared(),
+            KeyCode::Char(c) => match TetrominoType::from_letter(c) {
+                Some(piece) if c.is_ascii_uppercase() => self.hold = Some(piece),
+                Some(piece) => self.queue.push(piece),
+                None => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Paints where `event` lands on the board as drawn with `glyphs`.
+    pub fn handle_mouse(&mut self, event: MouseEvent, glyphs: Option<Glyphs>) {
+        let brush =
+            match event.kind {
+                MouseEventKind::Down(MouseButton::Left)
+                | MouseEventKind::Drag(MouseButton::Left) => self.brush,
+                MouseEventKind::Down(MouseButton::Right)
+                | MouseEventKind::Drag(MouseButton::Right) => Cell::Empty,
+                _ => return,
+            };
+        let Ok(layout) = self.layout(Area::terminal(), glyphs) else {
+            return;
+        };
+        let (x, y) = layout.cell_at(event.column as i32, event.row as i32);
+        let y = y + self.board.buffer_height() as i32;
+        if self.board.contains(x, y) && y >= self.board.buffer_height() as i32 {
+            self.cursor = (x as usize, y as usize);
+            self.board.set(x as usize, y as usize, brush);
+        }
+    }
+
+    /// A game starting from the edited position, with the rules and delays
+    /// from `config`. With an empty queue the pieces come from the
+    /// randomizer as usual.
+    pub fn game(&self, config: &Config) -> Game {
+        let mut game = Game::with_config(rand::random(), config, GameMode::Marathon);
+        game.load_position(self.board.clone(), self.hold, &self.queue);
+        game.start(GameMode::Marathon);
+        game
+    }
+
+    /// Fails unless the board is the fumen width.
+    pub fn fumen(&self) -> Result<String, FumenError> {
+        let mut page = Page::new(self.board.clone());
+        if !self.queue.is_empty() || self.hold.is_some() {
+            page.comment = Queue {
+                hold: self.hold,
+                pieces: self.queue.clone(),
+            }
+            .to_comment();
+        }
+        fumen::encode(&[page])
+    }
+
+    /// Replaces the position with the first page of a fumen.
+    pub fn load_fumen(&mut self, data: &str) -> Result<(), FumenError> {
+        let pages = fumen::decode(data)?;
+        let Some(page) = pages.first() else {
+            return Err(FumenError::Malformed);
+        };
+        let queue = page.queue().unwrap_or_default();
+        self.set_board(page.board.clone());
+        self.queue = queue.pieces;
+        self.hold = queue.hold;
+        Ok(())
+    }
+
+    pub fn load_puzzle(&mut self, puzzle: &Puzzle) {
+        self.set_board(puzzle.starting_board().unwrap_or_default());
+        self.queue = puzzle.pieces.clone();
+        self.hold = puzzle.hold;
+    }
+
+    // Loaded boards can be a different size, so keep the cursor on their
+    // visible rows.
+    fn set_board(&mut self, board: Board) {
+        self.cursor.0 = self.cursor.0.min(board.width() - 1);
+        self.cursor.1 = self
+            .cursor
+            .1
+            .clamp(board.buffer_height(), board.height() - 1);
+        self.board = board;
+    }
+
+    pub fn to_puzzle(&self, name: &str, goal: Goal) -> Puzzle {
+        Puzzle::new(name, &self.board, self.hold, &self.queue, goal)
+    }
+
+    pub fn draw(&self, glyphs: Option<Glyphs>) {
+        print!("\x1B[2J");
+        let area = Area::terminal();
+        let layout = match self.layout(area, glyphs) {
+            Ok(layout) => layout,
+            Err(too_small) => return too_small.draw(area),
+        };
+
+        let mut cells = self.board.to_cells();
+        let (x, y) = self.cursor;
+        cells[y - self.board.buffer_height()][x] = Cell::Cursor;
+        game::draw_cells(&cells, layout.board_x, layout.board_y, layout.glyphs);
+
+        let lines = self.side_lines();
+        let mut notice_y = layout.below_board() + 1;
+        if let Some((side_x, side_y)) = layout.side {
+            for (i, line) in lines.iter().enumerate() {
+                layout.area.put(side_x, side_y + i as i32, line);
+            }
+        } else {
+            // Without room for the panel, keep the queue and hold in view.
+            for (i, line) in lines[2..4].iter().enumerate() {
+                layout
+                    .area
+                    .put(layout.board_x, layout.below_board() + i as i32, line);
+            }
+            notice_y += 2;
+        }
+
+        if let Some(notice) = &self.notice {
+            layout.area.put(layout.board_x, notice_y, notice);
+        }
+    }
+
+    // The queue, hold and brush, then the key help.
+    fn side_lines(&self) -> Vec<String> {
+        let letters = |pieces: &[TetrominoType]| -> String {
+            pieces.iter().map(|piece| piece.letter()).collect()
+        };
+        let brush = match self.brush {
+            Cell::Filled => "fill",
+            _ => "erase",
+        };
+        let mut lines = vec![
+            "EDITOR".to_string(),
+            String::new(),
+            format!("Queue: {}", letters(&self.queue)),
+            format!("Hold:  {}", letters(self.hold.as_slice())),
+            format!("Brush: {}", brush),
+            String::new(),
+        ];
+        lines.extend(HELP.iter().map(|line| line.to_string()));
+        lines.push("Esc: back".to_string());
+        lines
+    }
+
+    // Leaves room on the right for the queue and the key help.
+    fn layout(&self, area: Area, glyphs: Option<Glyphs>) -> Result<Layout, TooSmall> {
+        let lines = self.side_lines();
+        let panel = Panel {
+            width: lines
+                .iter()
+                .map(|line| line.chars().count())
+                .max()
+                .unwrap_or(0) as i32,
+            height: lines.len() as i32,
+        };
+        Layout::fit(area, &self.board, false, glyphs, |_| panel)
+    }
+}
+
+/// Text the editor scene is asking for.
+enum Asking {
+    Fumen,
+    PuzzleName,
+    PuzzleGoal { name: String },
+}
+
+/// Edits a position until the player leaves it or plays it. The mouse is
+/// captured for painting while this scene is on top.
+pub struct EditorScene {
+    /// The settings the position is played and saved with, for the scene
+    /// calls that come without the `Context`.
+    config: Config,
+    editor: Editor,
+    asking: Option<(Asking, TextInput)>,
+    /// Puzzles to load a position from, and the one selected.
+    choosing: Option<(Vec<Puzzle>, usize)>,
+}
+
+impl EditorScene {
+    /// Edits an empty board of the size from `config`.
+    pub fn new(config: &Config) -> Self {
+        capture_mouse(true);
+        let (width, height) = config.board_size;
+        Self {
+            config: config.clone(),
+            editor: Editor::new(width, height),
+            asking: None,
+            choosing: None,
+        }
+    }
+
+    fn answer(&mut self, asking: Asking, text: String) {
+        match asking {
+            Asking::Fumen => {
+                if let Err(err) = self.editor.load_fumen(text.trim()) {
+                    self.editor.notify(format!("Could not load: {}", err));
+                }
+            }
+            Asking::PuzzleName => {
+                let goal = TextInput::new("Goal (pc, tetris, tspin N, mini N, lines N):", "pc");
+                self.asking = Some((Asking::PuzzleGoal { name: text }, goal));
+            }
+            Asking::PuzzleGoal { name } => match text.parse() {
+                Ok(goal) => match puzzle::save_puzzle(
+                    &self.config.home,
+                    &self.editor.to_puzzle(name.trim(), goal),
+                ) {
+                    Ok(id) => self.editor.notify(format!("Saved as puzzle {}", id)),
+                    Err(err) => self.editor.notify(format!("Could not save: {}", err)),
+                },
+                Err(err) => self.editor.notify(err),
+            },
+        }
+    }
+
+    fn choose_key(&mut self, key: KeyEvent) {
+        let Some((puzzles, selected)) = self.choosing.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.choosing = None,
+            KeyCode::Enter => {
+                self.editor.load_puzzle(&puzzles[*selected]);
+                self.choosing = None;
+            }
+            code => *selected = menu::step(*selected, puzzles.len(), code),
+        }
+    }
+
+    fn edit_key(&mut self, ctx: &mut Context, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Esc => return Transition::Pop,
+            KeyCode::Enter => match play::resume(ctx, self.editor.game(&self.config)) {
+                Ok(scene) => return Transition::Replace(scene),
+                Err(err) => self.editor.notify(format!("Could not play: {}", err)),
+            },
+            KeyCode::Char('f') => match self.editor.fumen() {
+                Ok(data) => {
+                    clipboard::copy(&data);
+                    self.editor.notify(format!("Copied to clipboard: {}", data));
+                }
+                Err(err) => self.editor.notify(format!("Could not copy: {}", err)),
+            },
+            KeyCode::Char('g') => {
+                self.asking = Some((Asking::Fumen, TextInput::new("Fumen to load:", "")));
+            }
+            KeyCode::Char('u') => {
+                let (puzzles, _) = puzzle::load_all(&self.config.home);
+                if !puzzles.is_empty() {
+                    self.choosing = Some((puzzles, 0));
+                }
+            }
+            KeyCode::Char('w') if self.editor.queue().is_empty() => {
+                self.editor.notify("Add pieces to the queue first");
+            }
+            KeyCode::Char('w') => {
+                self.asking = Some((Asking::PuzzleName, TextInput::new("Puzzle name:", "")));
+            }
+            _ => self.editor.handle_key(key),
+        }
+        Transition::None
+    }
+}
+
+// Errors are ignored: without capture the keyboard still edits.
+fn capture_mouse(on: bool) {
+    let _ = if on {
+        execute!(stdout(), EnableMouseCapture)
+    } else {
+        execute!(stdout(), DisableMouseCapture)
+    };
+}
+
+impl Scene for EditorScene {
+    fn handle_key(&mut self, ctx: &mut Context, key: KeyEvent) -> Transition {
+        if let Some((_, input)) = self.asking.as_mut() {
+            match input.handle_key(key) {
+                Entry::Editing => {}
+                Entry::Done(text) => {
+                    if let Some((asking, _)) = self.asking.take() {
+                        self.answer(asking, text);
+                    }
+                }
+                Entry::Cancelled => self.asking = None,
+            }
+            return Transition::None;
+        }
+        if self.choosing.is_some() {
+            self.choose_key(key);
+            return Transition::None;
+        }
+        self.edit_key(ctx, key)
+    }
+
+    fn handle_mouse(&mut self, ctx: &mut Context, event: MouseEvent) -> Transition {
+        if self.asking.is_none() && self.choosing.is_none() {
+            self.editor.handle_mouse(event, ctx.config.glyphs);
+        }
+        Transition::None
+    }
+
+    // Also called once the process continues after being stopped, when the
+    // terminal was set up again without mouse capture.
+    fn interrupt(&mut self, _ctx: &mut Context) -> Transition {
+        capture_mouse(true);
+        Transition::None
+    }
+
+    fn own_game(&self) -> Option<Game> {
+        Some(self.editor.game(&self.config))
+    }
+
+    fn suspend(&mut self, _ctx: &mut Context) {
+        capture_mouse(false);
+    }
+
+    fn resume(&mut self, _ctx: &mut Context) {
+        capture_mouse(true);
+    }
+
+    fn draw(&self, ctx: &Context) {
+        if let Some((_, input)) = &self.asking {
+            return input.draw();
+        }
+        let Some((puzzles, selected)) = &self.choosing else {
+            return self.editor.draw(ctx.config.glyphs);
+        };
+        let names: Vec<String> = puzzles.iter().map(|p| p.name.clone()).collect();
+        let mut lines = vec!["Load puzzle:".to_string(), String::new()];
+        lines.extend(menu::menu_lines(&names, *selected));
+        lines.push(String::new());
+        lines.push("Enter: choose  Esc: back".to_string());
+        menu::draw_lines(&lines);
+    }
+}
+
+impl Drop for EditorScene {
+    fn drop(&mut self) {
+        capture_mouse(false);
+    }
+}
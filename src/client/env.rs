@@ -0,0 +1,362 @@
+//! Gym-style environment over a headless `Game` for training agents.
+//!
+//! Agents either pick a whole placement per step (`ActionMode::Placement`)
+//! or press one input per engine frame (`ActionMode::Frame`). Both go
+//! through `Game::apply_input`, so they play by exactly the same rules as
+//! a human at the keyboard.
+
+use crate::client::ai;
+use crate::client::board::{Cell, DEFAULT_HEIGHT, DEFAULT_WIDTH};
+use crate::client::game::{Game, GameMode, GameState};
+use crate::client::input::Input;
+use crate::client::tetromino::TetrominoType;
+
+/// Number of bit planes in `Observation::board`.
+pub const PLANES: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ActionMode {
+    Placement,
+    Frame,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Lock the current piece (or the swapped-in piece if `hold`) with the
+    /// given rotation and top-left position.
+    Place {
+        hold: bool,
+        rotation: u8,
+        x: i32,
+        y: i32,
+    },
+    /// Press one input, or nothing, then advance one frame.
+    Frame(Option<Input>),
+}
+
+/// Weights applied to what happened during a step. Penalties are negative.
+#[derive(Clone, Debug)]
+pub struct RewardConfig {
+    /// Reward for clearing 0, 1, 2, 3 and 4 lines at once.
+    pub lines: [f64; 5],
+    /// Reward per line of attack sent.
+    pub attack: f64,
+    /// Reward per hole created (negative when holes are filled in).
+    pub holes: f64,
+    /// Reward for every step survived.
+    pub survival: f64,
+    /// Reward when the game ends by topping out.
+    pub top_out: f64,
+}
+
+impl Default for RewardConfig {
+    fn default() -> Self {
+        Self {
+            lines: [0.0, 1.0, 3.0, 5.0, 8.0],
+            attack: 0.0,
+            holes: -0.5,
+            survival: 0.01,
+            top_out: -10.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EnvConfig {
+    pub action_mode: ActionMode,
+    pub reward: RewardConfig,
+    /// In frame mode, gravity moves the piece down once every this many steps.
+    pub gravity_frames: u32,
+    /// Ends the episode after this many steps.
+    pub max_steps: Option<u64>,
+    /// Board width and height, e.g. a narrow board for early training.
+    pub board_size: (usize, usize),
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            action_mode: ActionMode::Placement,
+            reward: RewardConfig::default(),
+            gravity_frames: 30,
+            max_steps: None,
+            board_size: (DEFAULT_WIDTH, DEFAULT_HEIGHT),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Observation {
+    /// `PLANES` planes of `height` rows (hidden buffer included) of `width`
+    /// cells, row-major, holding 0 or 1: filled cells, the active piece,
+    /// then its landing position.
+    pub board: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    pub current: TetrominoType,
+    pub rotation: u8,
+    pub position: (i32, i32),
+    pub queue: Vec<TetrominoType>,
+    pub hold: Option<TetrominoType>,
+    pub can_hold: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Info {
+    pub lines_cleared: u32,
+    pub attack: u32,
+    pub holes: usize,
+    /// Total score so far.
+    pub score: u32,
+    pub pieces: u64,
+    pub steps: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f64,
+    pub done: bool,
+    pub info: Info,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EnvError {
+    /// `step` was called before the first `reset`.
+    NotReset,
+    /// The action is not in `legal_actions()` for this step.
+    IllegalAction(Action),
+    /// `step` was called after the episode ended; call `reset` first.
+    EpisodeOver,
+}
+
+pub struct Env {
+    config: EnvConfig,
+    game: Game,
+    steps: u64,
+}
+
+impl Env {
+    /// Creates the environment. Call `reset` to start the first episode.
+    pub fn new(config: EnvConfig) -> Self {
+        let (width, height) = config.board_size;
+        Self {
+            config,
+            game: Game::with_size(0, width, height),
+            steps: 0,
+        }
+    }
+
+    /// Starts a new episode whose piece sequence is determined by `seed`.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        let (width, height) = self.config.board_size;
+        self.game = Game::with_size(seed, width, height);
+        self.game.start(GameMode::Marathon);
+        self.steps = 0;
+        self.observe()
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    fn done(&self) -> bool {
+        self.game.get_state() == GameState::GameOver
+            || self.config.max_steps.is_some_and(|max| self.steps >= max)
+    }
+
+    pub fn legal_actions(&self) -> Vec<Action> {
+        if self.game.get_state() == GameState::Ready || self.done() {
+            return Vec::new();
+        }
+        match self.config.action_mode {
+            ActionMode::Placement => ai::generate_moves(&self.game)
+                .into_iter()
+                .map(|placement| Action::Place {
+                    hold: placement.inputs.first() == Some(&Input::Hold),
+                    rotation: placement.piece.rotation,
+                    x: placement.x,
+                    y: placement.y,
+                })
+                .collect(),
+            ActionMode::Frame => std::iter::once(None)
+                .chain(
+                    [
+                        Input::Left,
+                        Input::Right,
+                        Input::DasLeft,
+                        Input::DasRight,
+                        Input::SoftDrop,
+                        Input::RotateCw,
+                        Input::RotateCcw,
+                        Input::Hold,
+                        Input::HardDrop,
+                    ]
+                    .map(Some),
+                )
+                .map(Action::Frame)
+                .collect(),
+        }
+    }
+
+    pub fn step(&mut self, action: &Action) -> Result<Step, EnvError> {
+        if self.game.get_state() == GameState::Ready {
+            return Err(EnvError::NotReset);
+        }
+        if self.done() {
+            return Err(EnvError::EpisodeOver);
+        }
+
+        let lines = self.game.lines();
+        let attack_sent = self.game.attack_sent();
+        let holes = self.game.board().holes();
+        let pieces = self.game.pieces_dealt();
+
+        match (self.config.action_mode, action) {
+            (
+                ActionMode::Placement,
+                Action::Place {
+                    hold,
+                    rotation,
+                    x,
+                    y,
+                },
+            ) => {
+                let inputs = ai::generate_moves(&self.game)
+                    .into_iter()
+                    .find(|placement| {
+                        (placement.inputs.first() == Some(&Input::Hold)) == *hold
+                            && placement.piece.rotation == *rotation
+                            && placement.x == *x
+                            && placement.y == *y
+                    })
+                    .ok_or_else(|| EnvError::IllegalAction(action.clone()))?
+                    .inputs;
+                for input in inputs {
+                    self.game.apply_input(input);
+                }
+            }
+            (ActionMode::Frame, Action::Frame(input)) => {
+                if let Some(input) = input {
+                    self.game.apply_input(*input);
+                }
+                self.game.frame();
+                let frame = self.steps + 1;
+                if frame.is_multiple_of(self.config.gravity_frames.max(1) as u64)
+                    && self.game.get_state() == GameState::Playing
+                {
+                    self.game.tick();
+                }
+            }
+            _ => return Err(EnvError::IllegalAction(action.clone())),
+        }
+        self.steps += 1;
+
+        let lines_cleared = self.game.lines() - lines;
+        let attack = self.game.attack_sent() - attack_sent;
+        let new_holes = self.game.board().holes();
+        let topped_out = self.game.get_state() == GameState::GameOver;
+
+        let reward_config = &self.config.reward;
+        let mut reward = reward_config.lines[lines_cleared.min(4) as usize]
+            + reward_config.attack * attack as f64
+            + reward_config.holes * (new_holes as f64 - holes as f64)
+            + reward_config.survival;
+        if topped_out {
+            reward += reward_config.top_out;
+        }
+
+        Ok(Step {
+            observation: self.observe(),
+            reward,
+            done: self.done(),
+            info: Info {
+                lines_cleared,
+                attack,
+                holes: new_holes,
+                score: self.game.score(),
+                pieces: self.game.pieces_dealt() - pieces,
+                steps: self.steps,
+            },
+        })
+    }
+
+    pub fn observe(&self) -> Observation {
+        let game = &self.game;
+        let (width, height) = (game.board().width(), game.board().height());
+        let mut board = vec![0; PLANES * height * width];
+        for y in 0..height {
+            for x in 0..width {
+                if game.board().get(x, y) == Cell::Filled {
+                    board[y * width + x] = 1;
+                }
+            }
+        }
+
+        let piece = game.current_piece();
+        let (piece_x, piece_y) = game.piece_position();
+        let landing_y = game.landing_position();
+        for (plane, top) in [(1, piece_y), (2, landing_y)] {
+            for (dx, dy) in piece.minos() {
+                let (x, y) = (piece_x + dx, top + dy);
+                if game.board().contains(x, y) {
+                    board[plane * height * width + y as usize * width + x as usize] = 1;
+                }
+            }
+        }
+
+        Observation {
+            board,
+            width,
+            height,
+            current: piece.tetromino_type,
+            rotation: piece.rotation,
+            position: (piece_x, piece_y),
+            queue: vec![game.next_piece().tetromino_type],
+            hold: game.held_piece(),
+            can_hold: game.can_hold(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_wait_for_the_first_reset() {
+        let mut env = Env::new(EnvConfig::default());
+        assert!(env.legal_actions().is_empty());
+        let action = Action::Place {
+            hold: false,
+            rotation: 0,
+            x: 3,
+            y: 18,
+        };
+        assert_eq!(env.step(&action).unwrap_err(), EnvError::NotReset);
+
+        env.reset(1);
+        assert!(!env.legal_actions().is_empty());
+    }
+
+    #[test]
+    fn episodes_deal_whole_bags_from_the_first_piece() {
+        let mut env = Env::new(EnvConfig::default());
+        for seed in 0..5 {
+            let mut observation = env.reset(seed);
+            let mut dealt = Vec::new();
+            for _ in 0..TetrominoType::ALL.len() {
+                dealt.push(observation.current);
+                let action = env
+                    .legal_actions()
+                    .into_iter()
+                    .find(|action| matches!(action, Action::Place { hold: false, .. }))
+                    .unwrap();
+                observation = env.step(&action).unwrap().observation;
+            }
+            for piece in TetrominoType::ALL {
+                assert!(dealt.contains(&piece), "seed {} dealt {:?}", seed, dealt);
+            }
+        }
+    }
+}
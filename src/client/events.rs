@@ -0,0 +1,57 @@
+//! What happens inside a `Game`, as it happens. The game queues an event at
+//! each step of play, and observers such as stats, sound or network code
+//! drain them with `Game::drain_events` instead of comparing states.
+
+use crate::client::attack::ClearKind;
+use crate::client::game::TopOut;
+use crate::client::tetromino::TetrominoType;
+use serde::Serialize;
+
+/// Events kept until drained; older ones are dropped so a game nobody
+/// observes does not grow without bound.
+pub const MAX_EVENTS: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GameEvent {
+    /// A new piece appeared at its spawn position.
+    PieceSpawned {
+        piece: TetrominoType,
+        x: i32,
+        y: i32,
+    },
+    /// The piece moved to `(x, y)`, by the player or by gravity.
+    Moved { x: i32, y: i32 },
+    /// The piece rotated, after trying `kick` offsets that collided.
+    Rotated { clockwise: bool, kick: usize },
+    /// The current piece went into hold; `swapped` is the piece that came
+    /// out of it, if hold was not empty.
+    Held {
+        piece: TetrominoType,
+        swapped: Option<TetrominoType>,
+    },
+    /// The piece fell `rows` rows straight down and is about to lock.
+    HardDropped { rows: u32 },
+    /// The piece became part of the stack at `(x, y)`, turned `rotation`
+    /// quarter turns clockwise from its spawn orientation.
+    Locked {
+        piece: TetrominoType,
+        x: i32,
+        y: i32,
+        rotation: u8,
+    },
+    /// A lock cleared lines or was a T-spin. `combo` counts the clearing
+    /// locks in a row just before this one.
+    LinesCleared { kind: ClearKind, combo: u32 },
+    /// An opponent sent garbage; it rises after the next lock that clears
+    /// nothing.
+    GarbageReceived { lines: u32 },
+    /// Enough lines were cleared to reach `level`.
+    LevelUp { level: u32 },
+    /// The game ended.
+    TopOut { reason: TopOut },
+    /// A placement was taken back, restoring the game from before it.
+    Undone,
+    /// A placement taken back was played again.
+    Redone,
+}
@@ -0,0 +1,153 @@
+use crate::client::board::Board;
+use crate::client::input::Input;
+use crate::client::rules::RotationSystem;
+use crate::client::search::{self, find_placements, piece_cells};
+use crate::client::tetromino::{Tetromino, TetrominoType};
+use serde::{Deserialize, Serialize};
+
+/// A placement that took more keypresses than necessary.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FinesseFault {
+    pub piece: TetrominoType,
+    pub used: Vec<Input>,
+    pub optimal: Vec<Input>,
+}
+
+/// Compares the inputs the player used for one piece against the shortest
+/// sequence that reaches the same final cells from spawn.
+///
+/// Returns `None` when the placement was optimal, or when it could not have
+/// been reached without soft dropping (tucks and spins are not judged).
+///
+/// Terminals report a held arrow key as repeated presses, so a run of
+/// presses in one direction that ends with the piece against the wall or
+/// stack counts as a single DAS shift. Tapping all the way there looks the
+/// same and is not flagged either.
+pub fn check(
+    rotation: RotationSystem,
+    board: &Board,
+    spawned: &Tetromino,
+    spawn: (i32, i32),
+    used: &[Input],
+    locked: &Tetromino,
+    locked_at: (i32, i32),
+) -> Option<FinesseFault> {
+    if used.contains(&Input::SoftDrop) {
+        return None;
+    }
+
+    let final_cells = piece_cells(locked, locked_at.0, locked_at.1);
+    let optimal = find_placements(rotation, board, spawned, spawn.0, spawn.1, false)
+        .into_iter()
+        .find(|placement| placement.cells() == final_cells)?
+        .inputs;
+
+    let used = collapse_auto_repeat(rotation, board, spawned, spawn, used);
+    let moves = |inputs: &[Input]| inputs.iter().filter(|&&i| i != Input::HardDrop).count();
+    if moves(&used) > moves(&optimal) {
+        Some(FinesseFault {
+            piece: spawned.tetromino_type,
+            used,
+            optimal,
+        })
+    } else {
+        None
+    }
+}
+
+// Replays `used` from spawn, replacing each run of two or more Left or Right
+// presses that leaves the piece unable to move further that way with the
+// DAS shift it amounts to.
+fn collapse_auto_repeat(
+    rotation: RotationSystem,
+    board: &Board,
+    spawned: &Tetromino,
+    spawn: (i32, i32),
+    used: &[Input],
+) -> Vec<Input> {
+    let (mut piece, (mut x, mut y)) = (spawned.clone(), spawn);
+    let mut collapsed = Vec::with_capacity(used.len());
+    let mut i = 0;
+    while i < used.len() {
+        let input = used[i];
+        let run = match input {
+            Input::Left | Input::Right => {
+                used[i..].iter().take_while(|&&next| next == input).count()
+            }
+            _ => 1,
+        };
+        for _ in 0..run {
+            if let Some(moved) = search::step(rotation, board, &piece, x, y, input) {
+                (piece, x, y) = moved;
+            }
+        }
+        let against_wall = search::step(rotation, board, &piece, x, y, input).is_none();
+        match input {
+            Input::Left if run > 1 && against_wall => collapsed.push(Input::DasLeft),
+            Input::Right if run > 1 && against_wall => collapsed.push(Input::DasRight),
+            _ => collapsed.extend(&used[i..i + run]),
+        }
+        i += run;
+    }
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::board::{DEFAULT_HEIGHT, DEFAULT_WIDTH};
+    use crate::client::rules;
+    use Input::*;
+
+    // Judges `used` for a `kind` piece on an empty board.
+    fn judge(kind: TetrominoType, used: &[Input]) -> Option<FinesseFault> {
+        let board = Board::with_size(DEFAULT_WIDTH, DEFAULT_HEIGHT);
+        let spawned = Tetromino::new(kind);
+        let spawn = rules::spawn_position(&board, &spawned);
+        let (mut piece, (mut x, mut y)) = (spawned.clone(), spawn);
+        for &input in used {
+            if let Some(moved) = search::step(RotationSystem::Srs, &board, &piece, x, y, input) {
+                (piece, x, y) = moved;
+            }
+        }
+        let y = search::drop_y(&board, &piece, x, y);
+        check(
+            RotationSystem::Srs,
+            &board,
+            &spawned,
+            spawn,
+            used,
+            &piece,
+            (x, y),
+        )
+    }
+
+    #[test]
+    fn extra_presses_are_faults() {
+        let fault = judge(TetrominoType::T, &[RotateCw, RotateCw, RotateCw, HardDrop]).unwrap();
+        assert_eq!(fault.optimal, [RotateCcw, HardDrop]);
+        let fault = judge(TetrominoType::O, &[Left, Left, Right, HardDrop]).unwrap();
+        assert_eq!(fault.optimal, [Left, HardDrop]);
+        assert!(judge(TetrominoType::O, &[Left, HardDrop]).is_none());
+        assert!(judge(TetrominoType::T, &[RotateCcw, DasLeft, HardDrop]).is_none());
+    }
+
+    #[test]
+    fn holding_a_key_to_the_wall_counts_as_one_shift() {
+        // The repeats go on after the piece reaches the wall.
+        let held = [Left, Left, Left, Left, Left, Left, HardDrop];
+        assert!(judge(TetrominoType::O, &held).is_none());
+        assert!(judge(TetrominoType::I, &[Right, Right, Right, RotateCw, HardDrop]).is_none());
+
+        // A held key is still one shift too many if a tap would do.
+        let used = [Left, Left, Left, Left, Right, Right, Right, HardDrop];
+        let fault = judge(TetrominoType::O, &used).unwrap();
+        assert_eq!(fault.used, [DasLeft, Right, Right, Right, HardDrop]);
+        assert_eq!(fault.optimal, [Left, HardDrop]);
+    }
+
+    #[test]
+    fn soft_dropped_placements_are_not_judged() {
+        assert!(judge(TetrominoType::O, &[Left, Right, Left, SoftDrop, HardDrop]).is_none());
+    }
+}
@@ -0,0 +1,719 @@
+//! Import and export of fumen strings (`v115@...`), the format used by the
+//! fumen web diagram editor to share boards and setups.
+//!
+//! A fumen is a list of pages. Each page stores its field as a run-length
+//! encoded difference from the previous page's field after that page's piece
+//! locked, followed by the piece and an optional comment. Numbers are written
+//! as little-endian digits of a 64-character alphabet.
+
+use crate::client::board::{Board, Cell, DEFAULT_HEIGHT};
+use crate::client::game::Game;
+use crate::client::search::Placement;
+use crate::client::tbp::{self, Orientation, PieceLocation};
+use crate::client::tetromino::TetrominoType;
+use std::fmt;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const PREFIX: &str = "v115@";
+
+/// Columns of a fumen field; fumen has no other board widths.
+pub const FIELD_WIDTH: usize = 10;
+/// Rows of a fumen field above the garbage row.
+const FIELD_TOP: i32 = 23;
+/// Cells in a fumen field, including the garbage row under the floor.
+const FIELD_BLOCKS: u32 = (FIELD_TOP as u32 + 1) * FIELD_WIDTH as u32;
+
+/// Printable ASCII, in the order fumen packs comment characters.
+const COMMENT_TABLE: &[u8] =
+    b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const COMMENT_BASE: u32 = COMMENT_TABLE.len() as u32 + 1;
+
+// Fumen's cell values: 0 is empty, 1-7 are coloured pieces and 8 is garbage.
+const GARBAGE: u8 = 8;
+
+#[derive(Debug, PartialEq)]
+pub enum FumenError {
+    /// Only version 1.15 data (`v115@`) is supported.
+    UnsupportedVersion,
+    /// The data ended early or contains characters outside the alphabet.
+    Malformed,
+    /// A page has blocks above the top of the board it is copied into.
+    TooTall { page: usize },
+    /// Fumen fields are always `FIELD_WIDTH` columns wide.
+    WrongWidth { width: usize },
+}
+
+impl fmt::Display for FumenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FumenError::UnsupportedVersion => write!(f, "only v115 fumen data is supported"),
+            FumenError::Malformed => write!(f, "fumen data is malformed"),
+            FumenError::TooTall { page } => write!(f, "page {} is too tall", page + 1),
+            FumenError::WrongWidth { width } => write!(
+                f,
+                "fumen boards are {} columns wide, not {}",
+                FIELD_WIDTH, width
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FumenError {}
+
+/// One page of a diagram: the field before `piece` is placed.
+#[derive(Clone)]
+pub struct Page {
+    pub board: Board,
+    pub piece: Option<PieceLocation>,
+    pub comment: String,
+    /// Whether `piece` locks into the field the next page starts from.
+    pub lock: bool,
+}
+
+impl Page {
+    pub fn new(board: Board) -> Self {
+        Self {
+            board,
+            piece: None,
+            comment: String::new(),
+            lock: true,
+        }
+    }
+
+    /// Hold and queue from a quiz comment such as `#Q=[I](T)SZO`.
+    pub fn queue(&self) -> Option<Queue> {
+        Queue::from_comment(&self.comment)
+    }
+}
+
+/// Pieces a page expects the player to use, as given by a quiz comment.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Queue {
+    pub hold: Option<TetrominoType>,
+    /// The current piece followed by the upcoming ones.
+    pub pieces: Vec<TetrominoType>,
+}
+
+impl Queue {
+    pub fn from_comment(comment: &str) -> Option<Self> {
+        let quiz = comment.strip_prefix("#Q=")?;
+        let mut queue = Queue::default();
+        let mut chars = quiz.chars().peekable();
+        if chars.peek() == Some(&'[') {
+            chars.next();
+            let hold: String = chars.by_ref().take_while(|&c| c != ']').collect();
+            queue.hold = hold.chars().next().and_then(TetrominoType::from_letter);
+        }
+        for c in chars {
+            match c {
+                '(' | ')' => {}
+                ';' => break,
+                c => queue.pieces.push(TetrominoType::from_letter(c)?),
+            }
+        }
+        Some(queue)
+    }
+
+    pub fn to_comment(&self) -> String {
+        let letters: String = self.pieces.iter().map(|piece| piece.letter()).collect();
+        let (current, rest) = letters.split_at(letters.len().min(1));
+        format!(
+            "#Q=[{}]({}){}",
+            self.hold
+                .map(|piece| piece.letter().to_string())
+                .unwrap_or_default(),
+            current,
+            rest
+        )
+    }
+}
+
+fn piece_value(piece: TetrominoType) -> u8 {
+    match piece {
+        TetrominoType::I => 1,
+        TetrominoType::L => 2,
+        TetrominoType::O => 3,
+        TetrominoType::Z => 4,
+        TetrominoType::T => 5,
+        TetrominoType::J => 6,
+        TetrominoType::S => 7,
+    }
+}
+
+fn piece_from_value(value: u32) -> Option<TetrominoType> {
+    TetrominoType::ALL
+        .into_iter()
+        .find(|&piece| piece_value(piece) as u32 == value)
+}
+
+// Fumen numbers its rotations starting from South.
+const ROTATIONS: [Orientation; 4] = [
+    Orientation::South,
+    Orientation::East,
+    Orientation::North,
+    Orientation::West,
+];
+
+/// A fumen field, indexed `[y + 1][x]` with `y` counting up from the floor
+/// and `y = -1` the garbage row.
+#[derive(Clone, PartialEq)]
+struct Field([[u8; FIELD_WIDTH]; FIELD_TOP as usize + 1]);
+
+impl Field {
+    fn new() -> Self {
+        Field([[0; FIELD_WIDTH]; FIELD_TOP as usize + 1])
+    }
+
+    // Fails for boards of another width, or with blocks above the field.
+    fn from_board(board: &Board, page: usize) -> Result<Self, FumenError> {
+        if board.width() != FIELD_WIDTH {
+            return Err(FumenError::WrongWidth {
+                width: board.width(),
+            });
+        }
+        let height = board.height();
+        let mut field = Field::new();
+        for row in 0..height {
+            for x in 0..FIELD_WIDTH {
+                if board.get(x, row) == Cell::Filled {
+                    let y = (height - 1 - row) as i32;
+                    if y >= FIELD_TOP {
+                        return Err(FumenError::TooTall { page });
+                    }
+                    field.set(x as i32, y, GARBAGE);
+                }
+            }
+        }
+        Ok(field)
+    }
+
+    // Copies the field into a standard board, where rows above the visible
+    // 20 land in the hidden buffer.
+    fn to_board(&self) -> Option<Board> {
+        let mut board = Board::with_size(FIELD_WIDTH, DEFAULT_HEIGHT);
+        let height = board.height();
+        for y in 0..FIELD_TOP {
+            for x in 0..FIELD_WIDTH as i32 {
+                if self.get(x, y) == 0 {
+                    continue;
+                }
+                if y >= height as i32 {
+                    return None;
+                }
+                board.set(x as usize, height - 1 - y as usize, Cell::Filled);
+            }
+        }
+        Some(board)
+    }
+
+    // The cell at `index` when cells are listed from the top-left, ending
+    // with the garbage row.
+    fn position(index: u32) -> (i32, i32) {
+        (
+            (index % FIELD_WIDTH as u32) as i32,
+            FIELD_TOP - (index / FIELD_WIDTH as u32) as i32 - 1,
+        )
+    }
+
+    fn get(&self, x: i32, y: i32) -> u8 {
+        self.0[(y + 1) as usize][x as usize]
+    }
+
+    fn set(&mut self, x: i32, y: i32, value: u8) {
+        if (0..FIELD_WIDTH as i32).contains(&x) && (-1..FIELD_TOP).contains(&y) {
+            self.0[(y + 1) as usize][x as usize] = value;
+        }
+    }
+
+    // Locks a piece, clears full rows and applies the page flags, giving the
+    // field the next page is encoded against.
+    fn after(&self, page_piece: Option<&PieceLocation>, lock: bool, flags: Flags) -> Field {
+        let mut field = self.clone();
+        if let (Some(location), true) = (page_piece, lock) {
+            for (x, y) in tbp::srs_minos(location.kind, location.orientation)
+                .map(|(dx, dy)| (location.x + dx, location.y + dy))
+            {
+                field.set(x, y, piece_value(location.kind));
+            }
+        }
+
+        let mut rows: Vec<[u8; FIELD_WIDTH]> = field.0[1..]
+            .iter()
+            .copied()
+            .filter(|row| row.contains(&0))
+            .collect();
+        rows.resize(FIELD_TOP as usize, [0; FIELD_WIDTH]);
+        field.0[1..].copy_from_slice(&rows);
+
+        if flags.rise {
+            field.0.copy_within(0..FIELD_TOP as usize, 1);
+            field.0[0] = [0; FIELD_WIDTH];
+        }
+        if flags.mirror {
+            for row in field.0[1..].iter_mut() {
+                row.reverse();
+            }
+        }
+        field
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Flags {
+    rise: bool,
+    mirror: bool,
+}
+
+struct Reader<'a> {
+    digits: std::str::Bytes<'a>,
+}
+
+impl Reader<'_> {
+    fn is_empty(&self) -> bool {
+        self.digits.len() == 0
+    }
+
+    fn poll(&mut self, count: u32) -> Result<u32, FumenError> {
+        let mut value = 0;
+        for i in 0..count {
+            let digit = self.digits.next().ok_or(FumenError::Malformed)?;
+            let digit = ALPHABET
+                .iter()
+                .position(|&c| c == digit)
+                .ok_or(FumenError::Malformed)?;
+            value += digit as u32 * 64u32.pow(i);
+        }
+        Ok(value)
+    }
+}
+
+fn push(values: &mut Vec<u8>, mut value: u32, count: u32) {
+    for _ in 0..count {
+        values.push((value % 64) as u8);
+        value /= 64;
+    }
+}
+
+// Fumen measures a piece from a slightly different centre than SRS for a
+// few piece and rotation pairs; these are the offsets from fumen to SRS.
+fn centre_offset(piece: TetrominoType, orientation: Orientation) -> (i32, i32) {
+    match (piece, orientation) {
+        (TetrominoType::O, Orientation::West) => (1, -1),
+        (TetrominoType::O, Orientation::South) => (1, 0),
+        (TetrominoType::O, Orientation::North) => (0, -1),
+        (TetrominoType::I, Orientation::South) => (1, 0),
+        (TetrominoType::I, Orientation::West) => (0, -1),
+        (TetrominoType::S, Orientation::North) => (0, -1),
+        (TetrominoType::S, Orientation::East) => (-1, 0),
+        (TetrominoType::Z, Orientation::North) => (0, -1),
+        (TetrominoType::Z, Orientation::West) => (1, 0),
+        _ => (0, 0),
+    }
+}
+
+pub fn decode(fumen: &str) -> Result<Vec<Page>, FumenError> {
+    let data = fumen
+        .trim()
+        .strip_prefix(PREFIX)
+        .ok_or(FumenError::UnsupportedVersion)?
+        .replace('?', "");
+    let mut reader = Reader {
+        digits: data.bytes(),
+    };
+
+    let mut pages = Vec::new();
+    let mut previous = Field::new();
+    let mut comment = String::new();
+    let mut repeat = 0;
+    while !reader.is_empty() {
+        let mut field = previous.clone();
+        if repeat > 0 {
+            repeat -= 1;
+        } else {
+            let mut index = 0;
+            let mut changed = true;
+            while index < FIELD_BLOCKS {
+                let run = reader.poll(2)?;
+                let diff = run / FIELD_BLOCKS;
+                let count = run % FIELD_BLOCKS + 1;
+                if diff == GARBAGE as u32 && count == FIELD_BLOCKS {
+                    changed = false;
+                }
+                for _ in 0..count {
+                    if index >= FIELD_BLOCKS {
+                        return Err(FumenError::Malformed);
+                    }
+                    let (x, y) = Field::position(index);
+                    let value = field.get(x, y) as i32 + diff as i32 - GARBAGE as i32;
+                    field.set(x, y, value.clamp(0, GARBAGE as i32) as u8);
+                    index += 1;
+                }
+            }
+            if !changed {
+                repeat = reader.poll(1)?;
+            }
+        }
+
+        let mut action = reader.poll(3)?;
+        let kind = action % 8;
+        action /= 8;
+        let orientation = ROTATIONS[(action % 4) as usize];
+        action /= 4;
+        let (x, y) = Field::position(action % FIELD_BLOCKS);
+        action /= FIELD_BLOCKS;
+        let flags = Flags {
+            rise: action & 1 != 0,
+            mirror: action & 2 != 0,
+        };
+        let has_comment = action & 8 != 0;
+        let lock = action & 16 == 0;
+
+        if has_comment {
+            let length = reader.poll(2)? as usize;
+            let mut escaped = Vec::with_capacity(length + 3);
+            for _ in 0..length.div_ceil(4) {
+                let mut chunk = reader.poll(5)?;
+                for _ in 0..4 {
+                    let index = (chunk % COMMENT_BASE) as usize;
+                    escaped.push(*COMMENT_TABLE.get(index).ok_or(FumenError::Malformed)?);
+                    chunk /= COMMENT_BASE;
+                }
+            }
+            escaped.truncate(length);
+            comment = unescape(&String::from_utf8_lossy(&escaped));
+        }
+
+        let piece = piece_from_value(kind).map(|kind| {
+            let (dx, dy) = centre_offset(kind, orientation);
+            PieceLocation {
+                kind,
+                orientation,
+                x: x + dx,
+                y: y + dy,
+            }
+        });
+        let board = field
+            .to_board()
+            .ok_or(FumenError::TooTall { page: pages.len() })?;
+        previous = field.after(piece.as_ref(), lock, flags);
+        pages.push(Page {
+            board,
+            piece,
+            comment: comment.clone(),
+            lock,
+        });
+    }
+    Ok(pages)
+}
+
+/// Encodes `pages`, whose boards must be `FIELD_WIDTH` wide.
+pub fn encode(pages: &[Page]) -> Result<String, FumenError> {
+    let mut values = Vec::new();
+    let mut previous = Field::new();
+    let mut previous_comment = String::new();
+    // Index of the repeat counter for a run of unchanged fields, if any.
+    let mut repeat_at: Option<usize> = None;
+
+    for (i, page) in pages.iter().enumerate() {
+        let field = Field::from_board(&page.board, i)?;
+        if field != previous || repeat_at.is_none_or(|at| values[at] == 63) {
+            encode_field(&mut values, &previous, &field);
+            repeat_at = (field == previous).then(|| {
+                values.push(0);
+                values.len() - 1
+            });
+        } else if let Some(at) = repeat_at {
+            values[at] += 1;
+        }
+
+        let has_comment = page.comment != previous_comment;
+        let (kind, rotation, position) = match &page.piece {
+            Some(location) => {
+                let (dx, dy) = centre_offset(location.kind, location.orientation);
+                let (x, y) = (location.x - dx, location.y - dy);
+                let rotation = ROTATIONS
+                    .iter()
+                    .position(|&r| r == location.orientation)
+                    .unwrap_or(2);
+                (
+                    piece_value(location.kind) as u32,
+                    rotation as u32,
+                    ((FIELD_TOP - y - 1) * FIELD_WIDTH as i32 + x) as u32,
+                )
+            }
+            None => (0, 0, 0),
+        };
+        let colorize = i == 0;
+        let mut action = !page.lock as u32;
+        action = action * 2 + has_comment as u32;
+        action = action * 2 + colorize as u32;
+        action *= 4; // Mirror and rise are never set by this encoder.
+        action = action * FIELD_BLOCKS + position;
+        action = action * 4 + rotation;
+        action = action * 8 + kind;
+        push(&mut values, action, 3);
+
+        if has_comment {
+            let escaped: Vec<u8> = escape(&page.comment).into_bytes();
+            push(&mut values, escaped.len() as u32, 2);
+            for chunk in escaped.chunks(4) {
+                let value = chunk.iter().rev().fold(0, |value, &c| {
+                    let index = COMMENT_TABLE.iter().position(|&t| t == c).unwrap_or(0);
+                    value * COMMENT_BASE + index as u32
+                });
+                push(&mut values, value, 5);
+            }
+            previous_comment = page.comment.clone();
+        }
+
+        previous = field.after(page.piece.as_ref(), page.lock, Flags::default());
+    }
+
+    let data: String = values
+        .into_iter()
+        .map(|value| ALPHABET[value as usize] as char)
+        .collect();
+    // Fumen breaks long data with '?' after 42 characters and then every 47.
+    let mut out = String::from(PREFIX);
+    for (i, c) in data.chars().enumerate() {
+        if i >= 42 && (i - 42) % 47 == 0 {
+            out.push('?');
+        }
+        out.push(c);
+    }
+    Ok(out)
+}
+
+fn encode_field(values: &mut Vec<u8>, previous: &Field, field: &Field) {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for index in 0..FIELD_BLOCKS {
+        let (x, y) = Field::position(index);
+        let diff = (field.get(x, y) as i32 - previous.get(x, y) as i32 + GARBAGE as i32) as u32;
+        match runs.last_mut() {
+            Some((last, count)) if *last == diff => *count += 1,
+            _ => runs.push((diff, 1)),
+        }
+    }
+    for (diff, count) in runs {
+        push(values, diff * FIELD_BLOCKS + count - 1, 2);
+    }
+}
+
+// JavaScript's `escape`, which fumen applies to comments before packing.
+fn escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '@' | '*' | '_' | '+' | '-' | '.' | '/' => {
+                out.push(c)
+            }
+            c if (c as u32) < 256 => out.push_str(&format!("%{:02X}", c as u32)),
+            c => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    out.push_str(&format!("%u{:04X}", unit));
+                }
+            }
+        }
+    }
+    out
+}
+
+fn unescape(text: &str) -> String {
+    let mut units: Vec<u16> = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            let wide = chars.peek() == Some(&'u');
+            if wide {
+                chars.next();
+            }
+            let hex: String = chars.clone().take(if wide { 4 } else { 2 }).collect();
+            if let Ok(unit) = u16::from_str_radix(&hex, 16) {
+                units.push(unit);
+                for _ in 0..hex.len() {
+                    chars.next();
+                }
+                continue;
+            }
+            units.push('%' as u16);
+            if wide {
+                units.push('u' as u16);
+            }
+            continue;
+        }
+        let mut buffer = [0; 2];
+        units.extend_from_slice(c.encode_utf16(&mut buffer));
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// A one-page fumen of the game's board, with the active piece as the
+/// page's piece and the hold and queue in a quiz comment.
+pub fn encode_game(game: &Game) -> Result<String, FumenError> {
+    let (x, y) = game.piece_position();
+    let placement = Placement {
+        piece: game.current_piece().clone(),
+        x,
+        y,
+        inputs: Vec::new(),
+    };
+    let mut page = Page::new(game.board().clone());
+    // On a tall board the piece can be above the fumen field; leave it out.
+    page.piece = tbp::placement_location(&placement, game.board().height())
+        .filter(|location| location.y < FIELD_TOP);
+    let mut pieces = vec![game.current_piece().tetromino_type];
+    pieces.extend(game.upcoming());
+    page.comment = Queue {
+        hold: game.held_piece(),
+        pieces,
+    }
+    .to_comment();
+    encode(&[page])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::board::BUFFER_HEIGHT;
+
+    const EMPTY: &str = "v115@vhAAgH";
+    // An empty field with a T at the bottom centre and the quiz `#Q=[](T)I`.
+    const QUIZ: &str = "v115@vhAVQYVAFLDmClcJSAVDEHBEooRBUoAVBpAAAA";
+
+    // A standard board with `rows` at the bottom, `X` for a filled cell.
+    fn board(rows: &[&str]) -> Board {
+        let mut board = Board::new();
+        let top = board.height() - rows.len();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == 'X' {
+                    board.set(x, top + y, Cell::Filled);
+                }
+            }
+        }
+        board
+    }
+
+    fn location(kind: TetrominoType, orientation: Orientation, x: i32, y: i32) -> PieceLocation {
+        PieceLocation {
+            kind,
+            orientation,
+            x,
+            y,
+        }
+    }
+
+    #[test]
+    fn decodes_known_fumens() {
+        let pages = decode(EMPTY).unwrap();
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].board.is_empty());
+        assert!(pages[0].piece.is_none());
+        assert_eq!(pages[0].comment, "");
+
+        let pages = decode(QUIZ).unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(
+            pages[0].piece,
+            Some(location(TetrominoType::T, Orientation::North, 4, 0))
+        );
+        assert_eq!(pages[0].comment, "#Q=[](T)I");
+        assert_eq!(
+            pages[0].queue(),
+            Some(Queue {
+                hold: None,
+                pieces: vec![TetrominoType::T, TetrominoType::I],
+            })
+        );
+    }
+
+    #[test]
+    fn encodes_known_fumens() {
+        assert_eq!(encode(&[Page::new(Board::new())]).unwrap(), EMPTY);
+
+        let mut page = Page::new(Board::new());
+        page.piece = Some(location(TetrominoType::T, Orientation::North, 4, 0));
+        page.comment = "#Q=[](T)I".to_string();
+        assert_eq!(encode(&[page]).unwrap(), QUIZ);
+    }
+
+    #[test]
+    fn round_trips_several_pages() {
+        let stack = board(&["X.........", "XX.XXXXXXX", "XXXXXXXXX."]);
+        let mut first = Page::new(stack.clone());
+        first.piece = Some(location(TetrominoType::I, Orientation::East, 9, 1));
+        first.comment = "#Q=[O](I)TSZ".to_string();
+
+        // No piece locks here, so the next page's field repeats this one and
+        // is stored as a repeat count.
+        let cleared = board(&["X.........", "XX.XXXXXXX"]);
+        let second = Page::new(cleared.clone());
+        let mut third = Page::new(cleared);
+        third.comment = "Vérifié ✓".to_string();
+        third.piece = Some(location(TetrominoType::T, Orientation::South, 2, 2));
+        third.lock = false;
+        let mut fourth = Page::new(stack);
+        fourth.piece = Some(location(TetrominoType::O, Orientation::North, 4, 5));
+
+        let pages = vec![first, second, third, fourth];
+        let fumen = encode(&pages).unwrap();
+        let decoded = decode(&fumen).unwrap();
+        assert_eq!(decoded.len(), pages.len());
+        for (page, back) in pages.iter().zip(&decoded) {
+            assert!(back.board == page.board);
+            assert_eq!(back.piece, page.piece);
+            assert_eq!(back.lock, page.lock);
+            assert_eq!(back.comment, page.comment);
+        }
+        assert_eq!(encode(&decoded).unwrap(), fumen);
+    }
+
+    #[test]
+    fn rejects_bad_data() {
+        assert_eq!(
+            decode("v110@vhAAgH").err(),
+            Some(FumenError::UnsupportedVersion)
+        );
+        assert_eq!(decode("v115@vhA").err(), Some(FumenError::Malformed));
+        assert_eq!(decode("v115@vhAAg!").err(), Some(FumenError::Malformed));
+        // A run longer than the field.
+        assert_eq!(decode("v115@/hAAgH").err(), Some(FumenError::Malformed));
+    }
+
+    #[test]
+    fn rejects_boards_fumen_cannot_hold() {
+        let wide = Page::new(Board::with_size(12, DEFAULT_HEIGHT));
+        assert_eq!(
+            encode(&[wide]).err(),
+            Some(FumenError::WrongWidth { width: 12 })
+        );
+
+        let mut tall = Board::new();
+        tall.set(0, BUFFER_HEIGHT - 5, Cell::Filled);
+        let pages = [Page::new(Board::new()), Page::new(tall)];
+        assert_eq!(encode(&pages).err(), Some(FumenError::TooTall { page: 1 }));
+    }
+
+    #[test]
+    fn reads_and_writes_quiz_comments() {
+        let queue = Queue::from_comment("#Q=[I](T)SZO").unwrap();
+        assert_eq!(queue.hold, Some(TetrominoType::I));
+        assert_eq!(
+            queue.pieces,
+            [
+                TetrominoType::T,
+                TetrominoType::S,
+                TetrominoType::Z,
+                TetrominoType::O
+            ]
+        );
+        assert_eq!(queue.to_comment(), "#Q=[I](T)SZO");
+        assert_eq!(
+            Queue::from_comment("#Q=[](T)I;more").unwrap().pieces.len(),
+            2
+        );
+        assert_eq!(Queue::from_comment("just a note"), None);
+        assert_eq!(Queue::from_comment("#Q=[](T)X"), None);
+    }
+}
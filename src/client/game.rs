@@ -1,21 +1,173 @@
-use crate::client::board::{Board, Cell, HEIGHT, WIDTH};
+use crate::client::attack::{self, ClearKind, Spin};
+use crate::client::board::{Board, Cell, CellGrid, DEFAULT_HEIGHT, DEFAULT_WIDTH};
+use crate::client::config::Config;
+use crate::client::events::{GameEvent, MAX_EVENTS};
+use crate::client::finesse::{self, FinesseFault};
+use crate::client::input::Input;
+use crate::client::layout::{Area, Glyphs, Layout, Panel, TooSmall};
+use crate::client::randomizer::Randomizer;
+use crate::client::rules::{self, RuleSet};
+use crate::client::search;
 use crate::client::tetromino::{Tetromino, TetrominoType};
-use crossterm::{cursor::MoveTo, event::KeyCode, execute};
-use rand::Rng;
+use crate::client::timing::Timing;
+use crossterm::{cursor::MoveTo, execute};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::io::stdout;
+use std::time::Duration;
 
 const PREVIEW_WIDTH: usize = 4;
 const PREVIEW_HEIGHT: usize = 4;
-const PREVIEW_PADDING: i32 = 2;
 
-#[derive(Clone, PartialEq)]
+/// The top visible row turns red once the stack is this close to it.
+const DANGER_ROWS: usize = 4;
+
+/// Placements kept for undo; older ones are forgotten.
+const MAX_HISTORY: usize = 500;
+
+/// Lines cleared per level.
+const LINES_PER_LEVEL: u32 = 10;
+
+/// Offset of the piece randomizer's seed used for garbage hole columns, so
+/// that receiving garbage never changes the piece sequence.
+const GARBAGE_SEED_SALT: u64 = 0x0067_6172_6261_6765;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub enum GameState {
-    TitleScreen { selected_option: usize },
+    /// Set up but not started yet.
+    Ready,
     Playing,
     Paused,
     GameOver,
 }
 
+/// What the engine is waiting on between a piece locking and the next one
+/// spawning. Scoring and the board itself are updated at the lock; this only
+/// holds back the next piece and keeps what the renderer needs.
+#[derive(Clone, Serialize, Deserialize)]
+enum Delay {
+    /// Full `rows` of `board`, the stack as it was before they cleared, are
+    /// shown dissolving.
+    LineClear {
+        board: Box<Board>,
+        rows: Vec<usize>,
+        frames_left: u32,
+    },
+    /// Entry delay (ARE) before the next piece appears.
+    Entry { frames_left: u32 },
+}
+
+/// How a game topped out, following the guideline rules.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TopOut {
+    /// A new piece overlapped the stack where it spawned.
+    BlockOut,
+    /// A piece locked entirely inside the hidden buffer.
+    LockOut,
+    /// Garbage pushed blocks above the top of the buffer.
+    PushOut,
+}
+
+impl TopOut {
+    pub fn label(self) -> &'static str {
+        match self {
+            TopOut::BlockOut => "Block out",
+            TopOut::LockOut => "Lock out",
+            TopOut::PushOut => "Top out",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GameMode {
+    Marathon,
+    /// Flags placements that used more inputs than necessary.
+    Finesse,
+    /// Handcrafted challenges with a fixed piece sequence and a goal.
+    Puzzle,
+    /// Painting a starting position to play or save.
+    Editor,
+    /// The built-in bot plays while the user watches.
+    WatchAi,
+    /// An external Tetris Bot Protocol process plays while the user watches.
+    WatchBot,
+    /// Two players share the keyboard and send each other garbage.
+    Versus,
+    /// Versus against a remote player who connects to this instance.
+    HostOnline,
+    /// Versus against a remote player hosting elsewhere.
+    JoinOnline,
+    /// Rooms and free-for-all matches on a `tetrust-server` lobby.
+    Lobby,
+    /// Read-only view of a game broadcast by another instance.
+    Spectate,
+}
+
+impl GameMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            GameMode::Marathon => "Marathon",
+            GameMode::Finesse => "Finesse",
+            GameMode::Puzzle => "Puzzles",
+            GameMode::Editor => "Editor",
+            GameMode::WatchAi => "Watch AI",
+            GameMode::WatchBot => "Watch Bot",
+            GameMode::Versus => "Versus",
+            GameMode::HostOnline => "Host Online",
+            GameMode::JoinOnline => "Join Online",
+            GameMode::Lobby => "Online Lobby",
+            GameMode::Spectate => "Spectate",
+        }
+    }
+
+    /// Practice modes let the player take back placements. Competitive
+    /// modes and the watch modes, whose bots track the piece count, do not.
+    pub fn allows_undo(self) -> bool {
+        matches!(self, GameMode::Marathon | GameMode::Finesse)
+    }
+
+    /// The rules games in this mode are always played by, or `None` for
+    /// the configured rules. Finesse, puzzles, external bots and matches
+    /// against other players assume the guideline.
+    pub fn fixed_rules(self) -> Option<RuleSet> {
+        match self {
+            GameMode::Finesse
+            | GameMode::Puzzle
+            | GameMode::WatchBot
+            | GameMode::Versus
+            | GameMode::HostOnline
+            | GameMode::JoinOnline
+            | GameMode::Lobby => Some(RuleSet::GUIDELINE),
+            GameMode::Marathon | GameMode::Editor | GameMode::WatchAi | GameMode::Spectate => None,
+        }
+    }
+}
+
+/// Everything `lock_piece` can change, captured with the piece back at its
+/// spawn position so undo hands the player the same piece to place again.
+#[derive(Clone)]
+struct HistoryEntry {
+    board: Board,
+    current_piece: TetrominoType,
+    next_piece: TetrominoType,
+    held_piece: Option<TetrominoType>,
+    can_hold: bool,
+    score: u32,
+    lines: u32,
+    pieces_dealt: u64,
+    queue: VecDeque<TetrominoType>,
+    randomizer: Randomizer,
+    garbage_randomizer: Randomizer,
+    last_clear: ClearKind,
+    combo: u32,
+    b2b_chain: u32,
+    pending_garbage: u32,
+    outgoing_attack: u32,
+    attack_sent: u32,
+    finesse_faults: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Game {
     board: Board,
     current_piece: Tetromino,
@@ -23,23 +175,139 @@ pub struct Game {
     piece_x: i32,
     piece_y: i32,
     score: u32,
+    lines: u32,
     held_piece: Option<TetrominoType>,
     can_hold: bool,
     state: GameState,
+    /// Why the game ended, once it has topped out.
+    top_out: Option<TopOut>,
+    timing: Timing,
+    #[serde(default)]
+    rules: RuleSet,
+    /// Set between a lock and the next spawn while a delay runs.
+    delay: Option<Delay>,
+    /// Frames the piece has rested on the stack since it last reached a
+    /// new lowest row, `lowest_y`.
+    #[serde(default)]
+    lock_frames: u32,
+    #[serde(default)]
+    lowest_y: i32,
+    mode: GameMode,
+    piece_inputs: Vec<Input>,
+    finesse_faults: u32,
+    last_fault: Option<FinesseFault>,
+    pieces_dealt: u64,
+    /// Pieces dealt before the randomizer's, e.g. from an imported diagram.
+    #[serde(default)]
+    queue: VecDeque<TetrominoType>,
+    randomizer: Randomizer,
+    garbage_randomizer: Randomizer,
+    last_move_rotated: bool,
+    last_clear: ClearKind,
+    combo: u32,
+    /// Consecutive difficult clears (Tetrises and T-spins); 2+ is back-to-back.
+    b2b_chain: u32,
+    pending_garbage: u32,
+    outgoing_attack: u32,
+    attack_sent: u32,
+    /// States before each lock, newest last. Not saved with the game.
+    #[serde(skip)]
+    history: Vec<HistoryEntry>,
+    /// States undone since the last lock, newest last.
+    #[serde(skip)]
+    redo: Vec<HistoryEntry>,
+    /// Set once undo has been used, and shown with the results.
+    undo_used: bool,
+    /// Events not yet drained, oldest first. Not saved with the game.
+    #[serde(skip)]
+    events: VecDeque<GameEvent>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Game {
     pub fn new() -> Self {
+        Self::with_seed(rand::random())
+    }
+
+    /// Creates a game whose piece sequence is fully determined by `seed`,
+    /// played by the default rules.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rules(seed, RuleSet::default())
+    }
+
+    /// Creates a seeded game played by `rules`, on a standard board and
+    /// with the default delays.
+    pub fn with_rules(seed: u64, rules: RuleSet) -> Self {
+        let mut game = Self::build(seed, DEFAULT_WIDTH, DEFAULT_HEIGHT, rules);
+        game.timing = Timing::default();
+        game
+    }
+
+    /// Creates a seeded game for `mode` on the board and with the delays
+    /// from `config`, played by the mode's own rules if it has any and the
+    /// configured ones otherwise.
+    pub fn with_config(seed: u64, config: &Config, mode: GameMode) -> Self {
+        let (width, height) = config.board_size;
+        let rules = mode.fixed_rules().unwrap_or(config.rules);
+        let mut game = Self::build(seed, width, height, rules);
+        game.timing = config.timing;
+        game
+    }
+
+    /// Creates a seeded game on a `width` by `height` board, with the
+    /// default rules and no line clear or entry delay.
+    pub fn with_size(seed: u64, width: usize, height: usize) -> Self {
+        Self::build(seed, width, height, RuleSet::default())
+    }
+
+    fn build(seed: u64, width: usize, height: usize, rules: RuleSet) -> Self {
+        let board = Board::with_size(width, height);
+        let mut randomizer = Randomizer::with_kind(seed, rules.randomizer);
+        // Deal the preview; `spawn_piece` below moves it into play and deals
+        // the next one behind it.
+        let next_piece = Tetromino::new(randomizer.next_piece());
+        let (piece_x, piece_y) = rules::spawn_position(&board, &next_piece);
         let mut game = Self {
-            board: Board::new(),
-            current_piece: Tetromino::new(TetrominoType::I),
-            next_piece: Tetromino::new(TetrominoType::I),
-            piece_x: WIDTH as i32 / 2 - 2,
-            piece_y: 0,
+            board,
+            current_piece: next_piece.clone(),
+            next_piece,
+            piece_x,
+            piece_y,
             score: 0,
+            lines: 0,
             held_piece: None,
             can_hold: true,
-            state: GameState::TitleScreen { selected_option: 0 },
+            state: GameState::Ready,
+            top_out: None,
+            timing: Timing::INSTANT,
+            rules,
+            delay: None,
+            lock_frames: 0,
+            lowest_y: piece_y,
+            mode: GameMode::Marathon,
+            piece_inputs: Vec::new(),
+            finesse_faults: 0,
+            last_fault: None,
+            pieces_dealt: 0,
+            queue: VecDeque::new(),
+            randomizer,
+            garbage_randomizer: Randomizer::new(seed ^ GARBAGE_SEED_SALT),
+            last_move_rotated: false,
+            last_clear: ClearKind::None,
+            combo: 0,
+            b2b_chain: 0,
+            pending_garbage: 0,
+            outgoing_attack: 0,
+            attack_sent: 0,
+            history: Vec::new(),
+            redo: Vec::new(),
+            undo_used: false,
+            events: VecDeque::new(),
         };
         game.spawn_piece();
         game
@@ -47,49 +315,95 @@ impl Game {
 
     pub fn spawn_piece(&mut self) {
         self.current_piece = self.next_piece.clone();
-        let mut rng = rand::thread_rng();
-        let piece_types = [
-            TetrominoType::I,
-            TetrominoType::O,
-            TetrominoType::T,
-            TetrominoType::L,
-            TetrominoType::J,
-            TetrominoType::S,
-            TetrominoType::Z,
-        ];
-        self.next_piece = Tetromino::new(piece_types[rng.gen_range(0..piece_types.len())]);
-
-        self.piece_x = WIDTH as i32 / 2 - 2;
-        self.piece_y = 0;
+        let next = match self.queue.pop_front() {
+            Some(piece) => piece,
+            None => self.randomizer.next_piece(),
+        };
+        self.next_piece = Tetromino::new(next);
+        self.pieces_dealt += 1;
+
+        self.move_to_spawn();
+        self.emit(GameEvent::PieceSpawned {
+            piece: self.current_piece.tetromino_type,
+            x: self.piece_x,
+            y: self.piece_y,
+        });
 
         if self.check_collision() {
-            self.state = GameState::GameOver;
+            self.top_out(TopOut::BlockOut);
         }
 
         self.can_hold = true;
+        self.piece_inputs.clear();
+        self.last_move_rotated = false;
+    }
+
+    /// Applies a player input and records it for finesse checking. Inputs
+    /// during a line clear or entry delay are ignored.
+    pub fn apply_input(&mut self, input: Input) {
+        if !self.piece_active() {
+            return;
+        }
+        self.piece_inputs.push(input);
+        match input {
+            Input::Left => {
+                self.move_piece(-1, 0);
+            }
+            Input::Right => {
+                self.move_piece(1, 0);
+            }
+            Input::DasLeft => self.shift_to_wall(-1),
+            Input::DasRight => self.shift_to_wall(1),
+            Input::SoftDrop => {
+                self.move_piece(0, 1);
+            }
+            Input::RotateCw => self.rotate(true),
+            Input::RotateCcw => self.rotate(false),
+            Input::Hold => self.hold_piece(),
+            Input::HardDrop => self.hard_drop(),
+        }
     }
 
     pub fn rotate(&mut self, is_clockwise: bool) {
-        let original_x = self.piece_x;
-        let original_y = self.piece_y;
-        let original_cells = self.current_piece.cells;
+        let Some((piece, x, y, kick)) = self.rules.rotation.rotate(
+            &self.board,
+            &self.current_piece,
+            self.piece_x,
+            self.piece_y,
+            is_clockwise,
+        ) else {
+            return;
+        };
+        self.current_piece = piece;
+        (self.piece_x, self.piece_y) = (x, y);
+        self.last_move_rotated = true;
+        self.note_lowest();
+        self.emit(GameEvent::Rotated {
+            clockwise: is_clockwise,
+            kick,
+        });
+    }
 
-        if is_clockwise {
-            self.current_piece.rotate_clockwise();
-        } else {
-            self.current_piece.rotate_anticlockwise();
+    pub fn shift_to_wall(&mut self, dx: i32) {
+        if self.slide(dx, 0) > 0 {
+            self.emit_moved();
         }
+    }
 
-        // Revert if rotation causes collision
-        if self.check_collision() {
-            self.current_piece.cells = original_cells;
-            self.piece_x = original_x;
-            self.piece_y = original_y;
+    /// Advances gravity by one row. A piece that cannot fall locks here if
+    /// the rules have no lock delay; otherwise `frame` counts it down.
+    pub fn tick(&mut self) {
+        if !self.piece_active() {
+            return;
+        }
+        if !self.move_piece(0, 1) && self.rules.lock_delay == 0 {
+            self.lock_piece();
         }
     }
 
     pub fn hard_drop(&mut self) {
-        while self.move_piece(0, 1) {} // Move until collision
+        let rows = self.slide(0, 1);
+        self.emit(GameEvent::HardDropped { rows });
         self.lock_piece();
     }
 
@@ -97,276 +411,522 @@ impl Game {
         self.state.clone()
     }
 
-    fn draw_piece_preview(&self, piece: &Tetromino, x: i32, y: i32, title: &str) {
-        // Draw top border with title
-        execute!(stdout(), MoveTo(x as u16, y as u16)).unwrap();
-        println!("┌────────┐"); // 4 cells × 2 chars per cell = 8 chars wide
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
 
-        execute!(stdout(), MoveTo((x + 1) as u16, y as u16)).unwrap();
-        print!(" {} ", title);
+    pub fn score(&self) -> u32 {
+        self.score
+    }
 
-        // Create a temporary mini-board
-        let mut preview = vec![vec![Cell::Empty; PREVIEW_WIDTH]; PREVIEW_HEIGHT];
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
 
-        // Calculate centering for piece
-        for py in 0..4 {
-            for px in 0..4 {
-                if piece.cells[py][px] {
-                    if py < PREVIEW_HEIGHT && px < PREVIEW_WIDTH {
-                        preview[py][px] = Cell::Filled;
-                    }
+    /// Starts at 1 and goes up every `LINES_PER_LEVEL` lines.
+    pub fn level(&self) -> u32 {
+        self.lines / LINES_PER_LEVEL + 1
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn current_piece(&self) -> &Tetromino {
+        &self.current_piece
+    }
+
+    pub fn piece_position(&self) -> (i32, i32) {
+        (self.piece_x, self.piece_y)
+    }
+
+    pub fn next_piece(&self) -> &Tetromino {
+        &self.next_piece
+    }
+
+    pub fn held_piece(&self) -> Option<TetrominoType> {
+        self.held_piece
+    }
+
+    /// The upcoming pieces the rules show, soonest first.
+    pub fn preview(&self) -> Vec<TetrominoType> {
+        let mut randomizer = self.randomizer;
+        self.upcoming()
+            .into_iter()
+            .chain(std::iter::repeat_with(|| randomizer.next_piece()))
+            .take(self.rules.preview)
+            .collect()
+    }
+
+    /// The next piece followed by any queued ahead of the randomizer.
+    pub fn upcoming(&self) -> Vec<TetrominoType> {
+        std::iter::once(self.next_piece.tetromino_type)
+            .chain(self.queue.iter().copied())
+            .collect()
+    }
+
+    /// Replaces the board and hold, and deals `pieces` in order before the
+    /// randomizer takes over. The first piece becomes the active one.
+    pub fn load_position(
+        &mut self,
+        board: Board,
+        hold: Option<TetrominoType>,
+        pieces: &[TetrominoType],
+    ) {
+        self.board = board;
+        self.held_piece = hold;
+        self.delay = None;
+        self.queue = pieces.iter().copied().collect();
+        // Deal twice so the queue's first piece is active and its second next.
+        if !self.queue.is_empty() {
+            self.spawn_piece();
+            self.spawn_piece();
+        }
+        self.move_to_spawn();
+        self.can_hold = true;
+    }
+
+    /// Whether the rules allow hold and it has not been used on this piece.
+    pub fn can_hold(&self) -> bool {
+        self.rules.hold && self.can_hold
+    }
+
+    /// Number of pieces dealt into play so far, the current one included.
+    pub fn pieces_dealt(&self) -> u64 {
+        self.pieces_dealt
+    }
+
+    pub fn last_clear(&self) -> ClearKind {
+        self.last_clear
+    }
+
+    /// Pieces in a row that have cleared lines, up to the last one placed.
+    pub fn combo(&self) -> u32 {
+        self.combo
+    }
+
+    /// Whether the next tetris or spin clear continues a back-to-back chain.
+    pub fn back_to_back(&self) -> bool {
+        self.b2b_chain > 0
+    }
+
+    /// Total lines of attack sent after cancelling incoming garbage.
+    pub fn attack_sent(&self) -> u32 {
+        self.attack_sent
+    }
+
+    /// Garbage waiting to rise the next time a piece locks without clearing.
+    pub fn pending_garbage(&self) -> u32 {
+        self.pending_garbage
+    }
+
+    /// Queues garbage sent by an opponent.
+    pub fn receive_garbage(&mut self, lines: u32) {
+        if lines == 0 {
+            return;
+        }
+        self.pending_garbage += lines;
+        self.emit(GameEvent::GarbageReceived { lines });
+    }
+
+    /// Hands over the attack produced since the last call, for delivery to
+    /// an opponent.
+    pub fn take_outgoing_attack(&mut self) -> u32 {
+        std::mem::take(&mut self.outgoing_attack)
+    }
+
+    /// Where the current piece appears, and where it returns to after a hold.
+    pub fn spawn_position(&self) -> (i32, i32) {
+        rules::spawn_position(&self.board, &self.current_piece)
+    }
+
+    // Puts the current piece at its spawn position with a fresh lock delay.
+    fn move_to_spawn(&mut self) {
+        (self.piece_x, self.piece_y) = self.spawn_position();
+        self.lowest_y = self.piece_y;
+        self.lock_frames = 0;
+    }
+
+    pub fn rules(&self) -> RuleSet {
+        self.rules
+    }
+
+    /// Time between gravity ticks at the current level.
+    pub fn gravity_interval(&self) -> Duration {
+        self.rules.gravity.row_interval(self.level())
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    /// Changes the delays for the pieces that lock from now on.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+
+    /// False between a lock and the next spawn, while a line clear or entry
+    /// delay runs and there is no piece to control.
+    pub fn piece_active(&self) -> bool {
+        self.delay.is_none()
+    }
+
+    /// Advances a running line clear or entry delay by one engine frame,
+    /// spawning the next piece when it ends, or the lock delay of a piece
+    /// resting on the stack, locking it when it runs out. Returns false if
+    /// there was nothing to wait for.
+    pub fn frame(&mut self) -> bool {
+        if self.state != GameState::Playing {
+            return false;
+        }
+        let frames_left = match self.delay.as_mut() {
+            Some(Delay::LineClear { frames_left, .. } | Delay::Entry { frames_left }) => {
+                frames_left
+            }
+            None => return self.lock_delay_frame(),
+        };
+        *frames_left = frames_left.saturating_sub(1);
+        if *frames_left > 0 {
+            return true;
+        }
+        self.delay = match self.delay.take() {
+            Some(Delay::LineClear { .. }) if self.timing.are > 0 => Some(Delay::Entry {
+                frames_left: self.timing.are,
+            }),
+            _ => None,
+        };
+        if self.delay.is_none() {
+            self.spawn_piece();
+        }
+        true
+    }
+
+    // Counts down the lock delay while the piece rests on the stack.
+    fn lock_delay_frame(&mut self) -> bool {
+        let resting = self
+            .board
+            .collides(&self.current_piece, self.piece_x, self.piece_y + 1);
+        if self.rules.lock_delay == 0 || !resting {
+            return false;
+        }
+        self.lock_frames += 1;
+        if self.lock_frames >= self.rules.lock_delay {
+            self.lock_piece();
+        }
+        true
+    }
+
+    pub fn top_out_reason(&self) -> Option<TopOut> {
+        self.top_out
+    }
+
+    fn top_out(&mut self, reason: TopOut) {
+        self.top_out = Some(reason);
+        self.state = GameState::GameOver;
+        self.emit(GameEvent::TopOut { reason });
+    }
+
+    /// Hands over the events queued since the last call, oldest first.
+    pub fn drain_events(&mut self) -> impl Iterator<Item = GameEvent> + '_ {
+        self.events.drain(..)
+    }
+
+    fn emit(&mut self, event: GameEvent) {
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    /// Whether the stack is within `DANGER_ROWS` of the top of the visible
+    /// field.
+    pub fn in_danger(&self) -> bool {
+        let top = self.board.height() - self.board.column_heights().into_iter().max().unwrap_or(0);
+        top < self.board.buffer_height() + DANGER_ROWS
+    }
+
+    // The visible rows of `board` with its full `rows` dissolving from the
+    // centre outwards as the clear delay runs down.
+    fn clearing_cells(&self, board: &Board, rows: &[usize], frames_left: u32) -> CellGrid {
+        let mut cells = board.to_cells();
+        let width = board.width();
+        let elapsed = self.timing.clear_delay.saturating_sub(frames_left) as usize + 1;
+        let reach = width * elapsed / self.timing.clear_delay.max(1) as usize;
+        for &y in rows.iter().filter(|&&y| y >= board.buffer_height()) {
+            let row = &mut cells[y - board.buffer_height()];
+            for (x, cell) in row.iter_mut().enumerate() {
+                // Twice the distance from the centre line, so 0 or 1 in the middle.
+                if (2 * x + 1).abs_diff(width) < reach {
+                    *cell = Cell::Empty;
                 }
             }
         }
+        cells
+    }
 
-        // Draw the preview contents
-        for row in 0..PREVIEW_HEIGHT {
-            execute!(stdout(), MoveTo(x as u16, (y + 1 + row as i32) as u16)).unwrap();
-            print!("│"); // Left border
+    fn draw_piece_preview(&self, layout: &Layout, piece: &Tetromino, x: i32, y: i32, title: &str) {
+        let glyphs = layout.glyphs;
+        let inner = PREVIEW_WIDTH * glyphs.cell_width() as usize;
+
+        // Draw top border with title
+        layout.area.put(x, y, &format!("┌{}┐", "─".repeat(inner)));
+        layout.area.put(x + 1, y, &format!(" {} ", title));
+
+        // Create a temporary mini-board
+        let mut preview = vec![vec![Cell::Empty; PREVIEW_WIDTH]; PREVIEW_HEIGHT];
 
-            for col in 0..PREVIEW_WIDTH {
-                print!("{}", preview[row][col].to_string());
+        // Calculate centering for piece
+        for (px, py) in piece.minos() {
+            if (py as usize) < PREVIEW_HEIGHT && (px as usize) < PREVIEW_WIDTH {
+                preview[py as usize][px as usize] = Cell::Filled;
             }
+        }
 
-            println!("│"); // Right border
+        // Draw the preview contents between the side borders
+        for (row, cells) in preview.iter().enumerate() {
+            let line: String = cells.iter().map(|&cell| glyphs.cell(cell)).collect();
+            layout
+                .area
+                .put(x, y + 1 + row as i32, &format!("│{}│", line));
         }
 
         // Draw bottom border
-        execute!(
-            stdout(),
-            MoveTo(x as u16, (y + PREVIEW_HEIGHT as i32 + 1) as u16)
-        )
-        .unwrap();
-        println!("└────────┘");
+        layout.area.put(
+            x,
+            y + PREVIEW_HEIGHT as i32 + 1,
+            &format!("└{}┘", "─".repeat(inner)),
+        );
+    }
+
+    /// Where the board, previews and stats go in `area`, drawn with
+    /// `glyphs` if given.
+    pub fn layout(&self, area: Area, glyphs: Option<Glyphs>) -> Result<Layout, TooSmall> {
+        Layout::fit(area, &self.board, true, glyphs, |glyphs| Panel {
+            width: PREVIEW_WIDTH as i32 * glyphs.cell_width() + 2,
+            // The next and hold boxes, one row apart.
+            height: (PREVIEW_HEIGHT as i32 + 2) * 2 + 1,
+        })
     }
 
-    pub fn draw(&self) {
-        let (term_width, term_height) = crossterm::terminal::size().unwrap_or((80, 24));
+    pub fn draw(&self, glyphs: Option<Glyphs>) {
+        let area = Area::terminal();
         print!("\x1B[2J");
 
         match &self.state {
-            GameState::TitleScreen { selected_option } => {
-                self.draw_title_screen(*selected_option, term_width, term_height);
-            }
-            GameState::Paused => {
-                self.draw_pause_screen(term_width, term_height);
-            }
-            GameState::Playing | GameState::GameOver => {
-                self.draw_game_screen(term_width, term_height);
+            // The player's own pause screen is a scene; this is what
+            // spectators see.
+            GameState::Paused => area.centre_lines(&["Paused".to_string()]),
+            GameState::Ready | GameState::Playing | GameState::GameOver => {
+                self.draw_game_screen(area, glyphs, &[]);
             }
         }
     }
 
-    fn draw_title_screen(&self, selected_option: usize, term_width: u16, term_height: u16) {
-        let logo = vec!["---------------", "   Tet-Rust!   ", "---------------"];
-
-        let menu_options = vec!["Play", "Help", "GitHub", "Quit"];
-
-        let start_y = (term_height as i32) / 3; // Move logo higher up
-                                                // Draw logo
-        for (i, line) in logo.iter().enumerate() {
-            execute!(
-                stdout(),
-                MoveTo(
-                    (term_width as i32 - line.len() as i32) as u16 / 2,
-                    (start_y + i as i32) as u16
-                ),
-            )
-            .unwrap();
-            println!("{}", line);
-        }
-
-        for (i, option) in menu_options.iter().enumerate() {
-            execute!(
-                stdout(),
-                MoveTo(
-                    (term_width as i32 - option.len() as i32 - 4) as u16 / 2,
-                    (start_y + logo.len() as i32 + 1 + i as i32) as u16
-                ),
-            )
-            .unwrap();
-            print!(
-                "{} {}",
-                if i == selected_option { ">" } else { " " },
-                option
-            );
+    /// Draws the board and stats with `footer` under them, whatever the
+    /// state.
+    pub fn draw_with_footer(&self, glyphs: Option<Glyphs>, footer: &[String]) {
+        let area = Area::terminal();
+        print!("\x1B[2J");
+        self.draw_game_screen(area, glyphs, footer);
+    }
+
+    fn draw_game_screen(&self, area: Area, glyphs: Option<Glyphs>, footer: &[String]) {
+        let layout = match self.layout(area, glyphs) {
+            Ok(layout) => layout,
+            Err(too_small) => return too_small.draw(area),
+        };
+
+        let mut status_y = self.draw_playfield(&layout);
+
+        // Draw game over message if needed; puzzles show their own result
+        if self.state == GameState::GameOver && self.mode != GameMode::Puzzle {
+            let game_over_text = match self.top_out {
+                Some(reason) => format!("Game Over: {}", reason.label()),
+                None => "Game Over!".to_string(),
+            };
+            layout.centre(status_y, &game_over_text);
+            status_y += 1;
+        }
+        for (i, line) in footer.iter().enumerate() {
+            layout.centre(status_y + i as i32, line);
         }
+    }
 
-        let line = "Created by Han Yi";
+    /// Draws the board with its previews, garbage meter and stats where
+    /// `layout` puts them. Returns the first row below the stats that is
+    /// free for messages.
+    pub fn draw_playfield(&self, layout: &Layout) -> i32 {
+        let board_height = self.board.visible_height();
+        // Only the visible rows are drawn; `temp_board` row 0 is board row
+        // `buffer`.
+        let buffer = self.board.buffer_height() as i32;
 
-        execute!(
-            stdout(),
-            MoveTo(
-                (term_width as i32 - line.len() as i32) as u16 / 2,
-                (start_y + logo.len() as i32 + menu_options.len() as i32 + 2) as u16
-            ),
-        )
-        .unwrap();
-        println!("{}", line);
-    }
-
-    fn draw_game_screen(&self, term_width: u16, term_height: u16) {
-        let board_width = WIDTH;
-        let board_height = HEIGHT;
-        let start_x = (term_width as i32 - board_width as i32 * 2) / 2;
-        let start_y = (term_height as i32 - board_height as i32) / 2;
-
-        let mut temp_board = self.board.clone();
-
-        // Draw landing preview
-        let landing_y = self.get_landing_position();
-        for y in 0..4 {
-            for x in 0..4 {
-                if self.current_piece.cells[y][x] {
-                    let board_x = self.piece_x + x as i32;
-                    let board_y = landing_y + y as i32;
-                    if board_y >= 0
-                        && board_y < HEIGHT as i32
-                        && board_x >= 0
-                        && board_x < WIDTH as i32
-                    {
-                        if temp_board.get(board_x as usize, board_y as usize) == Cell::Empty {
-                            temp_board.set(board_x as usize, board_y as usize, Cell::Preview);
-                        }
-                    }
-                }
+        let mut temp_board = match &self.delay {
+            Some(Delay::LineClear {
+                board,
+                rows,
+                frames_left,
+            }) => self.clearing_cells(board, rows, *frames_left),
+            _ => self.board.to_cells(),
+        };
+        if self.in_danger() {
+            for cell in temp_board[0]
+                .iter_mut()
+                .filter(|cell| **cell == Cell::Empty)
+            {
+                *cell = Cell::Danger;
             }
         }
 
-        // Draw current piece
-        for y in 0..4 {
-            for x in 0..4 {
-                if self.current_piece.cells[y][x] {
-                    let board_x = self.piece_x + x as i32;
-                    let board_y = self.piece_y + y as i32;
-                    if board_y >= 0
-                        && board_y < HEIGHT as i32
-                        && board_x >= 0
-                        && board_x < WIDTH as i32
-                    {
-                        temp_board.set(board_x as usize, board_y as usize, Cell::Filled);
-                    }
-                }
+        // Draw landing preview if the rules show it; during a delay there is
+        // no piece to draw
+        let landing_y = self.landing_position();
+        let piece_minos: Vec<(i32, i32)> = if self.piece_active() {
+            self.current_piece.minos().collect()
+        } else {
+            Vec::new()
+        };
+        for &(x, y) in piece_minos.iter().filter(|_| self.rules.ghost) {
+            let board_x = self.piece_x + x;
+            let board_y = landing_y + y;
+            if self.board.contains(board_x, board_y)
+                && board_y >= buffer
+                && temp_board[(board_y - buffer) as usize][board_x as usize] != Cell::Filled
+            {
+                temp_board[(board_y - buffer) as usize][board_x as usize] = Cell::Preview;
             }
         }
 
-        // Calculate preview positions - both on right side
-        let preview_x = start_x + board_width as i32 * 2 + PREVIEW_PADDING;
+        // Draw current piece
+        for (x, y) in piece_minos {
+            let board_x = self.piece_x + x;
+            let board_y = self.piece_y + y;
+            if self.board.contains(board_x, board_y) && board_y >= buffer {
+                temp_board[(board_y - buffer) as usize][board_x as usize] = Cell::Filled;
+            }
+        }
 
-        // Draw next piece (always shown)
-        self.draw_piece_preview(&self.next_piece, preview_x, start_y, "NEXT");
+        // Draw next and hold previews in the side panel, if it fits
+        let preview = self.preview();
+        if let Some((preview_x, preview_y)) = layout.side {
+            if let Some(&next) = preview.first() {
+                let next_piece = Tetromino::new(next);
+                self.draw_piece_preview(layout, &next_piece, preview_x, preview_y, "NEXT");
+            }
 
-        // Draw hold piece if it exists
-        if let Some(held_type) = self.held_piece {
-            let held_piece = Tetromino::new(held_type);
-            let hold_y = start_y + PREVIEW_HEIGHT as i32 + 3;
-            self.draw_piece_preview(&held_piece, preview_x, hold_y, "HOLD");
+            if let Some(held_type) = self.held_piece {
+                let held_piece = Tetromino::new(held_type);
+                let hold_y = preview_y + PREVIEW_HEIGHT as i32 + 3;
+                self.draw_piece_preview(layout, &held_piece, preview_x, hold_y, "HOLD");
+            }
         }
 
         // Draw main board
-        for y in 0..HEIGHT {
-            execute!(
-                stdout(),
-                MoveTo(start_x as u16, (start_y + y as i32) as u16),
-            )
-            .unwrap();
-
-            for x in 0..WIDTH {
-                print!("{}", temp_board.get(x, y).to_string());
+        draw_cells(&temp_board, layout.board_x, layout.board_y, layout.glyphs);
+
+        // Draw incoming garbage meter along the left edge, rising from the floor
+        let meter_height = (self.pending_garbage as usize).min(board_height);
+        let meter_x = layout.board_x - layout.glyphs.cell_width();
+        for y in board_height - meter_height..board_height {
+            layout.area.put(
+                meter_x,
+                layout.board_y + y as i32,
+                layout.glyphs.cell(Cell::Danger),
+            );
+        }
+
+        // Draw score, with the previews that have no box: all of them
+        // without the side panel, or those after the first
+        let mut score_text = format!("Score: {}", self.score);
+        let letters = |pieces: &[TetrominoType]| -> String {
+            pieces
+                .iter()
+                .map(|piece| format!(" {}", piece.letter()))
+                .collect()
+        };
+        if layout.side.is_none() {
+            if !preview.is_empty() {
+                score_text.push_str(&format!("  Next:{}", letters(&preview)));
+            }
+            if let Some(held_type) = self.held_piece {
+                score_text.push_str(&format!("  Hold: {}", held_type.letter()));
             }
+        } else if preview.len() > 1 {
+            score_text.push_str(&format!("  Then:{}", letters(&preview[1..])));
         }
+        layout.centre(layout.below_board(), &score_text);
 
-        // Draw score
-        let score_text = format!("Score: {}", self.score);
-        execute!(
-            stdout(),
-            MoveTo(
-                (start_x + (board_width as i32 * 2 - score_text.len() as i32) / 2) as u16,
-                (start_y + board_height as i32 + 1) as u16
-            ),
-        )
-        .unwrap();
-        println!("{}", score_text);
-
-        // Draw game over message if needed
-        if let GameState::GameOver = self.state {
-            let game_over_text = "Game Over!";
-            execute!(
-                stdout(),
-                MoveTo(
-                    (start_x + (board_width as i32 * 2 - game_over_text.len() as i32) / 2) as u16,
-                    (start_y + board_height as i32 + 2) as u16
-                ),
-            )
-            .unwrap();
-            println!("{}", game_over_text);
-
-            let restart_text = "Press 'r' to restart or 'q' to quit";
-            execute!(
-                stdout(),
-                MoveTo(
-                    (start_x + (board_width as i32 * 2 - restart_text.len() as i32) / 2) as u16,
-                    (start_y + board_height as i32 + 3) as u16
-                ),
-            )
-            .unwrap();
-            println!("{}", restart_text);
-        }
-    }
-
-    fn draw_pause_screen(&self, term_width: u16, term_height: u16) {
-        let help_text = vec![
-            "Controls:",
-            "←/→: Move piece",
-            "A: Rotate clockwise",
-            "D: Rotate anti-clockwise",
-            "↓: Soft drop",
-            "Space: Hard drop",
-            "C: Hold piece",
-            "Esc/P: Pause/Unpause",
-            "R: Restart game",
-            "Q: Quit game",
-            "",
-            "Press Esc or P to resume",
-        ];
-
-        let start_y = (term_height as i32 - help_text.len() as i32) / 2;
-
-        for (i, line) in help_text.iter().enumerate() {
-            execute!(
-                stdout(),
-                MoveTo(
-                    (term_width as i32 - line.len() as i32) as u16 / 2,
-                    (start_y + i as i32) as u16
-                ),
-            )
-            .unwrap();
-            println!("{}", line);
-        }
-    }
-
-    pub fn handle_title_input(&mut self, key: KeyCode) {
-        if let GameState::TitleScreen { selected_option } = &mut self.state {
-            match key {
-                KeyCode::Up => {
-                    *selected_option = selected_option.checked_sub(1).unwrap_or(3);
-                }
-                KeyCode::Down => {
-                    *selected_option = (*selected_option + 1) % 4;
-                }
-                KeyCode::Enter => match *selected_option {
-                    0 => self.state = GameState::Playing,
-                    1 => self.state = GameState::Paused,
-                    2 => if let Ok(()) = open::that("https://github.com/yhanyi/TetRust") {},
-                    3 => std::process::exit(0),
-                    _ => {}
-                },
-                _ => {}
+        let mut status_y = layout.below_board() + 1;
+
+        if self.undo_used {
+            layout.centre(status_y, "(undo used)");
+            status_y += 1;
+        }
+
+        if self.last_clear != ClearKind::None {
+            let mut clear_text = self.last_clear.label().to_string();
+            if self.b2b_chain > 1 && self.last_clear.is_difficult() {
+                clear_text.push_str(" B2B");
+            }
+            if self.combo > 1 {
+                clear_text.push_str(&format!(" {} Combo", self.combo - 1));
+            }
+            layout.centre(status_y, &clear_text);
+            status_y += 1;
+        }
+
+        if self.mode == GameMode::Finesse {
+            let mut finesse_lines = vec![format!("Finesse faults: {}", self.finesse_faults)];
+            if let Some(fault) = &self.last_fault {
+                let sequence = |inputs: &[Input]| {
+                    inputs
+                        .iter()
+                        .map(|input| input.symbol())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                };
+                finesse_lines.push(format!(
+                    "{:?}: {} (optimal {})",
+                    fault.piece,
+                    sequence(&fault.used),
+                    sequence(&fault.optimal)
+                ));
+            }
+            for line in finesse_lines {
+                layout.centre(status_y, &line);
+                status_y += 1;
             }
         }
+
+        status_y
     }
 
     pub fn move_piece(&mut self, dx: i32, dy: i32) -> bool {
+        let moved = self.step(dx, dy);
+        if moved {
+            self.emit_moved();
+        }
+        moved
+    }
+
+    // Moves the piece as far as it goes in steps of `(dx, dy)`, returning
+    // how many it took, with no event for each one.
+    fn slide(&mut self, dx: i32, dy: i32) -> u32 {
+        let mut steps = 0;
+        while self.step(dx, dy) {
+            steps += 1;
+        }
+        steps
+    }
+
+    fn emit_moved(&mut self) {
+        self.emit(GameEvent::Moved {
+            x: self.piece_x,
+            y: self.piece_y,
+        });
+    }
+
+    fn step(&mut self, dx: i32, dy: i32) -> bool {
         self.piece_x += dx;
         self.piece_y += dy;
 
@@ -375,61 +935,96 @@ impl Game {
             self.piece_y -= dy;
             false
         } else {
+            // Any shift or fall after a rotation, by the player or by
+            // gravity, means the piece was not spun into place.
+            self.last_move_rotated = false;
+            self.note_lowest();
             true
         }
     }
 
-    fn check_collision(&self) -> bool {
-        for y in 0..4 {
-            for x in 0..4 {
-                if self.current_piece.cells[y][x] {
-                    let board_x = self.piece_x + x as i32;
-                    let board_y = self.piece_y + y as i32;
-
-                    if board_x < 0 || board_x >= WIDTH as i32 || board_y >= HEIGHT as i32 {
-                        return true;
-                    }
-
-                    if board_y >= 0
-                        && self.board.get(board_x as usize, board_y as usize) == Cell::Filled
-                    {
-                        return true;
-                    }
-                }
-            }
+    // Restarts the lock delay when the piece reaches a row lower than any
+    // it has been on, so it cannot be stalled forever in one place.
+    fn note_lowest(&mut self) {
+        if self.piece_y > self.lowest_y {
+            self.lowest_y = self.piece_y;
+            self.lock_frames = 0;
         }
-        false
+    }
+
+    fn check_collision(&self) -> bool {
+        self.board
+            .collides(&self.current_piece, self.piece_x, self.piece_y)
     }
 
     pub fn lock_piece(&mut self) {
-        let piece = self.current_piece.cells;
-        let piece_x = self.piece_x;
-        let piece_y = self.piece_y;
-
-        for y in 0..4 {
-            for x in 0..4 {
-                if piece[y][x] {
-                    let board_x = piece_x + x as i32;
-                    let board_y = piece_y + y as i32;
-                    if board_y >= 0
-                        && board_y < HEIGHT as i32
-                        && board_x >= 0
-                        && board_x < WIDTH as i32
-                    {
-                        self.board
-                            .set(board_x as usize, board_y as usize, Cell::Filled);
-                    }
-                }
+        if self.mode.allows_undo() {
+            if self.history.len() == MAX_HISTORY {
+                self.history.remove(0);
             }
+            self.history.push(self.history_entry());
+            self.redo.clear();
         }
 
-        self.clear_lines();
-        self.spawn_piece();
+        if self.mode == GameMode::Finesse {
+            self.check_finesse();
+        }
+
+        let spin = if self.last_move_rotated {
+            attack::detect_spin(&self.board, &self.current_piece, self.piece_x, self.piece_y)
+        } else {
+            Spin::None
+        };
+
+        let buffer = self.board.buffer_height() as i32;
+        let locked_out = self
+            .current_piece
+            .minos()
+            .all(|(_, dy)| self.piece_y + dy < buffer);
+
+        self.board
+            .place(&self.current_piece, self.piece_x, self.piece_y);
+        self.emit(GameEvent::Locked {
+            piece: self.current_piece.tetromino_type,
+            x: self.piece_x,
+            y: self.piece_y,
+            rotation: self.current_piece.rotation,
+        });
+        if locked_out && self.rules.lock_out {
+            self.top_out(TopOut::LockOut);
+            return;
+        }
+
+        let rows = self.board.full_rows();
+        let before =
+            (!rows.is_empty() && self.timing.clear_delay > 0).then(|| Box::new(self.board.clone()));
+        self.clear_lines(spin);
+        if self.state == GameState::GameOver {
+            return;
+        }
+        self.delay = match before {
+            Some(board) => Some(Delay::LineClear {
+                board,
+                rows,
+                frames_left: self.timing.clear_delay,
+            }),
+            None if self.timing.are > 0 => Some(Delay::Entry {
+                frames_left: self.timing.are,
+            }),
+            None => None,
+        };
+        if self.delay.is_none() {
+            self.spawn_piece();
+        }
     }
 
     pub fn hold_piece(&mut self) {
-        if self.can_hold {
+        if self.can_hold() {
             let current_type = self.current_piece.tetromino_type;
+            self.emit(GameEvent::Held {
+                piece: current_type,
+                swapped: self.held_piece,
+            });
             self.current_piece = match self.held_piece {
                 Some(held_type) => Tetromino::new(held_type),
                 None => {
@@ -438,65 +1033,168 @@ impl Game {
                 }
             };
             self.held_piece = Some(current_type);
-            self.piece_x = WIDTH as i32 / 2 - 2;
-            self.piece_y = 0;
+            self.move_to_spawn();
             self.can_hold = false;
+            self.piece_inputs.clear();
+        }
+    }
+
+    fn history_entry(&self) -> HistoryEntry {
+        HistoryEntry {
+            board: self.board.clone(),
+            current_piece: self.current_piece.tetromino_type,
+            next_piece: self.next_piece.tetromino_type,
+            held_piece: self.held_piece,
+            can_hold: self.can_hold,
+            score: self.score,
+            lines: self.lines,
+            pieces_dealt: self.pieces_dealt,
+            queue: self.queue.clone(),
+            randomizer: self.randomizer,
+            garbage_randomizer: self.garbage_randomizer,
+            last_clear: self.last_clear,
+            combo: self.combo,
+            b2b_chain: self.b2b_chain,
+            pending_garbage: self.pending_garbage,
+            outgoing_attack: self.outgoing_attack,
+            attack_sent: self.attack_sent,
+            finesse_faults: self.finesse_faults,
         }
     }
 
-    fn get_landing_position(&self) -> i32 {
-        let mut test_y = self.piece_y;
-        while !self.would_collide(self.piece_x, test_y + 1) {
-            test_y += 1;
+    fn restore(&mut self, entry: HistoryEntry) {
+        self.board = entry.board;
+        self.current_piece = Tetromino::new(entry.current_piece);
+        self.next_piece = Tetromino::new(entry.next_piece);
+        self.held_piece = entry.held_piece;
+        self.can_hold = entry.can_hold;
+        self.score = entry.score;
+        self.lines = entry.lines;
+        self.pieces_dealt = entry.pieces_dealt;
+        self.queue = entry.queue;
+        self.randomizer = entry.randomizer;
+        self.garbage_randomizer = entry.garbage_randomizer;
+        self.last_clear = entry.last_clear;
+        self.combo = entry.combo;
+        self.b2b_chain = entry.b2b_chain;
+        self.pending_garbage = entry.pending_garbage;
+        self.outgoing_attack = entry.outgoing_attack;
+        self.attack_sent = entry.attack_sent;
+        self.finesse_faults = entry.finesse_faults;
+        self.move_to_spawn();
+        self.piece_inputs.clear();
+        self.last_move_rotated = false;
+        self.last_fault = None;
+        self.state = GameState::Playing;
+        self.top_out = None;
+        self.delay = None;
+    }
+
+    /// Takes back the last placement, including after a top out. Returns
+    /// false if there is nothing to undo or the mode does not allow it.
+    pub fn undo(&mut self) -> bool {
+        if !self.mode.allows_undo() {
+            return false;
         }
-        test_y
+        let Some(entry) = self.history.pop() else {
+            return false;
+        };
+        self.redo.push(self.history_entry());
+        self.restore(entry);
+        self.undo_used = true;
+        self.emit(GameEvent::Undone);
+        true
     }
 
-    // Checks for potential collision
-    fn would_collide(&self, test_x: i32, test_y: i32) -> bool {
-        for y in 0..4 {
-            for x in 0..4 {
-                if self.current_piece.cells[y][x] {
-                    let board_x = test_x + x as i32;
-                    let board_y = test_y + y as i32;
+    /// Replays a placement taken back with `undo`. The restored randomizer
+    /// state means the same pieces come next.
+    pub fn redo(&mut self) -> bool {
+        let Some(entry) = self.redo.pop() else {
+            return false;
+        };
+        self.history.push(self.history_entry());
+        self.restore(entry);
+        self.emit(GameEvent::Redone);
+        true
+    }
 
-                    if board_x < 0 || board_x >= WIDTH as i32 || board_y >= HEIGHT as i32 {
-                        return true;
-                    }
+    pub fn undo_used(&self) -> bool {
+        self.undo_used
+    }
 
-                    if board_y >= 0
-                        && self.board.get(board_x as usize, board_y as usize) == Cell::Filled
-                    {
-                        return true;
-                    }
-                }
-            }
+    pub fn landing_position(&self) -> i32 {
+        search::drop_y(&self.board, &self.current_piece, self.piece_x, self.piece_y)
+    }
+
+    // Judges the placement about to lock against the shortest input sequence
+    fn check_finesse(&mut self) {
+        let spawned = Tetromino::new(self.current_piece.tetromino_type);
+        if let Some(fault) = finesse::check(
+            self.rules.rotation,
+            &self.board,
+            &spawned,
+            rules::spawn_position(&self.board, &spawned),
+            &self.piece_inputs,
+            &self.current_piece,
+            (self.piece_x, self.piece_y),
+        ) {
+            self.finesse_faults += 1;
+            self.last_fault = Some(fault);
         }
-        false
     }
 
-    fn clear_lines(&mut self) {
-        let mut lines_cleared = 0;
-        for y in (0..HEIGHT).rev() {
-            let mut line_filled = true;
-            for x in 0..WIDTH {
-                if self.board.get(x, y) == Cell::Empty {
-                    line_filled = false;
-                    break;
-                }
-            }
-            if line_filled {
-                lines_cleared += 1;
-                self.board.clear_line(y);
-            }
+    fn clear_lines(&mut self, spin: Spin) {
+        let lines_cleared = self.board.clear_full_lines();
+        let level = self.level();
+        self.lines += lines_cleared as u32;
+
+        let kind = ClearKind::new(lines_cleared, spin);
+        self.last_clear = kind;
+        if kind != ClearKind::None {
+            self.emit(GameEvent::LinesCleared {
+                kind,
+                combo: if lines_cleared > 0 { self.combo } else { 0 },
+            });
         }
-        self.score += match lines_cleared {
-            1 => 100,
-            2 => 300,
-            3 => 500,
-            4 => 800,
-            _ => 0,
-        };
+        if self.level() > level {
+            self.emit(GameEvent::LevelUp {
+                level: self.level(),
+            });
+        }
+        if lines_cleared > 0 {
+            let attack =
+                attack::attack(kind, self.combo, self.b2b_chain > 0, self.board.is_empty());
+            self.combo += 1;
+            self.b2b_chain = if kind.is_difficult() {
+                self.b2b_chain + 1
+            } else {
+                0
+            };
+
+            // Attack cancels incoming garbage before anything is sent
+            let cancelled = attack.min(self.pending_garbage);
+            self.pending_garbage -= cancelled;
+            self.outgoing_attack += attack - cancelled;
+            self.attack_sent += attack - cancelled;
+        } else {
+            self.combo = 0;
+            self.rise_garbage();
+        }
+
+        self.score += self.rules.scoring.points(lines_cleared as u32, level);
+    }
+
+    fn rise_garbage(&mut self) {
+        if self.pending_garbage == 0 {
+            return;
+        }
+        let hole = self
+            .garbage_randomizer
+            .next_below(self.board.width() as u64) as usize;
+        if self.board.add_garbage(self.pending_garbage as usize, hole) {
+            self.top_out(TopOut::PushOut);
+        }
+        self.pending_garbage = 0;
     }
 
     pub fn toggle_pause(&mut self) {
@@ -507,12 +1205,208 @@ impl Game {
         };
     }
 
+    /// Begins play in `mode`.
+    pub fn start(&mut self, mode: GameMode) {
+        self.mode = mode;
+        self.state = GameState::Playing;
+    }
+
     pub fn restart(&mut self) {
-        self.board = Board::new();
+        self.board = self.board.cleared();
         self.score = 0;
+        self.lines = 0;
+        self.finesse_faults = 0;
+        self.last_fault = None;
+        self.last_clear = ClearKind::None;
+        self.combo = 0;
+        self.b2b_chain = 0;
+        self.pending_garbage = 0;
+        self.outgoing_attack = 0;
+        self.attack_sent = 0;
+        self.history.clear();
+        self.redo.clear();
+        self.undo_used = false;
         self.state = GameState::Playing;
+        self.top_out = None;
+        self.delay = None;
         self.held_piece = None;
         self.can_hold = true;
         self.spawn_piece();
     }
 }
+
+/// Draws a full board grid with its top-left corner at (`start_x`, `start_y`).
+/// The caller's layout makes sure it fits on screen.
+pub fn draw_cells(cells: &CellGrid, start_x: i32, start_y: i32, glyphs: Glyphs) {
+    for (y, row) in cells.iter().enumerate() {
+        execute!(
+            stdout(),
+            MoveTo(start_x as u16, (start_y + y as i32) as u16),
+        )
+        .unwrap();
+
+        for &cell in row {
+            print!("{}", glyphs.cell(cell));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A puzzle-style game: `rows` at the bottom of a 10x20 board, `X` for a
+    // filled cell, dealing `pieces` first.
+    fn position(rows: &[&str], pieces: &[TetrominoType]) -> Game {
+        let mut board = Board::new();
+        let top = board.height() - rows.len();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == 'X' {
+                    board.set(x, top + y, Cell::Filled);
+                }
+            }
+        }
+        let mut game = Game::with_rules(1, RuleSet::default());
+        game.set_timing(Timing::INSTANT);
+        game.load_position(board, None, pieces);
+        game.start(GameMode::Marathon);
+        game
+    }
+
+    // The "Twist In" puzzle: a T-spin mini single.
+    fn twist_in() -> Game {
+        position(
+            &["XXX.......", "XX...XXXXX", "XXX.XXXXXX"],
+            &[TetrominoType::T],
+        )
+    }
+
+    fn play(game: &mut Game, inputs: &[Input]) {
+        for &input in inputs {
+            game.apply_input(input);
+        }
+    }
+
+    fn is_spin(kind: ClearKind) -> bool {
+        matches!(kind, ClearKind::TSpin(_) | ClearKind::TSpinMini(_))
+    }
+
+    #[test]
+    fn rotating_in_the_air_and_dropping_is_not_a_spin() {
+        let mut game = twist_in();
+        play(&mut game, &[Input::Left, Input::RotateCw, Input::HardDrop]);
+        assert!(!is_spin(game.last_clear()));
+
+        let mut game = twist_in();
+        play(&mut game, &[Input::Left, Input::RotateCw]);
+        for _ in 0..game.board().height() {
+            game.tick();
+        }
+        play(&mut game, &[Input::HardDrop]);
+        assert!(!is_spin(game.last_clear()));
+    }
+
+    #[test]
+    fn rotating_into_place_is_a_spin() {
+        let mut game = twist_in();
+        play(&mut game, &[Input::RotateCw, Input::Left]);
+        while game.move_piece(0, 1) {}
+        play(&mut game, &[Input::RotateCcw, Input::HardDrop]);
+        assert_eq!(game.last_clear(), ClearKind::TSpinMini(1));
+    }
+
+    #[test]
+    fn a_new_game_deals_its_first_piece_once() {
+        let firsts: Vec<TetrominoType> = (0..20)
+            .map(|seed| {
+                let mut game = Game::with_size(seed, DEFAULT_WIDTH, DEFAULT_HEIGHT);
+                let piece = game.current_piece().tetromino_type;
+                let (x, y) = game.piece_position();
+                assert_eq!(game.pieces_dealt(), 1);
+                assert_eq!(
+                    game.drain_events().collect::<Vec<_>>(),
+                    [GameEvent::PieceSpawned { piece, x, y }]
+                );
+                piece
+            })
+            .collect();
+        assert!(firsts.iter().any(|&piece| piece != TetrominoType::I));
+    }
+
+    #[test]
+    fn events_follow_a_piece_from_spawn_to_clear() {
+        let mut game = position(&["XXXXX.XXXX"], &[TetrominoType::T, TetrominoType::I]);
+        game.drain_events().for_each(drop);
+        game.spawn_piece();
+        let (x, y) = game.piece_position();
+        play(&mut game, &[Input::RotateCw, Input::HardDrop]);
+        let bottom = game.board().height() as i32 - 4;
+        let events: Vec<GameEvent> = game.drain_events().collect();
+        assert_eq!(
+            events,
+            [
+                GameEvent::PieceSpawned {
+                    piece: TetrominoType::I,
+                    x,
+                    y,
+                },
+                GameEvent::Rotated {
+                    clockwise: true,
+                    kick: 0,
+                },
+                GameEvent::HardDropped {
+                    rows: (bottom - y) as u32,
+                },
+                GameEvent::Locked {
+                    piece: TetrominoType::I,
+                    x,
+                    y: bottom,
+                    rotation: 1,
+                },
+                GameEvent::LinesCleared {
+                    kind: ClearKind::Single,
+                    combo: 0,
+                },
+                GameEvent::PieceSpawned {
+                    piece: game.current_piece().tetromino_type,
+                    x: game.piece_position().0,
+                    y: game.piece_position().1,
+                },
+            ]
+        );
+
+        game.undo();
+        assert_eq!(game.drain_events().collect::<Vec<_>>(), [GameEvent::Undone]);
+        game.redo();
+        assert_eq!(game.drain_events().collect::<Vec<_>>(), [GameEvent::Redone]);
+    }
+
+    // The whole game as saved, apart from the record that undo was used.
+    fn state(game: &Game) -> serde_json::Value {
+        let mut state = serde_json::to_value(game).unwrap();
+        state.as_object_mut().unwrap().remove("undo_used");
+        state
+    }
+
+    #[test]
+    fn undo_then_redo_restores_the_same_game() {
+        let mut game = position(
+            &["XXXXX.XXXX", "XXXXX.XXXX"],
+            &[TetrominoType::I, TetrominoType::O],
+        );
+        let before = state(&game);
+        play(&mut game, &[Input::RotateCw, Input::HardDrop]);
+        assert_eq!(game.last_clear(), ClearKind::Double);
+        assert!(game.attack_sent() > 0);
+        let after = state(&game);
+
+        assert!(game.undo());
+        assert_eq!(game.attack_sent(), 0);
+        assert_eq!(game.take_outgoing_attack(), 0);
+        assert_eq!(state(&game), before);
+
+        assert!(game.redo());
+        assert_eq!(state(&game), after);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::client::game::Game;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+/// A single player action. Keyboard handling, bots and replays all drive
+/// `Game` through these so that every source of input obeys the same rules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Input {
+    Left,
+    Right,
+    /// Shift all the way to the left wall, as when holding the key past DAS.
+    DasLeft,
+    /// Shift all the way to the right wall.
+    DasRight,
+    SoftDrop,
+    RotateCw,
+    RotateCcw,
+    Hold,
+    HardDrop,
+}
+
+impl Input {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Input::Left => "←",
+            Input::Right => "→",
+            Input::DasLeft => "⇇",
+            Input::DasRight => "⇉",
+            Input::SoftDrop => "↓",
+            Input::RotateCw => "↻",
+            Input::RotateCcw => "↺",
+            Input::Hold => "H",
+            Input::HardDrop => "⤓",
+        }
+    }
+}
+
+/// Default single-player key bindings. Keys that act on the game as a whole,
+/// such as pause and restart, are handled by the caller.
+pub fn key_to_input(key: KeyEvent) -> Option<Input> {
+    let shifted = key.modifiers.contains(KeyModifiers::SHIFT);
+    Some(match key.code {
+        KeyCode::Left if shifted => Input::DasLeft,
+        KeyCode::Right if shifted => Input::DasRight,
+        KeyCode::Left => Input::Left,
+        KeyCode::Right => Input::Right,
+        KeyCode::Down => Input::SoftDrop,
+        KeyCode::Char('a') => Input::RotateCcw,
+        KeyCode::Char('s') => Input::RotateCw,
+        KeyCode::Char('c') => Input::Hold,
+        KeyCode::Char(' ') => Input::HardDrop,
+        _ => return None,
+    })
+}
+
+/// Anything that plays a `Game` by issuing inputs, such as the built-in AI
+/// or an external bot process.
+pub trait Controller {
+    /// Returns the next input to apply, or `None` if nothing should be
+    /// pressed this frame.
+    fn next_input(&mut self, game: &Game) -> Option<Input>;
+
+    /// Forgets any planned inputs, e.g. after the game restarts.
+    fn reset(&mut self);
+
+    /// Why the controller has stopped playing, if it has.
+    fn error(&self) -> Option<&str> {
+        None
+    }
+}
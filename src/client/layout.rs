@@ -0,0 +1,259 @@
+//! Placing the board and its panels in the space the terminal has. A layout
+//! is worked out from the terminal size on every draw, so resizing takes
+//! effect on the next frame. When the full layout does not fit, it switches
+//! to one-column glyphs and then drops the side panel; when not even the
+//! bare board fits, callers draw `TooSmall` instead of a clipped board.
+
+use crate::client::board::{Board, Cell};
+use crossterm::{cursor::MoveTo, execute, style::Print};
+use std::io::stdout;
+
+/// Rows kept free under the board for the score and status lines when
+/// there is room for them. Lines that do not fit are dropped.
+const STATUS_ROWS: i32 = 4;
+
+/// Columns between the board and the side panel.
+const PANEL_GAP: i32 = 2;
+
+/// How board cells are drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Glyphs {
+    /// Emoji squares, two columns per cell.
+    Wide,
+    /// ASCII, one column per cell, for small terminals.
+    Compact,
+}
+
+impl Glyphs {
+    /// Parses a glyph set name; `auto` is `None`.
+    pub fn parse(name: &str) -> Result<Option<Glyphs>, String> {
+        match name.trim() {
+            "auto" => Ok(None),
+            "wide" => Ok(Some(Glyphs::Wide)),
+            "compact" => Ok(Some(Glyphs::Compact)),
+            other => Err(format!(
+                "glyphs must be auto, wide or compact, not {:?}",
+                other
+            )),
+        }
+    }
+
+    /// Terminal columns one cell takes up.
+    pub fn cell_width(self) -> i32 {
+        match self {
+            Glyphs::Wide => 2,
+            Glyphs::Compact => 1,
+        }
+    }
+
+    pub fn cell(self, cell: Cell) -> &'static str {
+        match self {
+            Glyphs::Wide => cell.to_string(),
+            Glyphs::Compact => match cell {
+                Cell::Empty => ".",
+                Cell::Filled => "#",
+                Cell::Preview => "+",
+                Cell::Cursor => "@",
+                Cell::Danger => "!",
+            },
+        }
+    }
+}
+
+/// A rectangle of the terminal, in columns and rows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Area {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Area {
+    /// The whole terminal.
+    pub fn terminal() -> Area {
+        let (width, height) = crossterm::terminal::size().unwrap_or((80, 24));
+        Area {
+            x: 0,
+            y: 0,
+            width: width as i32,
+            height: height as i32,
+        }
+    }
+
+    pub fn right(&self) -> i32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height
+    }
+
+    /// Splits the area into `count` side-by-side columns of equal width.
+    pub fn columns(&self, count: i32) -> Vec<Area> {
+        let width = self.width / count.max(1);
+        (0..count)
+            .map(|i| Area {
+                x: self.x + width * i,
+                width,
+                ..*self
+            })
+            .collect()
+    }
+
+    /// The area without its top `rows` rows.
+    pub fn below(&self, rows: i32) -> Area {
+        let rows = rows.clamp(0, self.height);
+        Area {
+            y: self.y + rows,
+            height: self.height - rows,
+            ..*self
+        }
+    }
+
+    /// Prints `text` at (`x`, `y`), cut to the part inside the area. Rows
+    /// outside it are skipped entirely, so nothing ever lands at a wrapped
+    /// or scrolled position.
+    pub fn put(&self, x: i32, y: i32, text: &str) {
+        if y < self.y || y >= self.bottom() {
+            return;
+        }
+        let skip = (self.x - x).max(0) as usize;
+        let x = x.max(self.x);
+        let room = (self.right() - x).max(0) as usize;
+        let text: String = text.chars().skip(skip).take(room).collect();
+        if text.is_empty() {
+            return;
+        }
+        execute!(stdout(), MoveTo(x as u16, y as u16), Print(text)).unwrap();
+    }
+
+    /// Prints `text` centred on row `y`.
+    pub fn centre(&self, y: i32, text: &str) {
+        let x = self.x + (self.width - text.chars().count() as i32).max(0) / 2;
+        self.put(x, y, text);
+    }
+
+    /// Prints `lines` centred in the area, both ways.
+    pub fn centre_lines(&self, lines: &[String]) {
+        let top = self.y + (self.height - lines.len() as i32).max(0) / 2;
+        for (i, line) in lines.iter().enumerate() {
+            self.centre(top + i as i32, line);
+        }
+    }
+}
+
+/// The size of a panel beside the board.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Panel {
+    pub width: i32,
+    pub height: i32,
+}
+
+/// The area was too small for even the compact board.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TooSmall {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl TooSmall {
+    /// Replaces whatever would have been drawn in `area` with a notice.
+    pub fn draw(&self, area: Area) {
+        area.centre_lines(&[
+            "Terminal too small".to_string(),
+            format!("need {}x{}", self.width, self.height),
+            format!("have {}x{}", area.width, area.height),
+        ]);
+    }
+}
+
+/// Where a board and its panels go within an area.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub area: Area,
+    pub glyphs: Glyphs,
+    /// Top-left corner of the board's visible rows.
+    pub board_x: i32,
+    pub board_y: i32,
+    /// Top-left corner of the side panel, if there is room for it.
+    pub side: Option<(i32, i32)>,
+    board_columns: i32,
+    board_rows: i32,
+}
+
+impl Layout {
+    /// Fits `board` into `area`, with a one-cell garbage meter on its left
+    /// if `meter` is set and a `side` panel on its right, which may depend
+    /// on the glyphs. Tries wide glyphs with the panel, compact glyphs with
+    /// the panel, then each without it, skipping any set other than `only`
+    /// when one is given.
+    pub fn fit(
+        area: Area,
+        board: &Board,
+        meter: bool,
+        only: Option<Glyphs>,
+        side: impl Fn(Glyphs) -> Panel,
+    ) -> Result<Layout, TooSmall> {
+        let rows = board.visible_height() as i32;
+        let candidates = [
+            (Glyphs::Wide, true),
+            (Glyphs::Compact, true),
+            (Glyphs::Wide, false),
+            (Glyphs::Compact, false),
+        ];
+        for (glyphs, with_side) in candidates {
+            if only.is_some_and(|only| only != glyphs) {
+                continue;
+            }
+            let cell = glyphs.cell_width();
+            let columns = board.width() as i32 * cell;
+            let meter_width = if meter { cell } else { 0 };
+            let panel = with_side.then(|| side(glyphs));
+            let width = meter_width + columns + panel.map_or(0, |panel| PANEL_GAP + panel.width);
+            let content = rows.max(panel.map_or(0, |panel| panel.height));
+            if width > area.width || content + 1 > area.height {
+                continue;
+            }
+
+            let left = area.x + (area.width - width) / 2;
+            let board_x = left + meter_width;
+            let board_y = area.y + (area.height - content - STATUS_ROWS).max(0) / 2;
+            return Ok(Layout {
+                area,
+                glyphs,
+                board_x,
+                board_y,
+                side: panel.map(|_| (board_x + columns + PANEL_GAP, board_y)),
+                board_columns: columns,
+                board_rows: rows,
+            });
+        }
+        let cell = only.unwrap_or(Glyphs::Compact).cell_width();
+        Err(TooSmall {
+            width: (meter as i32 + board.width() as i32) * cell,
+            height: rows + 1,
+        })
+    }
+
+    /// The first row under the board.
+    pub fn below_board(&self) -> i32 {
+        self.board_y + self.board_rows
+    }
+
+    /// Prints `text` centred under or over the board on row `y`, cut to the
+    /// layout's area.
+    pub fn centre(&self, y: i32, text: &str) {
+        let x = self.board_x + (self.board_columns - text.chars().count() as i32) / 2;
+        self.area.put(x, y, text);
+    }
+
+    /// Board cell under terminal position (`column`, `row`), counted from
+    /// the top-left visible cell. May be outside the board.
+    pub fn cell_at(&self, column: i32, row: i32) -> (i32, i32) {
+        (
+            (column - self.board_x).div_euclid(self.glyphs.cell_width()),
+            row - self.board_y,
+        )
+    }
+}
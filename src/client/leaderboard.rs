@@ -0,0 +1,85 @@
+//! Best scores for the single-player modes, kept in the data directory.
+//! Games where undo was used, and games a bot played, are not ranked.
+
+use crate::client::game::{Game, GameMode};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const LEADERBOARD_FILE_NAME: &str = "leaderboard.json";
+
+/// Scores kept per mode.
+pub const MAX_ENTRIES: usize = 10;
+
+/// Modes with a leaderboard, in the order they are shown.
+pub const MODES: [GameMode; 2] = [GameMode::Marathon, GameMode::Finesse];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub score: u32,
+    pub lines: u32,
+}
+
+/// Scores for each mode, best first, keyed by the mode's label.
+pub type Leaderboard = BTreeMap<String, Vec<Entry>>;
+
+fn leaderboard_path(dir: &Path) -> PathBuf {
+    dir.join(LEADERBOARD_FILE_NAME)
+}
+
+/// The leaderboard in the data directory `dir`. A missing or unreadable
+/// one starts empty rather than failing.
+pub fn load(dir: &Path) -> Leaderboard {
+    fs::read_to_string(leaderboard_path(dir))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(dir: &Path, leaderboard: &Leaderboard) -> io::Result<()> {
+    let path = leaderboard_path(dir);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(
+        path,
+        serde_json::to_vec_pretty(leaderboard).map_err(io::Error::from)?,
+    )
+}
+
+/// Whether a finished `game` can be ranked.
+pub fn eligible(game: &Game) -> bool {
+    MODES.contains(&game.mode()) && !game.undo_used() && game.score() > 0
+}
+
+/// Ranks a finished `game` and saves the leaderboard in `dir`. Returns
+/// its place, counting from 1, if it made the top `MAX_ENTRIES`.
+pub fn submit(dir: &Path, game: &Game) -> io::Result<Option<usize>> {
+    if !eligible(game) {
+        return Ok(None);
+    }
+    let mut leaderboard = load(dir);
+    let entries = leaderboard
+        .entry(game.mode().label().to_string())
+        .or_default();
+    // Ties go to the earlier game.
+    let place = entries
+        .iter()
+        .position(|entry| entry.score < game.score())
+        .unwrap_or(entries.len());
+    if place >= MAX_ENTRIES {
+        return Ok(None);
+    }
+    entries.insert(
+        place,
+        Entry {
+            score: game.score(),
+            lines: game.lines(),
+        },
+    );
+    entries.truncate(MAX_ENTRIES);
+    save(dir, &leaderboard)?;
+    Ok(Some(place + 1))
+}
@@ -0,0 +1,574 @@
+//! The scenes around a game: the title screen and the screens it leads to.
+
+use crate::client::cli;
+use crate::client::config::Config;
+use crate::client::editor::EditorScene;
+use crate::client::game::GameMode;
+use crate::client::layout::Area;
+use crate::client::leaderboard::{self, Leaderboard};
+use crate::client::online;
+use crate::client::play;
+use crate::client::puzzle;
+use crate::client::rules;
+use crate::client::save;
+use crate::client::scene::{Context, Scene, Transition};
+use crate::client::spectate;
+use crate::client::timing::Timing;
+use crate::client::versus::VersusScene;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::path::Path;
+
+/// Modes offered by the mode select screen, in order.
+const MODES: [GameMode; 11] = [
+    GameMode::Marathon,
+    GameMode::Finesse,
+    GameMode::Puzzle,
+    GameMode::Editor,
+    GameMode::WatchAi,
+    GameMode::WatchBot,
+    GameMode::Versus,
+    GameMode::HostOnline,
+    GameMode::JoinOnline,
+    GameMode::Lobby,
+    GameMode::Spectate,
+];
+
+/// Moves a menu selection of `len` items for Up and Down, wrapping around.
+pub fn step(selected: usize, len: usize, key: KeyCode) -> usize {
+    match key {
+        KeyCode::Up => selected.checked_sub(1).unwrap_or(len - 1),
+        KeyCode::Down => (selected + 1) % len,
+        _ => selected,
+    }
+}
+
+/// Lines for a menu with `selected` marked.
+pub fn menu_lines(items: &[String], selected: usize) -> Vec<String> {
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| format!("{} {}  ", if i == selected { ">" } else { " " }, item))
+        .collect()
+}
+
+/// Clears the screen and prints `lines` centred.
+pub fn draw_lines(lines: &[String]) {
+    print!("\x1B[2J");
+    Area::terminal().centre_lines(lines);
+}
+
+/// Starts `mode` and returns the scene that plays it, or one saying why it
+/// could not start.
+pub fn launch(ctx: &mut Context, mode: GameMode) -> Box<dyn Scene> {
+    match mode {
+        GameMode::Versus => Box::new(VersusScene::new(&ctx.config, ctx.seed.take())),
+        GameMode::HostOnline => online::host(),
+        GameMode::JoinOnline => online::join(),
+        GameMode::Lobby => online::lobby(),
+        GameMode::Spectate => spectate::watch(),
+        GameMode::Puzzle => puzzle::browse(&ctx.config.home),
+        GameMode::Editor => Box::new(EditorScene::new(&ctx.config)),
+        mode => match play::start(ctx, mode) {
+            Ok(scene) => scene,
+            Err(err) => Box::new(MessageScene::new(format!(
+                "Could not start {}: {}",
+                mode.label(),
+                err
+            ))),
+        },
+    }
+}
+
+/// What a `TextInput` did with a key.
+pub enum Entry {
+    Editing,
+    Done(String),
+    Cancelled,
+}
+
+/// A line of text being typed, with a label above it.
+pub struct TextInput {
+    label: String,
+    text: String,
+}
+
+impl TextInput {
+    /// Starts out holding `default`.
+    pub fn new(label: impl Into<String>, default: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            text: default.into(),
+        }
+    }
+
+    /// Enter finishes the text and Esc abandons it.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Entry {
+        match key.code {
+            KeyCode::Enter => return Entry::Done(std::mem::take(&mut self.text)),
+            KeyCode::Esc => return Entry::Cancelled,
+            KeyCode::Backspace => {
+                self.text.pop();
+            }
+            KeyCode::Char(c) => self.text.push(c),
+            _ => {}
+        }
+        Entry::Editing
+    }
+
+    pub fn draw(&self) {
+        draw_lines(&[
+            self.label.clone(),
+            format!("> {}_", self.text),
+            "Enter: confirm  Esc: back".to_string(),
+        ]);
+    }
+}
+
+/// Decides where a `PromptScene` goes with the text entered.
+type OnEnter = Box<dyn FnMut(&mut Context, String) -> Transition>;
+
+/// Asks for a line of text, then hands it to `on_enter` for the scene to
+/// go to next. Esc goes back.
+pub struct PromptScene {
+    input: TextInput,
+    on_enter: OnEnter,
+}
+
+impl PromptScene {
+    pub fn new(
+        label: impl Into<String>,
+        default: impl Into<String>,
+        on_enter: impl FnMut(&mut Context, String) -> Transition + 'static,
+    ) -> Self {
+        Self {
+            input: TextInput::new(label, default),
+            on_enter: Box::new(on_enter),
+        }
+    }
+}
+
+impl Scene for PromptScene {
+    fn handle_key(&mut self, ctx: &mut Context, key: KeyEvent) -> Transition {
+        match self.input.handle_key(key) {
+            Entry::Editing => Transition::None,
+            Entry::Done(text) => (self.on_enter)(ctx, text),
+            Entry::Cancelled => Transition::Pop,
+        }
+    }
+
+    fn draw(&self, _ctx: &Context) {
+        self.input.draw();
+    }
+}
+
+/// Shows a message, such as an error, until a key is pressed.
+pub struct MessageScene {
+    message: String,
+}
+
+impl MessageScene {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl Scene for MessageScene {
+    fn handle_key(&mut self, _ctx: &mut Context, _key: KeyEvent) -> Transition {
+        Transition::Pop
+    }
+
+    fn draw(&self, _ctx: &Context) {
+        draw_lines(&[self.message.clone(), "Press any key".to_string()]);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TitleOption {
+    Continue,
+    Play,
+    Settings,
+    Leaderboard,
+    Help,
+    GitHub,
+    Quit,
+}
+
+impl TitleOption {
+    fn label(self) -> &'static str {
+        match self {
+            TitleOption::Continue => "Continue",
+            TitleOption::Play => "Play",
+            TitleOption::Settings => "Settings",
+            TitleOption::Leaderboard => "Leaderboard",
+            TitleOption::Help => "Help",
+            TitleOption::GitHub => "GitHub",
+            TitleOption::Quit => "Quit",
+        }
+    }
+}
+
+/// The first screen, and the one every game returns to.
+pub struct TitleScene {
+    selected: usize,
+    notice: Option<String>,
+    /// Whether a save exists, checked when the screen is shown rather than
+    /// on every draw.
+    saved: bool,
+}
+
+impl TitleScene {
+    /// Offers to continue if there is a save in the data directory `dir`.
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            selected: 0,
+            notice: None,
+            saved: save::exists(dir),
+        }
+    }
+
+    // "Continue" is only offered when there is a save to resume.
+    fn options(&self) -> Vec<TitleOption> {
+        let mut options = vec![
+            TitleOption::Play,
+            TitleOption::Settings,
+            TitleOption::Leaderboard,
+            TitleOption::Help,
+            TitleOption::GitHub,
+            TitleOption::Quit,
+        ];
+        if self.saved {
+            options.insert(0, TitleOption::Continue);
+        }
+        options
+    }
+
+    // Resumes the saved game, which is consumed so a save can only be
+    // resumed once.
+    fn resume_saved(&mut self, ctx: &mut Context) -> Transition {
+        let game = match save::load(&ctx.config.home) {
+            Ok(game) => game,
+            Err(err) => {
+                self.notice = Some(format!("Could not continue: {}", err));
+                return Transition::None;
+            }
+        };
+        if let Err(err) = save::delete(&ctx.config.home) {
+            self.notice = Some(format!("Could not remove the save: {}", err));
+        }
+        match play::resume(ctx, game) {
+            Ok(scene) => Transition::Push(scene),
+            Err(err) => {
+                self.notice = Some(format!("Could not continue: {}", err));
+                Transition::None
+            }
+        }
+    }
+}
+
+impl Scene for TitleScene {
+    fn handle_key(&mut self, ctx: &mut Context, key: KeyEvent) -> Transition {
+        self.notice = None;
+        let options = self.options();
+        self.selected = step(
+            self.selected.min(options.len() - 1),
+            options.len(),
+            key.code,
+        );
+        if key.code != KeyCode::Enter {
+            return Transition::None;
+        }
+        match options[self.selected] {
+            TitleOption::Continue => self.resume_saved(ctx),
+            TitleOption::Play => Transition::Push(Box::new(ModeSelectScene::default())),
+            TitleOption::Settings => Transition::Push(Box::new(SettingsScene::new(&ctx.config))),
+            TitleOption::Leaderboard => {
+                Transition::Push(Box::new(LeaderboardScene::new(&ctx.config.home)))
+            }
+            TitleOption::Help => Transition::Push(Box::new(HelpScene)),
+            TitleOption::GitHub => {
+                if let Err(err) = open::that("https://github.com/yhanyi/TetRust") {
+                    self.notice = Some(format!("Could not open the browser: {}", err));
+                }
+                Transition::None
+            }
+            TitleOption::Quit => Transition::Quit,
+        }
+    }
+
+    // Games that end or are saved come back here.
+    fn resume(&mut self, ctx: &mut Context) {
+        self.saved = save::exists(&ctx.config.home);
+    }
+
+    fn draw(&self, _ctx: &Context) {
+        print!("\x1B[2J");
+        let area = Area::terminal();
+        let logo = ["---------------", "   Tet-Rust!   ", "---------------"];
+        let options = self.options();
+        let labels: Vec<String> = options.iter().map(|o| o.label().to_string()).collect();
+        let selected = self.selected.min(options.len() - 1);
+
+        let mut y = area.height / 3;
+        for line in logo {
+            area.centre(y, line);
+            y += 1;
+        }
+        y += 1;
+        for line in menu_lines(&labels, selected) {
+            area.centre(y, &line);
+            y += 1;
+        }
+        area.centre(y + 1, "Created by Han Yi");
+        if let Some(notice) = &self.notice {
+            area.centre(y + 3, notice);
+        }
+    }
+}
+
+/// Picks a mode to play.
+#[derive(Default)]
+pub struct ModeSelectScene {
+    selected: usize,
+}
+
+impl Scene for ModeSelectScene {
+    fn handle_key(&mut self, ctx: &mut Context, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Esc => Transition::Pop,
+            KeyCode::Enter => Transition::Push(launch(ctx, MODES[self.selected])),
+            code => {
+                self.selected = step(self.selected, MODES.len(), code);
+                Transition::None
+            }
+        }
+    }
+
+    fn draw(&self, _ctx: &Context) {
+        let labels: Vec<String> = MODES.iter().map(|mode| mode.label().to_string()).collect();
+        let mut lines = vec!["Select Mode".to_string(), String::new()];
+        lines.extend(menu_lines(&labels, self.selected));
+        lines.push(String::new());
+        lines.push("Enter: play  Esc: back".to_string());
+        draw_lines(&lines);
+    }
+}
+
+/// Board sizes the settings screen cycles through.
+const BOARD_SIZES: [&str; 4] = ["10x20", "10x24", "8x16", "20x40"];
+
+const GLYPH_SETS: [&str; 3] = ["auto", "wide", "compact"];
+
+/// Longest delays the settings screen offers, in frames.
+const MAX_CLEAR_DELAY: u32 = 60;
+const MAX_ARE: u32 = 30;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Setting {
+    Rules,
+    Board,
+    ClearDelay,
+    Are,
+    Glyphs,
+    Save,
+}
+
+const SETTINGS: [Setting; 6] = [
+    Setting::Rules,
+    Setting::Board,
+    Setting::ClearDelay,
+    Setting::Are,
+    Setting::Glyphs,
+    Setting::Save,
+];
+
+/// Changes the `Config` for this session, and saves it for the next on
+/// request. Changes apply to games started afterwards.
+pub struct SettingsScene {
+    selected: usize,
+    rules: String,
+    board: String,
+    timing: Timing,
+    glyphs: String,
+    notice: Option<String>,
+}
+
+impl SettingsScene {
+    /// Starts from the settings in `config`.
+    pub fn new(config: &Config) -> Self {
+        let (width, height) = config.board_size;
+        Self {
+            selected: 0,
+            rules: config.value("rules").unwrap_or("guideline").to_string(),
+            board: format!("{}x{}", width, height),
+            timing: config.timing,
+            glyphs: config.value("glyphs").unwrap_or("auto").to_string(),
+            notice: None,
+        }
+    }
+
+    fn values(&self) -> [(&'static str, String); 5] {
+        [
+            ("rules", self.rules.clone()),
+            ("board", self.board.clone()),
+            ("clear_delay", self.timing.clear_delay.to_string()),
+            ("are", self.timing.are.to_string()),
+            ("glyphs", self.glyphs.clone()),
+        ]
+    }
+
+    // Steps the selected setting by `delta` and puts it in effect in
+    // `config`.
+    fn change(&mut self, config: &mut Config, delta: i32) {
+        match SETTINGS[self.selected] {
+            Setting::Rules => {
+                let presets = rules::PRESETS.map(|(name, _)| name);
+                self.rules = cycle(&presets, &self.rules, delta);
+            }
+            Setting::Board => self.board = cycle(&BOARD_SIZES, &self.board, delta),
+            Setting::ClearDelay => {
+                self.timing.clear_delay = adjust(self.timing.clear_delay, delta, MAX_CLEAR_DELAY)
+            }
+            Setting::Are => self.timing.are = adjust(self.timing.are, delta, MAX_ARE),
+            Setting::Glyphs => self.glyphs = cycle(&GLYPH_SETS, &self.glyphs, delta),
+            Setting::Save => return,
+        }
+        for (name, value) in self.values() {
+            if let Err(err) = config.set(name, &value) {
+                self.notice = Some(err);
+            }
+        }
+    }
+}
+
+// The choice `delta` places after `current`, wrapping around. A value that
+// is not one of the choices, such as a custom board size, moves to the
+// first.
+fn cycle(choices: &[&str], current: &str, delta: i32) -> String {
+    let next = match choices.iter().position(|&choice| choice == current) {
+        Some(i) => (i as i32 + delta).rem_euclid(choices.len() as i32) as usize,
+        None => 0,
+    };
+    choices[next].to_string()
+}
+
+fn adjust(value: u32, delta: i32, max: u32) -> u32 {
+    value.saturating_add_signed(delta).min(max)
+}
+
+impl Scene for SettingsScene {
+    fn handle_key(&mut self, ctx: &mut Context, key: KeyEvent) -> Transition {
+        self.notice = None;
+        match key.code {
+            KeyCode::Esc => return Transition::Pop,
+            KeyCode::Left => self.change(&mut ctx.config, -1),
+            KeyCode::Right => self.change(&mut ctx.config, 1),
+            KeyCode::Enter if SETTINGS[self.selected] == Setting::Save => {
+                let path = ctx.config.path();
+                self.notice = Some(match cli::save_config(&path, &self.values()) {
+                    Ok(()) => format!("Saved to {}", path.display()),
+                    Err(err) => format!("Could not save: {}", err),
+                });
+            }
+            code => self.selected = step(self.selected, SETTINGS.len(), code),
+        }
+        Transition::None
+    }
+
+    fn draw(&self, _ctx: &Context) {
+        let items: Vec<String> = SETTINGS
+            .iter()
+            .map(|setting| match setting {
+                Setting::Rules => format!("Rules: < {} >", self.rules),
+                Setting::Board => format!("Board size: < {} >", self.board),
+                Setting::ClearDelay => {
+                    format!("Line clear delay: < {} frames >", self.timing.clear_delay)
+                }
+                Setting::Are => format!("Entry delay: < {} frames >", self.timing.are),
+                Setting::Glyphs => format!("Glyphs: < {} >", self.glyphs),
+                Setting::Save => "Save for next time".to_string(),
+            })
+            .collect();
+        let mut lines = vec!["Settings".to_string(), String::new()];
+        lines.extend(menu_lines(&items, self.selected));
+        lines.push(String::new());
+        lines.push("←/→: change  Enter: save  Esc: back".to_string());
+        lines.push("Changes apply to new games".to_string());
+        lines.push(self.notice.clone().unwrap_or_default());
+        draw_lines(&lines);
+    }
+}
+
+/// The controls, reachable without starting a game.
+pub struct HelpScene;
+
+impl Scene for HelpScene {
+    fn handle_key(&mut self, _ctx: &mut Context, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+
+    fn draw(&self, _ctx: &Context) {
+        let mut lines: Vec<String> = play::CONTROLS.iter().map(|line| line.to_string()).collect();
+        lines.push(String::new());
+        lines.push("Press Esc to go back".to_string());
+        draw_lines(&lines);
+    }
+}
+
+/// Best scores, one mode at a time.
+pub struct LeaderboardScene {
+    leaderboard: Leaderboard,
+    mode: usize,
+}
+
+impl LeaderboardScene {
+    /// Shows the leaderboard in the data directory `dir`.
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            leaderboard: leaderboard::load(dir),
+            mode: 0,
+        }
+    }
+}
+
+impl Scene for LeaderboardScene {
+    fn handle_key(&mut self, _ctx: &mut Context, key: KeyEvent) -> Transition {
+        let modes = leaderboard::MODES.len();
+        match key.code {
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => return Transition::Pop,
+            KeyCode::Left => self.mode = (self.mode + modes - 1) % modes,
+            KeyCode::Right => self.mode = (self.mode + 1) % modes,
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn draw(&self, _ctx: &Context) {
+        let label = leaderboard::MODES[self.mode].label();
+        let mut lines = vec![format!("Leaderboard: < {} >", label), String::new()];
+        match self
+            .leaderboard
+            .get(label)
+            .filter(|entries| !entries.is_empty())
+        {
+            Some(entries) => {
+                for (i, entry) in entries.iter().enumerate() {
+                    lines.push(format!(
+                        "{:>2}. {:>8}  {:>4} lines",
+                        i + 1,
+                        entry.score,
+                        entry.lines
+                    ));
+                }
+            }
+            None => lines.push("No scores yet".to_string()),
+        }
+        lines.push(String::new());
+        lines.push("←/→: mode  Esc: back".to_string());
+        draw_lines(&lines);
+    }
+}
@@ -1,3 +1,34 @@
+pub mod ai;
+pub mod attack;
+pub mod bench;
 pub mod board;
+pub mod cli;
+pub mod clipboard;
+pub mod config;
+pub mod crash;
+pub mod editor;
+pub mod env;
+pub mod events;
+pub mod finesse;
+pub mod fumen;
 pub mod game;
+pub mod input;
+pub mod layout;
+pub mod leaderboard;
+pub mod menu;
+pub mod net;
+pub mod online;
+pub mod play;
+pub mod puzzle;
+pub mod randomizer;
+pub mod rules;
+pub mod save;
+pub mod scene;
+pub mod search;
+pub mod signals;
+pub mod spectate;
+pub mod tbp;
+pub mod terminal;
 pub mod tetromino;
+pub mod timing;
+pub mod versus;
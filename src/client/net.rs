@@ -0,0 +1,812 @@
+//! Versus play over TCP. One side hosts on a port and the other joins by
+//! address; both then run their own `Game` from a shared seed and exchange
+//! attacks and board snapshots as newline-delimited JSON. The same messages,
+//! plus a few lobby ones, are spoken to a `tetrust-server` lobby.
+
+use crate::client::board::{Row, DEFAULT_HEIGHT, DEFAULT_WIDTH};
+use crate::client::game::{Game, GameMode, GameState};
+use crate::client::input::Input;
+use crate::client::layout::{Area, Glyphs};
+use crate::client::rules::RuleSet;
+use crate::client::search::piece_cells;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Bumped whenever `NetMessage` changes incompatibly.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Identifies a client connected to a lobby server.
+pub type PlayerId = u32;
+
+pub type RoomId = u32;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NetMessage {
+    Hello {
+        version: u32,
+    },
+    /// Sent by the host once versions match; both sides play from `seed`.
+    Start {
+        seed: u64,
+    },
+    Reject {
+        reason: String,
+    },
+    Attack {
+        lines: u32,
+    },
+    Snapshot(BoardSnapshot),
+    TopOut,
+    Bye,
+    /// Lobby server's reply to `Hello`, carrying the id it assigned.
+    Welcome {
+        player: PlayerId,
+    },
+    /// Asks the server to resend the room list and, if in a room, its state.
+    ListRooms,
+    RoomList {
+        rooms: Vec<RoomInfo>,
+    },
+    CreateRoom {
+        name: String,
+    },
+    JoinRoom {
+        room: RoomId,
+    },
+    LeaveRoom,
+    RoomState {
+        room: RoomInfo,
+        members: Vec<RoomMember>,
+    },
+    Ready {
+        ready: bool,
+    },
+    /// Another player's board, relayed by the server.
+    PlayerSnapshot {
+        player: PlayerId,
+        snapshot: BoardSnapshot,
+    },
+    /// Finishing order of a lobby match, winner first.
+    Results {
+        standings: Vec<PlayerId>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoomInfo {
+    pub id: RoomId,
+    pub name: String,
+    pub players: usize,
+    pub in_game: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoomMember {
+    pub player: PlayerId,
+    pub ready: bool,
+}
+
+/// Writes one message as a line of JSON.
+pub fn write_message(writer: &mut impl Write, message: &impl Serialize) -> io::Result<()> {
+    let line = serde_json::to_string(message)?;
+    writeln!(writer, "{}", line)?;
+    writer.flush()
+}
+
+/// Longest line `read_messages` accepts, newline excluded. Far more than any
+/// message needs, but it keeps a peer from making the reader buffer without
+/// end.
+pub const MAX_LINE: usize = 1 << 20;
+
+/// Calls `handle` with each message read from `reader` until the stream
+/// ends, a line runs past `MAX_LINE` or `handle` returns false. Unknown
+/// messages are skipped so newer peers can add variants.
+pub fn read_messages<T: DeserializeOwned>(reader: impl Read, mut handle: impl FnMut(T) -> bool) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let limit = MAX_LINE as u64 + 1;
+        match (&mut reader).take(limit).read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        } else if line.len() > MAX_LINE {
+            return;
+        }
+        if let Ok(message) = serde_json::from_slice(&line) {
+            if !handle(message) {
+                return;
+            }
+        }
+    }
+}
+
+/// Enough of a player's state to draw their board on the other side: the
+/// visible rows, with the active piece in the same coordinates.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BoardSnapshot {
+    pub width: usize,
+    pub rows: Vec<Row>,
+    pub piece: Vec<(i32, i32)>,
+    pub score: u32,
+    pub lines: u32,
+    pub pending_garbage: u32,
+}
+
+// An empty standard board, shown until the first real snapshot arrives.
+impl Default for BoardSnapshot {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_WIDTH,
+            rows: vec![0; DEFAULT_HEIGHT],
+            piece: Vec::new(),
+            score: 0,
+            lines: 0,
+            pending_garbage: 0,
+        }
+    }
+}
+
+impl BoardSnapshot {
+    pub fn of(game: &Game) -> Self {
+        let (x, y) = game.piece_position();
+        let board = game.board();
+        let buffer = board.buffer_height();
+        Self {
+            width: board.width(),
+            rows: (buffer..board.height()).map(|y| board.row(y)).collect(),
+            piece: piece_cells(game.current_piece(), x, y - buffer as i32),
+            score: game.score(),
+            lines: game.lines(),
+            pending_garbage: game.pending_garbage(),
+        }
+    }
+
+    /// Columns taken by the board drawn with `draw_compact_board`.
+    pub fn compact_width(&self) -> i32 {
+        self.width as i32 + 2
+    }
+
+    fn filled(&self, x: usize, y: usize) -> bool {
+        self.rows.get(y).is_some_and(|row| row & 1 << x != 0)
+            || self.piece.contains(&(x as i32, y as i32))
+    }
+}
+
+pub enum NetEvent {
+    Message(NetMessage),
+    Disconnected,
+}
+
+/// A connected peer. Incoming lines are parsed on a background thread.
+pub struct Connection {
+    stream: TcpStream,
+    events: Receiver<NetEvent>,
+    connected: bool,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let reader = stream.try_clone()?;
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || {
+            read_messages(reader, |message| {
+                sender.send(NetEvent::Message(message)).is_ok()
+            });
+            let _ = sender.send(NetEvent::Disconnected);
+        });
+        Ok(Self {
+            stream,
+            events,
+            connected: true,
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn send(&mut self, message: &NetMessage) -> io::Result<()> {
+        let result = write_message(&mut self.stream, message);
+        if result.is_err() {
+            self.connected = false;
+        }
+        result
+    }
+
+    pub fn try_recv(&mut self) -> Option<NetMessage> {
+        match self.events.try_recv() {
+            Ok(NetEvent::Message(message)) => Some(message),
+            Ok(NetEvent::Disconnected) | Err(TryRecvError::Disconnected) => {
+                self.connected = false;
+                None
+            }
+            Err(TryRecvError::Empty) => None,
+        }
+    }
+
+    pub fn recv_timeout(&mut self, timeout: Duration) -> io::Result<NetMessage> {
+        match self.events.recv_timeout(timeout) {
+            Ok(NetEvent::Message(message)) => Ok(message),
+            Ok(NetEvent::Disconnected) => {
+                self.connected = false;
+                Err(io::Error::new(
+                    io::ErrorKind::ConnectionAborted,
+                    "peer disconnected",
+                ))
+            }
+            Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "peer timed out")),
+        }
+    }
+}
+
+/// Waits for an opponent without blocking the UI. Peers are accepted as
+/// they connect and finish the handshake over later polls; one that sends
+/// the wrong thing, leaves or stays silent is dropped and the host keeps
+/// listening.
+pub struct Host {
+    listener: TcpListener,
+    /// Connected peers that have not said `Hello` yet, with when they gave
+    /// up their chance to.
+    pending: Vec<(Connection, Instant)>,
+}
+
+impl Host {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            pending: Vec::new(),
+        })
+    }
+
+    pub fn port(&self) -> io::Result<u16> {
+        Ok(self.listener.local_addr()?.port())
+    }
+
+    /// Accepts pending connections and moves their handshakes along, never
+    /// waiting on a peer. Returns the first peer to complete the handshake
+    /// with the seed both players will use.
+    pub fn poll_accept(&mut self) -> io::Result<Option<(Connection, u64)>> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    // A peer we cannot set up is one we cannot play.
+                    let connection = stream
+                        .set_nonblocking(false)
+                        .and_then(|()| Connection::new(stream));
+                    if let Ok(connection) = connection {
+                        self.pending
+                            .push((connection, Instant::now() + HANDSHAKE_TIMEOUT));
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                // The peer hung up before we got to it.
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::ConnectionAborted
+                            | io::ErrorKind::ConnectionReset
+                            | io::ErrorKind::Interrupted
+                    ) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut index = 0;
+        while index < self.pending.len() {
+            let (connection, deadline) = &mut self.pending[index];
+            match connection.try_recv() {
+                Some(NetMessage::Hello { version }) if version == PROTOCOL_VERSION => {
+                    let (mut connection, _) = self.pending.swap_remove(index);
+                    let seed = rand::random();
+                    let greeted = connection
+                        .send(&NetMessage::Hello {
+                            version: PROTOCOL_VERSION,
+                        })
+                        .and_then(|()| connection.send(&NetMessage::Start { seed }));
+                    if greeted.is_ok() {
+                        return Ok(Some((connection, seed)));
+                    }
+                }
+                Some(NetMessage::Hello { version }) => {
+                    let reason = format!(
+                        "protocol version {} is not supported (host uses {})",
+                        version, PROTOCOL_VERSION
+                    );
+                    let _ = connection.send(&NetMessage::Reject { reason });
+                    self.pending.swap_remove(index);
+                }
+                Some(_) => {
+                    self.pending.swap_remove(index);
+                }
+                None if !connection.is_connected() || Instant::now() >= *deadline => {
+                    self.pending.swap_remove(index);
+                }
+                None => index += 1,
+            }
+        }
+        Ok(None)
+    }
+}
+
+// Connects and sends our `Hello`.
+fn connect(address: impl ToSocketAddrs) -> io::Result<Connection> {
+    let address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address found"))?;
+    let stream = TcpStream::connect_timeout(&address, HANDSHAKE_TIMEOUT)?;
+    let mut connection = Connection::new(stream)?;
+    connection.send(&NetMessage::Hello {
+        version: PROTOCOL_VERSION,
+    })?;
+    Ok(connection)
+}
+
+/// Connects to a host and performs the handshake, returning the connection
+/// and the seed chosen by the host.
+pub fn join(address: impl ToSocketAddrs) -> io::Result<(Connection, u64)> {
+    let mut connection = connect(address)?;
+    loop {
+        match connection.recv_timeout(HANDSHAKE_TIMEOUT)? {
+            NetMessage::Hello { .. } => {}
+            NetMessage::Start { seed } => return Ok((connection, seed)),
+            NetMessage::Reject { reason } => {
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, reason))
+            }
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "expected start")),
+        }
+    }
+}
+
+/// Connects to a lobby server, returning the connection and the id the
+/// server assigned to us.
+pub fn join_lobby(address: impl ToSocketAddrs) -> io::Result<(Connection, PlayerId)> {
+    let mut connection = connect(address)?;
+    loop {
+        match connection.recv_timeout(HANDSHAKE_TIMEOUT)? {
+            NetMessage::Hello { .. } => {}
+            NetMessage::Welcome { player } => return Ok((connection, player)),
+            NetMessage::Reject { reason } => {
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, reason))
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "not a lobby server",
+                ))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NetResult {
+    Won,
+    Lost,
+    OpponentLeft,
+    /// Final position in a lobby match of `players`, 1 being the winner.
+    Placed {
+        place: usize,
+        players: usize,
+    },
+}
+
+/// One side of a networked match: the local game plus what is known about
+/// the opponents. Direct matches have a single opponent; lobby matches have
+/// one per other player in the room.
+pub struct NetMatch {
+    game: Game,
+    connection: Connection,
+    /// Our id when playing through a lobby server.
+    player: Option<PlayerId>,
+    opponents: Vec<(PlayerId, BoardSnapshot)>,
+    last_sent: Option<BoardSnapshot>,
+    result: Option<NetResult>,
+}
+
+impl NetMatch {
+    /// Both sides play by the guideline on a standard board with the
+    /// default delays, whatever their own settings, so that the same seed
+    /// deals them the same game.
+    pub fn new(connection: Connection, seed: u64) -> Self {
+        let mut game = Game::with_rules(seed, RuleSet::GUIDELINE);
+        game.start(GameMode::Versus);
+        Self {
+            game,
+            connection,
+            player: None,
+            opponents: vec![(0, BoardSnapshot::default())],
+            last_sent: None,
+            result: None,
+        }
+    }
+
+    /// A match started by a lobby server; `opponents` are the other players
+    /// in the room.
+    pub fn in_lobby(
+        connection: Connection,
+        seed: u64,
+        player: PlayerId,
+        opponents: &[PlayerId],
+    ) -> Self {
+        let mut net_match = Self::new(connection, seed);
+        net_match.player = Some(player);
+        net_match.opponents = opponents
+            .iter()
+            .map(|&id| (id, BoardSnapshot::default()))
+            .collect();
+        net_match
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn result(&self) -> Option<NetResult> {
+        self.result
+    }
+
+    /// Hands the connection back, e.g. to return to the lobby.
+    pub fn into_connection(self) -> Connection {
+        self.connection
+    }
+
+    pub fn apply_input(&mut self, input: Input) {
+        if self.result.is_none() {
+            self.game.apply_input(input);
+        }
+    }
+
+    pub fn tick(&mut self) {
+        if self.result.is_none() {
+            self.game.tick();
+        }
+    }
+
+    /// Time between gravity ticks at this player's level.
+    pub fn gravity_interval(&self) -> Duration {
+        self.game.gravity_interval()
+    }
+
+    /// Advances the line clear, entry and lock delays by one engine frame.
+    pub fn frame(&mut self) {
+        if self.result.is_none() {
+            self.game.frame();
+        }
+    }
+
+    fn set_opponent(&mut self, player: PlayerId, snapshot: BoardSnapshot) {
+        match self.opponents.iter_mut().find(|(id, _)| *id == player) {
+            Some((_, board)) => *board = snapshot,
+            None => self.opponents.push((player, snapshot)),
+        }
+    }
+
+    /// Exchanges attacks and snapshots with the peer. Call once per frame.
+    pub fn update(&mut self) {
+        while let Some(message) = self.connection.try_recv() {
+            match message {
+                NetMessage::Attack { lines } => self.game.receive_garbage(lines),
+                NetMessage::Snapshot(snapshot) => self.set_opponent(0, snapshot),
+                NetMessage::PlayerSnapshot { player, snapshot } => {
+                    self.set_opponent(player, snapshot)
+                }
+                // In a lobby the server decides the outcome with `Results`.
+                NetMessage::TopOut if self.player.is_none() => {
+                    self.result.get_or_insert(NetResult::Won);
+                }
+                NetMessage::Bye => {
+                    self.result.get_or_insert(NetResult::OpponentLeft);
+                }
+                NetMessage::Results { standings } => {
+                    if let Some(place) = standings.iter().position(|&id| Some(id) == self.player) {
+                        self.result = Some(NetResult::Placed {
+                            place: place + 1,
+                            players: standings.len(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !self.connection.is_connected() {
+            // A lobby player who topped out is still waiting on `Results`.
+            let waiting = self.result.is_none()
+                || (self.player.is_some() && self.result == Some(NetResult::Lost));
+            if waiting {
+                self.result = Some(NetResult::OpponentLeft);
+            }
+            return;
+        }
+        if self.result.is_some() {
+            return;
+        }
+
+        let lines = self.game.take_outgoing_attack();
+        if lines > 0 {
+            let _ = self.connection.send(&NetMessage::Attack { lines });
+        }
+        let snapshot = BoardSnapshot::of(&self.game);
+        if self.last_sent.as_ref() != Some(&snapshot) {
+            let _ = self
+                .connection
+                .send(&NetMessage::Snapshot(snapshot.clone()));
+            self.last_sent = Some(snapshot);
+        }
+        if self.game.get_state() == GameState::GameOver {
+            let _ = self.connection.send(&NetMessage::TopOut);
+            self.result = Some(NetResult::Lost);
+        }
+    }
+
+    /// Tells the peer we are leaving. In a lobby this forfeits the match but
+    /// stays connected to the server.
+    pub fn leave(&mut self) {
+        if self.player.is_none() {
+            let _ = self.connection.send(&NetMessage::Bye);
+        } else if self.result.is_none() {
+            let _ = self.connection.send(&NetMessage::TopOut);
+            self.result = Some(NetResult::Lost);
+        }
+    }
+
+    pub fn draw(&self, glyphs: Option<Glyphs>) {
+        let area = Area::terminal();
+        print!("\x1B[2J");
+
+        // Our board on the left half, opponents side by side on the right.
+        let [own, others] = <[Area; 2]>::try_from(area.columns(2)).unwrap();
+        let layout = match self.game.layout(own, glyphs) {
+            Ok(layout) => layout,
+            Err(too_small) => return too_small.draw(area),
+        };
+        let status_y = self.game.draw_playfield(&layout);
+
+        let total: i32 = self
+            .opponents
+            .iter()
+            .map(|(_, snapshot)| snapshot.compact_width() + 2)
+            .sum();
+        let mut opponent_x = others.x + (others.width - total).max(0) / 2;
+        for (id, snapshot) in &self.opponents {
+            let title = match self.player {
+                Some(_) => format!("Player {}", id),
+                None => "Opponent".to_string(),
+            };
+            draw_compact_board(others, snapshot, &title, opponent_x, layout.board_y);
+            opponent_x += snapshot.compact_width() + 2;
+        }
+
+        let message = match self.result {
+            Some(NetResult::Won) => "You win! Press Esc to leave".to_string(),
+            Some(NetResult::Lost) if self.player.is_some() => {
+                "Topped out! Waiting for the others... Esc: leave".to_string()
+            }
+            Some(NetResult::Lost) => "You lose! Press Esc to leave".to_string(),
+            Some(NetResult::OpponentLeft) if self.player.is_some() => {
+                "Lost connection to the server. Press Esc to leave".to_string()
+            }
+            Some(NetResult::OpponentLeft) => {
+                "Opponent disconnected. Press Esc to leave".to_string()
+            }
+            Some(NetResult::Placed { place, players }) => {
+                format!("Finished {} of {}. Press Esc to leave", place, players)
+            }
+            None => "Esc: leave match".to_string(),
+        };
+        area.centre(status_y.min(area.bottom() - 1), &message);
+    }
+}
+
+/// Draws a board at half size: one character per column and two rows per
+/// line, using half-block glyphs. Anything outside `area` is cut off.
+pub fn draw_compact_board(area: Area, snapshot: &BoardSnapshot, title: &str, x: i32, y: i32) {
+    let width = snapshot.width;
+    let height = snapshot.rows.len();
+    area.put(x, y, &format!("┌{}┐", "─".repeat(width)));
+    for line in 0..height.div_ceil(2) {
+        let cells: String = (0..width)
+            .map(|column| {
+                let top = snapshot.filled(column, line * 2);
+                let bottom = snapshot.filled(column, line * 2 + 1);
+                match (top, bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                }
+            })
+            .collect();
+        area.put(x, y + 1 + line as i32, &format!("│{}│", cells));
+    }
+    let bottom_y = y + 1 + height.div_ceil(2) as i32;
+    area.put(x, bottom_y, &format!("└{}┘", "─".repeat(width)));
+
+    let stats = [
+        title.to_string(),
+        format!("Score: {}", snapshot.score),
+        format!("Lines: {}", snapshot.lines),
+        format!("Incoming: {}", snapshot.pending_garbage),
+    ];
+    for (i, line) in stats.iter().enumerate() {
+        area.put(x, bottom_y + 1 + i as i32, line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::board::Cell;
+    use crate::client::tetromino::TetrominoType;
+
+    // Polls like the host screen does until a peer completes the handshake.
+    fn accept(host: &mut Host) -> (Connection, u64) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if let Some(accepted) = host.poll_accept().unwrap() {
+                return accepted;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("no peer completed the handshake");
+    }
+
+    // Both sides of a direct match over the loopback interface, host first.
+    fn loopback_match() -> (NetMatch, NetMatch) {
+        let mut host = Host::bind(0).unwrap();
+        let port = host.port().unwrap();
+        let joiner = thread::spawn(move || join(("127.0.0.1", port)));
+        let (connection, seed) = accept(&mut host);
+        let host_match = NetMatch::new(connection, seed);
+        let (connection, seed) = joiner.join().unwrap().unwrap();
+        (host_match, NetMatch::new(connection, seed))
+    }
+
+    // Lets both sides exchange messages until `done` holds.
+    fn exchange(a: &mut NetMatch, b: &mut NetMatch, done: impl Fn(&NetMatch, &NetMatch) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(a, b) {
+            assert!(Instant::now() < deadline, "the match stalled");
+            a.update();
+            b.update();
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn a_match_plays_out_over_loopback() {
+        let (mut host, mut guest) = loopback_match();
+        assert!(host.game().current_piece() == guest.game().current_piece());
+        assert_eq!(host.game().board().width(), DEFAULT_WIDTH);
+        assert_eq!(guest.game().board().height(), host.game().board().height());
+        assert_eq!(guest.game().timing(), host.game().timing());
+
+        // The guest clears a tetris with an I piece down the left wall,
+        // leaving a row behind so it is not a perfect clear.
+        let mut board = guest.game().board().cleared();
+        for y in board.height() - 5..board.height() {
+            for x in 1..board.width() {
+                board.set(x, y, Cell::Filled);
+            }
+        }
+        board.set(1, board.height() - 5, Cell::Empty);
+        guest
+            .game
+            .load_position(board, None, &[TetrominoType::I, TetrominoType::O]);
+        for input in [Input::RotateCw, Input::DasLeft, Input::HardDrop] {
+            guest.apply_input(input);
+        }
+        assert_eq!(guest.game().lines(), 4);
+        exchange(&mut host, &mut guest, |host, _| {
+            host.game().pending_garbage() == 4 && host.opponents[0].1.lines == 4
+        });
+
+        // Then stacks up until it tops out.
+        for _ in 0..1000 {
+            if guest.game().get_state() == GameState::GameOver {
+                break;
+            }
+            guest.apply_input(Input::HardDrop);
+            guest.frame();
+        }
+        exchange(&mut host, &mut guest, |host, guest| {
+            host.result().is_some() && guest.result().is_some()
+        });
+        assert_eq!(host.result(), Some(NetResult::Won));
+        assert_eq!(guest.result(), Some(NetResult::Lost));
+    }
+
+    #[test]
+    fn host_and_join_agree_on_a_seed() {
+        let mut host = Host::bind(0).unwrap();
+        let port = host.port().unwrap();
+        let joiner = thread::spawn(move || join(("127.0.0.1", port)));
+        let (_, host_seed) = accept(&mut host);
+        let (_, seed) = joiner.join().unwrap().unwrap();
+        assert_eq!(seed, host_seed);
+    }
+
+    #[test]
+    fn host_rejects_other_versions_and_keeps_listening() {
+        let mut host = Host::bind(0).unwrap();
+        let port = host.port().unwrap();
+
+        // A peer that never speaks must not hold up the others.
+        let _silent = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let mut old = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write_message(
+            &mut old,
+            &NetMessage::Hello {
+                version: PROTOCOL_VERSION - 1,
+            },
+        )
+        .unwrap();
+        let reply = thread::spawn(move || {
+            let mut reply = None;
+            read_messages(old, |message: NetMessage| {
+                reply = Some(message);
+                false
+            });
+            reply
+        });
+        while !reply.is_finished() {
+            let started = Instant::now();
+            assert!(host.poll_accept().unwrap().is_none());
+            assert!(started.elapsed() < Duration::from_secs(1));
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(matches!(
+            reply.join().unwrap(),
+            Some(NetMessage::Reject { .. })
+        ));
+
+        let joiner = thread::spawn(move || join(("127.0.0.1", port)));
+        let (_, host_seed) = accept(&mut host);
+        let (_, seed) = joiner.join().unwrap().unwrap();
+        assert_eq!(seed, host_seed);
+    }
+
+    #[test]
+    fn reading_stops_at_a_line_that_is_too_long() {
+        let mut stream = Vec::new();
+        write_message(&mut stream, &NetMessage::TopOut).unwrap();
+        stream.extend(b"junk that is skipped\n");
+        // Ends without a newline, which still counts as a line.
+        write_message(&mut stream, &NetMessage::Bye).unwrap();
+        stream.pop();
+        let mut read = Vec::new();
+        read_messages(stream.as_slice(), |message: NetMessage| {
+            read.push(message);
+            true
+        });
+        assert_eq!(read, [NetMessage::TopOut, NetMessage::Bye]);
+
+        let mut stream = Vec::new();
+        write_message(&mut stream, &NetMessage::TopOut).unwrap();
+        stream.extend(vec![b' '; MAX_LINE + 1]);
+        stream.push(b'\n');
+        write_message(&mut stream, &NetMessage::Bye).unwrap();
+        let mut read = Vec::new();
+        read_messages(stream.as_slice(), |message: NetMessage| {
+            read.push(message);
+            true
+        });
+        assert_eq!(read, [NetMessage::TopOut]);
+    }
+}
@@ -0,0 +1,353 @@
+//! Scenes for networked play: hosting or joining a direct match, browsing
+//! a lobby server's rooms, and the match itself. Connection problems are
+//! reported on screen rather than ending the program.
+
+use crate::client::game::Game;
+use crate::client::input;
+use crate::client::menu::{self, Entry, MessageScene, PromptScene, TextInput};
+use crate::client::net::{
+    self, Connection, Host, NetMatch, NetMessage, PlayerId, RoomInfo, RoomMember,
+};
+use crate::client::scene::{Context, Scene, Transition};
+use crate::client::timing::{FrameClock, TickClock};
+use crossterm::event::{KeyCode, KeyEvent};
+
+fn connection_failed(err: impl std::fmt::Display) -> Transition {
+    Transition::Replace(Box::new(MessageScene::new(format!(
+        "Connection failed: {}",
+        err
+    ))))
+}
+
+/// Asks for a port, then waits on it for an opponent.
+pub fn host() -> Box<dyn Scene> {
+    Box::new(PromptScene::new(
+        "Port to host on:",
+        net::DEFAULT_PORT.to_string(),
+        |_ctx, port| {
+            let Ok(port) = port.trim().parse() else {
+                return Transition::Replace(Box::new(MessageScene::new("Invalid port")));
+            };
+            match Host::bind(port).and_then(|host| Ok((host.port()?, host))) {
+                Ok((port, host)) => Transition::Replace(Box::new(HostScene { host, port })),
+                Err(err) => connection_failed(err),
+            }
+        },
+    ))
+}
+
+/// Asks for the address of a host, then plays the match it starts.
+pub fn join() -> Box<dyn Scene> {
+    let default = format!("127.0.0.1:{}", net::DEFAULT_PORT);
+    Box::new(PromptScene::new(
+        "Address to join:",
+        default,
+        |_ctx, address| {
+            menu::draw_lines(&[format!("Connecting to {}...", address.trim())]);
+            match net::join(address.trim()) {
+                Ok((connection, seed)) => {
+                    Transition::Replace(Box::new(MatchScene::new(NetMatch::new(connection, seed))))
+                }
+                Err(err) => connection_failed(err),
+            }
+        },
+    ))
+}
+
+/// Asks for the address of a lobby server, then browses its rooms.
+pub fn lobby() -> Box<dyn Scene> {
+    let default = format!("127.0.0.1:{}", net::DEFAULT_PORT);
+    Box::new(PromptScene::new(
+        "Lobby server address:",
+        default,
+        |_ctx, address| {
+            menu::draw_lines(&[format!("Connecting to {}...", address.trim())]);
+            match net::join_lobby(address.trim()) {
+                Ok((connection, player)) => {
+                    Transition::Replace(Box::new(LobbyScene::new(connection, player)))
+                }
+                Err(err) => connection_failed(err),
+            }
+        },
+    ))
+}
+
+/// Waits for an opponent to connect, until the host gives up.
+struct HostScene {
+    host: Host,
+    port: u16,
+}
+
+impl Scene for HostScene {
+    fn handle_key(&mut self, _ctx: &mut Context, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Esc => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+
+    fn update(&mut self, _ctx: &mut Context) -> Transition {
+        match self.host.poll_accept() {
+            Ok(Some((connection, seed))) => {
+                Transition::Replace(Box::new(MatchScene::new(NetMatch::new(connection, seed))))
+            }
+            Ok(None) => Transition::None,
+            Err(err) => connection_failed(err),
+        }
+    }
+
+    fn draw(&self, _ctx: &Context) {
+        menu::draw_lines(&[
+            format!("Waiting for an opponent on port {}...", self.port),
+            "Esc: cancel".to_string(),
+        ]);
+    }
+}
+
+/// Plays a networked match until the player presses Esc. There is no
+/// pausing a match against someone else.
+pub struct MatchScene {
+    net_match: NetMatch,
+    ticks: TickClock,
+    frames: FrameClock,
+}
+
+impl MatchScene {
+    pub fn new(net_match: NetMatch) -> Self {
+        Self {
+            net_match,
+            ticks: TickClock::new(),
+            frames: FrameClock::new(),
+        }
+    }
+
+    /// Hands the connection back once the match has been left.
+    pub fn into_connection(self) -> Connection {
+        self.net_match.into_connection()
+    }
+}
+
+impl Scene for MatchScene {
+    fn handle_key(&mut self, _ctx: &mut Context, key: KeyEvent) -> Transition {
+        if key.code == KeyCode::Esc {
+            self.net_match.leave();
+            return Transition::Pop;
+        }
+        if let Some(input) = input::key_to_input(key) {
+            self.net_match.apply_input(input);
+        }
+        Transition::None
+    }
+
+    fn update(&mut self, _ctx: &mut Context) -> Transition {
+        for _ in 0..self.ticks.due(self.net_match.gravity_interval()) {
+            self.net_match.tick();
+        }
+        for _ in 0..self.frames.due() {
+            self.net_match.frame();
+        }
+        self.net_match.update();
+        Transition::None
+    }
+
+    fn own_game(&self) -> Option<Game> {
+        Some(self.net_match.game().clone())
+    }
+
+    fn draw(&self, ctx: &Context) {
+        self.net_match.draw(ctx.config.glyphs);
+    }
+}
+
+/// Browses rooms on a lobby server and plays the matches it starts. A
+/// match runs inside this scene, as it borrows the lobby's connection.
+struct LobbyScene {
+    /// `None` while a match has it.
+    connection: Option<Connection>,
+    player: PlayerId,
+    rooms: Vec<RoomInfo>,
+    room: Option<(RoomInfo, Vec<RoomMember>)>,
+    selected: usize,
+    notice: String,
+    /// The name of a room being created.
+    naming: Option<TextInput>,
+    playing: Option<MatchScene>,
+}
+
+impl LobbyScene {
+    fn new(connection: Connection, player: PlayerId) -> Self {
+        Self {
+            connection: Some(connection),
+            player,
+            rooms: Vec::new(),
+            room: None,
+            selected: 0,
+            notice: String::new(),
+            naming: None,
+            playing: None,
+        }
+    }
+
+    // Takes the connection back from a match that has been left.
+    fn end_match(&mut self) {
+        if let Some(playing) = self.playing.take() {
+            let mut connection = playing.into_connection();
+            // Room updates sent during the match were not seen here.
+            let _ = connection.send(&NetMessage::ListRooms);
+            self.connection = Some(connection);
+        }
+    }
+
+    fn send(&mut self, message: &NetMessage) {
+        if let Some(connection) = self.connection.as_mut() {
+            if let Err(err) = connection.send(message) {
+                self.notice = format!("Could not reach the server: {}", err);
+            }
+        }
+    }
+
+    // Sends room commands for a key pressed while browsing.
+    fn browse(&mut self, key: KeyEvent) -> Transition {
+        match (&self.room, key.code) {
+            (Some(_), KeyCode::Esc) => {
+                self.send(&NetMessage::LeaveRoom);
+                self.room = None;
+            }
+            (Some((_, members)), KeyCode::Char('r')) => {
+                let ready = members.iter().any(|m| m.player == self.player && m.ready);
+                self.send(&NetMessage::Ready { ready: !ready });
+            }
+            (None, KeyCode::Esc) => {
+                self.send(&NetMessage::Bye);
+                return Transition::Pop;
+            }
+            (None, KeyCode::Up) => self.selected = self.selected.saturating_sub(1),
+            (None, KeyCode::Down) => self.selected += 1,
+            (None, KeyCode::Enter) => {
+                if let Some(info) = self.rooms.get(self.selected) {
+                    let room = info.id;
+                    self.send(&NetMessage::JoinRoom { room });
+                }
+            }
+            (None, KeyCode::Char('n')) => self.naming = Some(TextInput::new("Room name:", "")),
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        match &self.room {
+            Some((info, members)) => {
+                lines.push(format!("Room: {}", info.name));
+                lines.push(String::new());
+                for member in members {
+                    lines.push(format!(
+                        "Player {}{} - {}",
+                        member.player,
+                        if member.player == self.player {
+                            " (you)"
+                        } else {
+                            ""
+                        },
+                        if member.ready { "ready" } else { "not ready" }
+                    ));
+                }
+                lines.push(String::new());
+                lines.push("r: toggle ready  Esc: leave room".to_string());
+            }
+            None => {
+                lines.push(format!("Lobby - you are Player {}", self.player));
+                lines.push(String::new());
+                if self.rooms.is_empty() {
+                    lines.push("No rooms yet".to_string());
+                }
+                for (i, info) in self.rooms.iter().enumerate() {
+                    lines.push(format!(
+                        "{} {} ({} players{})",
+                        if i == self.selected { ">" } else { " " },
+                        info.name,
+                        info.players,
+                        if info.in_game { ", playing" } else { "" }
+                    ));
+                }
+                lines.push(String::new());
+                lines.push("Enter: join  n: new room  Esc: disconnect".to_string());
+            }
+        }
+        lines.push(self.notice.clone());
+        lines
+    }
+}
+
+impl Scene for LobbyScene {
+    fn handle_key(&mut self, ctx: &mut Context, key: KeyEvent) -> Transition {
+        if let Some(playing) = self.playing.as_mut() {
+            if let Transition::Pop = playing.handle_key(ctx, key) {
+                self.end_match();
+            }
+            return Transition::None;
+        }
+        if let Some(naming) = self.naming.as_mut() {
+            match naming.handle_key(key) {
+                Entry::Editing => {}
+                Entry::Done(name) => {
+                    self.naming = None;
+                    self.send(&NetMessage::CreateRoom { name });
+                }
+                Entry::Cancelled => self.naming = None,
+            }
+            return Transition::None;
+        }
+        self.notice.clear();
+        self.browse(key)
+    }
+
+    fn update(&mut self, ctx: &mut Context) -> Transition {
+        if let Some(playing) = self.playing.as_mut() {
+            return playing.update(ctx);
+        }
+        let Some(connection) = self.connection.as_mut() else {
+            return Transition::None;
+        };
+        while let Some(message) = connection.try_recv() {
+            match message {
+                NetMessage::RoomList { rooms } => self.rooms = rooms,
+                NetMessage::RoomState { room, members } => self.room = Some((room, members)),
+                NetMessage::Reject { reason } => self.notice = reason,
+                NetMessage::Start { seed } => {
+                    let opponents: Vec<PlayerId> = self
+                        .room
+                        .iter()
+                        .flat_map(|(_, members)| members.iter().map(|m| m.player))
+                        .filter(|&id| id != self.player)
+                        .collect();
+                    let connection = self.connection.take().expect("checked above");
+                    let net_match = NetMatch::in_lobby(connection, seed, self.player, &opponents);
+                    self.playing = Some(MatchScene::new(net_match));
+                    return Transition::None;
+                }
+                _ => {}
+            }
+        }
+        if !connection.is_connected() {
+            return Transition::Replace(Box::new(MessageScene::new(
+                "Lost connection to the lobby server",
+            )));
+        }
+        self.selected = self.selected.min(self.rooms.len().saturating_sub(1));
+        Transition::None
+    }
+
+    fn own_game(&self) -> Option<Game> {
+        self.playing.as_ref().and_then(MatchScene::own_game)
+    }
+
+    fn draw(&self, ctx: &Context) {
+        match (&self.playing, &self.naming) {
+            (Some(playing), _) => playing.draw(ctx),
+            (None, Some(naming)) => naming.draw(),
+            (None, None) => menu::draw_lines(&self.lines()),
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! Scenes for a game in progress: playing it, the pause screen over it and
+//! the results once it ends. The game itself lives in the scene `Context`,
+//! so all three see the same one.
+
+use crate::client::ai::{AiPlayer, Weights};
+use crate::client::clipboard;
+use crate::client::config::Config;
+use crate::client::fumen;
+use crate::client::game::{Game, GameMode, GameState};
+use crate::client::input::{self, Controller};
+use crate::client::layout::Area;
+use crate::client::leaderboard;
+use crate::client::menu::{HelpScene, SettingsScene};
+use crate::client::save;
+use crate::client::scene::{Context, Scene, Transition};
+use crate::client::spectate::Delta;
+use crate::client::tbp::TbpBot;
+use crate::client::timing::{self, FrameClock, PlayClock, TickClock};
+use crossterm::event::{KeyCode, KeyEvent};
+use std::io;
+
+/// Key help shown on the pause and help screens.
+pub const CONTROLS: [&str; 11] = [
+    "Controls:",
+    "←/→: Move piece",
+    "Shift+←/→: Move piece to wall",
+    "A: Rotate clockwise",
+    "D: Rotate anti-clockwise",
+    "↓: Soft drop",
+    "Space: Hard drop",
+    "C: Hold piece",
+    "Esc/P: Pause/Unpause",
+    "R: Restart game",
+    "Z/Y: Undo/redo placement (practice)",
+];
+
+/// Creates the controller for modes where the player only watches, with
+/// the bot from `config`.
+pub fn launch_controller(
+    mode: GameMode,
+    config: &Config,
+) -> io::Result<Option<Box<dyn Controller>>> {
+    Ok(match mode {
+        GameMode::WatchAi => Some(Box::new(AiPlayer::new(Weights::default()))),
+        GameMode::WatchBot => Some(Box::new(TbpBot::launch(&config.bot)?)),
+        GameMode::Marathon
+        | GameMode::Finesse
+        | GameMode::Puzzle
+        | GameMode::Editor
+        | GameMode::Versus
+        | GameMode::HostOnline
+        | GameMode::JoinOnline
+        | GameMode::Lobby
+        | GameMode::Spectate => None,
+    })
+}
+
+/// Starts a new game in `mode`, seeded with the context's seed if it still
+/// has one, and returns the scene that plays it.
+pub fn start(ctx: &mut Context, mode: GameMode) -> io::Result<Box<dyn Scene>> {
+    let controller = launch_controller(mode, &ctx.config)?;
+    let seed = ctx.seed.take().unwrap_or_else(rand::random);
+    let mut game = Game::with_config(seed, &ctx.config, mode);
+    game.start(mode);
+    Ok(play(ctx, game, controller))
+}
+
+/// Carries on with `game`, such as a resumed save, which comes back paused.
+pub fn resume(ctx: &mut Context, game: Game) -> io::Result<Box<dyn Scene>> {
+    let controller = launch_controller(game.mode(), &ctx.config)?;
+    Ok(play(ctx, game, controller))
+}
+
+fn play(ctx: &mut Context, game: Game, controller: Option<Box<dyn Controller>>) -> Box<dyn Scene> {
+    ctx.game = game;
+    ctx.controller = controller;
+    ctx.clock = PlayClock::new();
+    Box::new(GameScene::new())
+}
+
+// Starts the current game over, bot and clock included.
+fn restart(ctx: &mut Context) {
+    ctx.game.restart();
+    if let Some(controller) = ctx.controller.as_mut() {
+        controller.reset();
+    }
+    ctx.clock = PlayClock::new();
+}
+
+// The play time line shown under the board.
+fn time_line(ctx: &Context) -> String {
+    format!("Time {}", timing::format_time(ctx.clock.elapsed()))
+}
+
+/// Runs the game: the player's keys or the controller's inputs, gravity
+/// and the engine's delays.
+pub struct GameScene {
+    ticks: TickClock,
+    frames: FrameClock,
+}
+
+impl Default for GameScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameScene {
+    pub fn new() -> Self {
+        Self {
+            ticks: TickClock::new(),
+            frames: FrameClock::new(),
+        }
+    }
+}
+
+impl Scene for GameScene {
+    fn handle_key(&mut self, ctx: &mut Context, key: KeyEvent) -> Transition {
+        if ctx.game.get_state() != GameState::Playing {
+            return Transition::None;
+        }
+        let watching = ctx.controller.is_some();
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('p') => {
+                ctx.game.toggle_pause();
+                return Transition::Push(Box::new(PauseScene::new()));
+            }
+            KeyCode::Char('r') => restart(ctx),
+            _ if watching => {}
+            KeyCode::Char('z') => {
+                ctx.game.undo();
+            }
+            KeyCode::Char('y') => {
+                ctx.game.redo();
+            }
+            _ => {
+                if let Some(input) = input::key_to_input(key) {
+                    ctx.game.apply_input(input);
+                    ctx.record(Delta::Input(input));
+                }
+            }
+        }
+        Transition::None
+    }
+
+    fn update(&mut self, ctx: &mut Context) -> Transition {
+        match ctx.game.get_state() {
+            GameState::Paused => return Transition::Push(Box::new(PauseScene::new())),
+            GameState::GameOver => return Transition::Push(Box::new(ResultsScene::new(ctx))),
+            GameState::Ready | GameState::Playing => {}
+        }
+
+        for _ in 0..self.frames.due() {
+            if ctx.game.frame() {
+                ctx.record(Delta::Frame);
+            }
+        }
+
+        if ctx.game.piece_active() {
+            if let Some(controller) = ctx.controller.as_mut() {
+                if let Some(input) = controller.next_input(&ctx.game) {
+                    ctx.game.apply_input(input);
+                    ctx.record(Delta::Input(input));
+                }
+            }
+        }
+
+        for _ in 0..self.ticks.due(ctx.game.gravity_interval()) {
+            ctx.game.tick();
+            ctx.record(Delta::Tick);
+        }
+
+        ctx.sync();
+        Transition::None
+    }
+
+    fn interrupt(&mut self, ctx: &mut Context) -> Transition {
+        if ctx.game.get_state() != GameState::Playing {
+            return Transition::None;
+        }
+        ctx.game.toggle_pause();
+        Transition::Push(Box::new(PauseScene::new()))
+    }
+
+    fn holds_game(&self) -> bool {
+        true
+    }
+
+    fn suspend(&mut self, ctx: &mut Context) {
+        ctx.clock.pause();
+    }
+
+    // Time spent under the pause or results screen is not played out.
+    fn resume(&mut self, ctx: &mut Context) {
+        self.ticks = TickClock::new();
+        self.frames = FrameClock::new();
+        ctx.clock.resume();
+    }
+
+    fn draw(&self, ctx: &Context) {
+        let mut footer = vec![time_line(ctx)];
+        if let Some(err) = ctx.controller.as_ref().and_then(|c| c.error()) {
+            footer.push(format!("Bot stopped: {}", err));
+        }
+        ctx.game.draw_with_footer(ctx.config.glyphs, &footer);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PauseOption {
+    Resume,
+    Restart,
+    Settings,
+    Controls,
+    CopyFumen,
+    SaveAndQuit,
+    QuitToTitle,
+    Quit,
+}
+
+const PAUSE_OPTIONS: [PauseOption; 8] = [
+    PauseOption::Resume,
+    PauseOption::Restart,
+    PauseOption::Settings,
+    PauseOption::Controls,
+    PauseOption::CopyFumen,
+    PauseOption::SaveAndQuit,
+    PauseOption::QuitToTitle,
+    PauseOption::Quit,
+];
+
+impl PauseOption {
+    fn label(self) -> &'static str {
+        match self {
+            PauseOption::Resume => "Resume",
+            PauseOption::Restart => "Restart",
+            PauseOption::Settings => "Settings",
+            PauseOption::Controls => "Controls",
+            PauseOption::CopyFumen => "Copy Board as Fumen",
+            PauseOption::SaveAndQuit => "Save & Quit to Title",
+            PauseOption::QuitToTitle => "Quit to Title",
+            PauseOption::Quit => "Quit",
+        }
+    }
+}
+
+/// The pause menu. The board is hidden while it is open, so pausing cannot
+/// be used to plan ahead.
+#[derive(Default)]
+pub struct PauseScene {
+    selected: usize,
+    notice: Option<String>,
+}
+
+impl PauseScene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn choose(&mut self, ctx: &mut Context, option: PauseOption) -> Transition {
+        match option {
+            PauseOption::Resume => {
+                ctx.game.toggle_pause();
+                Transition::Pop
+            }
+            PauseOption::Restart => {
+                restart(ctx);
+                Transition::Pop
+            }
+            PauseOption::Settings => Transition::Push(Box::new(SettingsScene::new(&ctx.config))),
+            PauseOption::Controls => Transition::Push(Box::new(HelpScene)),
+            PauseOption::CopyFumen => {
+                self.notice = Some(match fumen::encode_game(&ctx.game) {
+                    Ok(data) => {
+                        clipboard::copy(&data);
+                        format!("Copied to clipboard: {}", data)
+                    }
+                    Err(err) => format!("Could not copy: {}", err),
+                });
+                Transition::None
+            }
+            PauseOption::SaveAndQuit => match save::save(&ctx.config.home, &ctx.game) {
+                Ok(()) => Transition::Home,
+                Err(err) => {
+                    self.notice = Some(format!("Could not save: {}", err));
+                    Transition::None
+                }
+            },
+            PauseOption::QuitToTitle => Transition::Home,
+            PauseOption::Quit => Transition::Quit,
+        }
+    }
+}
+
+impl Scene for PauseScene {
+    fn handle_key(&mut self, ctx: &mut Context, key: KeyEvent) -> Transition {
+        self.notice = None;
+        let shortcut = match key.code {
+            KeyCode::Esc | KeyCode::Char('p') => PauseOption::Resume,
+            KeyCode::Char('f') => PauseOption::CopyFumen,
+            KeyCode::Char('s') => PauseOption::SaveAndQuit,
+            KeyCode::Char('q') => PauseOption::QuitToTitle,
+            KeyCode::Enter => PAUSE_OPTIONS[self.selected],
+            KeyCode::Up => {
+                self.selected = self
+                    .selected
+                    .checked_sub(1)
+                    .unwrap_or(PAUSE_OPTIONS.len() - 1);
+                return Transition::None;
+            }
+            KeyCode::Down => {
+                self.selected = (self.selected + 1) % PAUSE_OPTIONS.len();
+                return Transition::None;
+            }
+            _ => return Transition::None,
+        };
+        self.choose(ctx, shortcut)
+    }
+
+    fn update(&mut self, ctx: &mut Context) -> Transition {
+        ctx.sync();
+        Transition::None
+    }
+
+    fn draw(&self, ctx: &Context) {
+        print!("\x1B[2J");
+        let mut lines = vec!["Paused".to_string(), String::new()];
+        for (i, option) in PAUSE_OPTIONS.iter().enumerate() {
+            let marker = if i == self.selected { ">" } else { " " };
+            lines.push(format!("{} {}  ", marker, option.label()));
+        }
+        lines.push(String::new());
+        lines.push(time_line(ctx));
+        lines.push("Enter: choose  Esc: resume".to_string());
+        lines.push(self.notice.clone().unwrap_or_default());
+        Area::terminal().centre_lines(&lines);
+    }
+}
+
+/// The finished game with its leaderboard place, if it earned one.
+pub struct ResultsScene {
+    place: Option<usize>,
+    notice: Option<String>,
+}
+
+impl ResultsScene {
+    /// Ranks the game that just ended.
+    pub fn new(ctx: &Context) -> Self {
+        let (place, notice) = match leaderboard::submit(&ctx.config.home, &ctx.game) {
+            Ok(place) => (place, None),
+            Err(err) => (None, Some(format!("Could not save the score: {}", err))),
+        };
+        Self { place, notice }
+    }
+}
+
+impl Scene for ResultsScene {
+    fn handle_key(&mut self, ctx: &mut Context, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Char('r') => {
+                restart(ctx);
+                Transition::Pop
+            }
+            KeyCode::Char('z') if ctx.game.undo() => Transition::Pop,
+            KeyCode::Char('q') | KeyCode::Esc => Transition::Home,
+            _ => Transition::None,
+        }
+    }
+
+    fn update(&mut self, ctx: &mut Context) -> Transition {
+        ctx.sync();
+        Transition::None
+    }
+
+    fn draw(&self, ctx: &Context) {
+        let mut footer = vec![time_line(ctx)];
+        if let Some(place) = self.place {
+            footer.push(format!(
+                "New best: #{} in {}",
+                place,
+                ctx.game.mode().label()
+            ));
+        } else if ctx.game.undo_used() && leaderboard::MODES.contains(&ctx.game.mode()) {
+            footer.push("Undo was used, so this game is not ranked".to_string());
+        }
+        if let Some(notice) = &self.notice {
+            footer.push(notice.clone());
+        }
+        footer.push(if ctx.game.mode().allows_undo() {
+            "r: play again  z: undo  q: title".to_string()
+        } else {
+            "r: play again  q: title".to_string()
+        });
+        ctx.game.draw_with_footer(ctx.config.glyphs, &footer);
+    }
+}
@@ -0,0 +1,765 @@
+//! Handcrafted challenges: a starting board, a fixed piece sequence and a
+//! goal to reach before the pieces run out. Puzzles are JSON files; a few
+//! ship with the game and more can be dropped into `puzzles/` under the data
+//! directory. Which puzzles have been solved is remembered between runs.
+
+use crate::client::attack::ClearKind;
+use crate::client::board::{
+    Board, Cell, DEFAULT_HEIGHT, DEFAULT_WIDTH, MAX_HEIGHT, MAX_WIDTH, MIN_HEIGHT, MIN_WIDTH,
+};
+use crate::client::game::{Game, GameMode, GameState};
+use crate::client::input;
+use crate::client::layout::Area;
+use crate::client::menu::{self, MessageScene};
+use crate::client::rules::RuleSet;
+use crate::client::scene::{Context, Scene, Transition};
+use crate::client::tetromino::TetrominoType;
+use crate::client::timing::Timing;
+use crossterm::event::{KeyCode, KeyEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const PROGRESS_FILE_NAME: &str = "puzzles.json";
+
+const BUILT_IN: [(&str, &str); 4] = [
+    ("01-tetris", include_str!("../../puzzles/01-tetris.json")),
+    (
+        "02-perfect-clear",
+        include_str!("../../puzzles/02-perfect-clear.json"),
+    ),
+    (
+        "03-t-spin-mini",
+        include_str!("../../puzzles/03-t-spin-mini.json"),
+    ),
+    (
+        "04-four-lines",
+        include_str!("../../puzzles/04-four-lines.json"),
+    ),
+];
+
+/// What the player has to achieve.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Goal {
+    /// Leave the board empty after a clear.
+    PerfectClear,
+    /// Clear four lines with one piece.
+    Tetris,
+    /// Clear `lines` lines with a T-spin; a full one unless `mini` is set.
+    TSpin {
+        lines: u8,
+        #[serde(default)]
+        mini: bool,
+    },
+    /// Clear at least `lines` lines in total.
+    Lines { lines: u32 },
+}
+
+impl Goal {
+    pub fn describe(&self) -> String {
+        match self {
+            Goal::PerfectClear => "Perfect clear".to_string(),
+            Goal::Tetris => "Clear a Tetris".to_string(),
+            Goal::TSpin { .. } => self.clear_kind().label().to_string(),
+            Goal::Lines { lines } => format!("Clear {} lines", lines),
+        }
+    }
+
+    fn clear_kind(&self) -> ClearKind {
+        match *self {
+            Goal::TSpin { lines, mini: true } => ClearKind::TSpinMini(lines),
+            Goal::TSpin { lines, mini: false } => ClearKind::TSpin(lines),
+            _ => ClearKind::None,
+        }
+    }
+
+    fn reached(&self, game: &Game) -> bool {
+        match self {
+            Goal::PerfectClear => game.last_clear().lines() > 0 && game.board().is_empty(),
+            Goal::Tetris => game.last_clear() == ClearKind::Tetris,
+            Goal::TSpin { .. } => game.last_clear() == self.clear_kind(),
+            Goal::Lines { lines } => game.lines() >= *lines,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Puzzle {
+    /// File name without the extension; progress is stored under it.
+    #[serde(skip)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Rows of the starting stack from top to bottom, resting on the floor.
+    /// `.` or a space is empty, anything else filled. Rows beyond the
+    /// visible height reach into the hidden buffer.
+    #[serde(default)]
+    pub board: Vec<String>,
+    /// Visible board size, when not the standard 10x20.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<usize>,
+    /// The active piece followed by the rest of the sequence.
+    pub pieces: Vec<TetrominoType>,
+    #[serde(default)]
+    pub hold: Option<TetrominoType>,
+    pub goal: Goal,
+}
+
+impl FromStr for Goal {
+    type Err = String;
+
+    /// Parses the short forms typed in the editor: `pc`, `tetris`,
+    /// `tspin <lines>`, `mini <lines>` and `lines <count>`.
+    fn from_str(text: &str) -> Result<Self, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        fn count<T: FromStr>(word: Option<&&str>, text: &str) -> Result<T, String> {
+            word.and_then(|word| word.parse().ok())
+                .ok_or_else(|| format!("\"{}\" needs a number of lines", text.trim()))
+        }
+        match words
+            .first()
+            .map(|word| word.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("pc") => Ok(Goal::PerfectClear),
+            Some("tetris") => Ok(Goal::Tetris),
+            Some("tspin") => Ok(Goal::TSpin {
+                lines: count(words.get(1), text)?,
+                mini: false,
+            }),
+            Some("mini") => Ok(Goal::TSpin {
+                lines: count(words.get(1), text)?,
+                mini: true,
+            }),
+            Some("lines") => Ok(Goal::Lines {
+                lines: count(words.get(1), text)?,
+            }),
+            _ => Err(format!("unknown goal \"{}\"", text.trim())),
+        }
+    }
+}
+
+impl Puzzle {
+    /// A puzzle starting from `board`, with the rows above its highest
+    /// filled cell left out.
+    pub fn new(
+        name: &str,
+        board: &Board,
+        hold: Option<TetrominoType>,
+        pieces: &[TetrominoType],
+        goal: Goal,
+    ) -> Self {
+        let height = board.height();
+        let top = (0..height).find(|&y| board.row(y) != 0).unwrap_or(height);
+        let rows = (top..height)
+            .map(|y| {
+                (0..board.width())
+                    .map(|x| match board.get(x, y) {
+                        Cell::Filled => 'X',
+                        _ => '.',
+                    })
+                    .collect()
+            })
+            .collect();
+        Self {
+            id: String::new(),
+            name: name.to_string(),
+            description: String::new(),
+            board: rows,
+            width: (board.width() != DEFAULT_WIDTH).then_some(board.width()),
+            height: (board.visible_height() != DEFAULT_HEIGHT).then_some(board.visible_height()),
+            pieces: pieces.to_vec(),
+            hold,
+            goal,
+        }
+    }
+
+    pub fn parse(id: &str, text: &str) -> Result<Self, String> {
+        let mut puzzle: Puzzle = serde_json::from_str(text).map_err(|err| err.to_string())?;
+        puzzle.id = id.to_string();
+        if puzzle.pieces.is_empty() {
+            return Err("the piece sequence is empty".to_string());
+        }
+        puzzle.starting_board()?;
+        Ok(puzzle)
+    }
+
+    pub fn starting_board(&self) -> Result<Board, String> {
+        let width = self.width.unwrap_or(DEFAULT_WIDTH);
+        let height = self.height.unwrap_or(DEFAULT_HEIGHT);
+        if !(MIN_WIDTH..=MAX_WIDTH).contains(&width) || !(MIN_HEIGHT..=MAX_HEIGHT).contains(&height)
+        {
+            return Err(format!("a {}x{} board is not supported", width, height));
+        }
+        let mut board = Board::with_size(width, height);
+        if self.board.len() > board.height() {
+            return Err(format!(
+                "the board is taller than {} rows, buffer included",
+                board.height()
+            ));
+        }
+        let top = board.height() - self.board.len();
+        for (i, row) in self.board.iter().enumerate() {
+            if row.chars().count() != width {
+                return Err(format!("board row {} is not {} cells wide", i + 1, width));
+            }
+            for (x, c) in row.chars().enumerate() {
+                if c != '.' && c != ' ' {
+                    board.set(x, top + i, Cell::Filled);
+                }
+            }
+        }
+        Ok(board)
+    }
+
+    /// A fresh attempt, ready to play. Puzzles have no gravity, so they
+    /// have no line clear or entry delay either.
+    pub fn game(&self) -> Game {
+        let mut game = Game::with_rules(rand::random(), RuleSet::GUIDELINE);
+        game.set_timing(Timing::INSTANT);
+        let board = self.starting_board().unwrap_or_default();
+        game.load_position(board, self.hold, &self.pieces);
+        game.start(GameMode::Puzzle);
+        game
+    }
+}
+
+/// Where puzzle files go in the data directory `dir`.
+pub fn puzzle_dir(dir: &Path) -> PathBuf {
+    dir.join("puzzles")
+}
+
+/// Writes `puzzle` to `puzzle_dir(dir)` under a file name made from its
+/// name, never replacing an existing file. Returns the id it was saved as.
+pub fn save_puzzle(dir: &Path, puzzle: &Puzzle) -> io::Result<String> {
+    let dir = puzzle_dir(dir);
+    fs::create_dir_all(&dir)?;
+    let slug: String = puzzle
+        .name
+        .to_ascii_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let base = if slug.is_empty() { "custom" } else { &slug };
+    let id = (1..)
+        .map(|n| match n {
+            1 => base.to_string(),
+            n => format!("{}-{}", base, n),
+        })
+        .find(|id| !dir.join(format!("{}.json", id)).exists())
+        .expect("some file name is free");
+    let text = serde_json::to_string_pretty(puzzle).map_err(io::Error::from)?;
+    fs::write(dir.join(format!("{}.json", id)), text)?;
+    Ok(id)
+}
+
+/// The built-in puzzles and any in `puzzle_dir(dir)`, ordered by id. A
+/// file sharing a built-in puzzle's id replaces it. Files that fail to
+/// parse are skipped and reported in the second list.
+pub fn load_all(dir: &Path) -> (Vec<Puzzle>, Vec<String>) {
+    let mut puzzles = BTreeMap::new();
+    let mut errors = Vec::new();
+    for (id, text) in BUILT_IN {
+        match Puzzle::parse(id, text) {
+            Ok(puzzle) => {
+                puzzles.insert(id.to_string(), puzzle);
+            }
+            Err(err) => errors.push(format!("{}: {}", id, err)),
+        }
+    }
+
+    if let Ok(entries) = fs::read_dir(puzzle_dir(dir)) {
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let parsed = fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| Puzzle::parse(id, &text));
+            match parsed {
+                Ok(puzzle) => {
+                    puzzles.insert(id.to_string(), puzzle);
+                }
+                Err(err) => errors.push(format!("{}: {}", id, err)),
+            }
+        }
+    }
+    (puzzles.into_values().collect(), errors)
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct PuzzleProgress {
+    pub attempts: u32,
+    pub solved: bool,
+}
+
+/// Progress for every puzzle played so far, keyed by puzzle id.
+pub type Progress = BTreeMap<String, PuzzleProgress>;
+
+fn progress_path(dir: &Path) -> PathBuf {
+    dir.join(PROGRESS_FILE_NAME)
+}
+
+/// The progress kept in the data directory `dir`. Missing or unreadable
+/// progress starts over rather than failing.
+pub fn load_progress(dir: &Path) -> Progress {
+    fs::read_to_string(progress_path(dir))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_progress(dir: &Path, progress: &Progress) -> io::Result<()> {
+    let path = progress_path(dir);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(
+        path,
+        serde_json::to_vec_pretty(progress).map_err(io::Error::from)?,
+    )
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Solved,
+    Failed(&'static str),
+}
+
+/// Judges one attempt. The engine deals random pieces once the sequence is
+/// exhausted, so this follows which of the active and held pieces still
+/// belong to the puzzle and calls the attempt failed when neither can be
+/// played.
+#[derive(Clone)]
+pub struct Attempt {
+    goal: Goal,
+    sequence_len: u64,
+    /// `pieces_dealt` when the sequence's first piece became active.
+    first_dealt: u64,
+    pieces_dealt: u64,
+    could_hold: bool,
+    current_in_sequence: bool,
+    held_in_sequence: bool,
+    outcome: Option<Outcome>,
+}
+
+impl Attempt {
+    pub fn new(puzzle: &Puzzle, game: &Game) -> Self {
+        Self {
+            goal: puzzle.goal.clone(),
+            sequence_len: puzzle.pieces.len() as u64,
+            first_dealt: game.pieces_dealt(),
+            pieces_dealt: game.pieces_dealt(),
+            could_hold: game.can_hold(),
+            current_in_sequence: true,
+            held_in_sequence: puzzle.hold.is_some(),
+            outcome: None,
+        }
+    }
+
+    pub fn outcome(&self) -> Option<&Outcome> {
+        self.outcome.as_ref()
+    }
+
+    /// Call after every input applied to `game`.
+    pub fn update(&mut self, game: &Game) -> Option<&Outcome> {
+        if self.outcome.is_some() {
+            return self.outcome.as_ref();
+        }
+        if game.get_state() == GameState::GameOver {
+            self.outcome = Some(Outcome::Failed("Topped out"));
+            return self.outcome.as_ref();
+        }
+
+        let dealt = game.pieces_dealt() > self.pieces_dealt;
+        let fresh_in_sequence = game.pieces_dealt() - self.first_dealt < self.sequence_len;
+        if dealt && game.can_hold() {
+            // A lock. Placing a piece from beyond the sequence instead of
+            // swapping in the held one forfeits the attempt.
+            self.outcome = if !self.current_in_sequence {
+                Some(Outcome::Failed("Out of pieces"))
+            } else if self.goal.reached(game) {
+                Some(Outcome::Solved)
+            } else {
+                None
+            };
+            if self.outcome.is_some() {
+                return self.outcome.as_ref();
+            }
+            self.current_in_sequence = fresh_in_sequence;
+        } else if dealt {
+            // Held into an empty slot.
+            self.held_in_sequence = self.current_in_sequence;
+            self.current_in_sequence = fresh_in_sequence;
+        } else if self.could_hold && !game.can_hold() {
+            std::mem::swap(&mut self.current_in_sequence, &mut self.held_in_sequence);
+        }
+        self.pieces_dealt = game.pieces_dealt();
+        self.could_hold = game.can_hold();
+
+        let can_swap = self.held_in_sequence && game.can_hold();
+        if !self.current_in_sequence && !can_swap {
+            self.outcome = Some(Outcome::Failed("Out of pieces"));
+        }
+        self.outcome.as_ref()
+    }
+}
+
+/// Lists the puzzles in the data directory `dir` with their progress, or
+/// says there are none.
+pub fn browse(dir: &Path) -> Box<dyn Scene> {
+    let (puzzles, errors) = load_all(dir);
+    if puzzles.is_empty() {
+        return Box::new(MessageScene::new("No puzzles found"));
+    }
+    Box::new(PuzzleScene {
+        dir: dir.to_path_buf(),
+        puzzles,
+        progress: load_progress(dir),
+        selected: 0,
+        notice: errors
+            .first()
+            .map(|err| format!("Skipped {}", err))
+            .unwrap_or_default(),
+        playing: None,
+    })
+}
+
+/// An attempt at one of the listed puzzles.
+struct Playing {
+    index: usize,
+    game: Game,
+    attempt: Attempt,
+}
+
+/// The puzzle list, and the puzzle chosen from it once one is being
+/// played. Solving one moves on to the next.
+pub struct PuzzleScene {
+    /// The data directory progress is saved to.
+    dir: PathBuf,
+    puzzles: Vec<Puzzle>,
+    progress: Progress,
+    selected: usize,
+    notice: String,
+    playing: Option<Playing>,
+}
+
+impl PuzzleScene {
+    // Starts a fresh attempt at the puzzle at `index`.
+    fn play(&mut self, index: usize) {
+        let puzzle = &self.puzzles[index];
+        let game = puzzle.game();
+        let attempt = Attempt::new(puzzle, &game);
+        self.playing = Some(Playing {
+            index,
+            game,
+            attempt,
+        });
+        self.record(index, false);
+    }
+
+    // Counts an attempt, or marks the puzzle solved.
+    fn record(&mut self, index: usize, solved: bool) {
+        let record = self
+            .progress
+            .entry(self.puzzles[index].id.clone())
+            .or_default();
+        if solved {
+            record.solved = true;
+        } else {
+            record.attempts += 1;
+        }
+        if let Err(err) = save_progress(&self.dir, &self.progress) {
+            self.notice = format!("Could not save puzzle progress: {}", err);
+        }
+    }
+
+    fn handle_play_key(&mut self, key: KeyEvent) {
+        let Some(playing) = self.playing.as_mut() else {
+            return;
+        };
+        let index = playing.index;
+        let solved = playing.attempt.outcome() == Some(&Outcome::Solved);
+        match key.code {
+            KeyCode::Esc => self.playing = None,
+            KeyCode::Char('r') => self.play(index),
+            KeyCode::Enter if solved && index + 1 < self.puzzles.len() => {
+                self.selected = index + 1;
+                self.play(self.selected);
+            }
+            KeyCode::Enter if solved => self.playing = None,
+            _ if playing.attempt.outcome().is_some() => {}
+            _ => {
+                if let Some(input) = input::key_to_input(key) {
+                    playing.game.apply_input(input);
+                    if playing.attempt.update(&playing.game) == Some(&Outcome::Solved) {
+                        self.record(index, true);
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_list(&self) {
+        let mut lines = vec!["Puzzles".to_string(), String::new()];
+        for (i, puzzle) in self.puzzles.iter().enumerate() {
+            let record = self.progress.get(&puzzle.id).copied().unwrap_or_default();
+            lines.push(format!(
+                "{} [{}] {}",
+                if i == self.selected { ">" } else { " " },
+                if record.solved { "x" } else { " " },
+                puzzle.name
+            ));
+        }
+        let chosen = &self.puzzles[self.selected];
+        lines.push(String::new());
+        lines.push(format!("Goal: {}", chosen.goal.describe()));
+        lines.push(chosen.description.clone());
+        lines.push(String::new());
+        lines.push("Enter: play  Esc: back".to_string());
+        lines.push(self.notice.clone());
+        menu::draw_lines(&lines);
+    }
+}
+
+impl Scene for PuzzleScene {
+    fn handle_key(&mut self, _ctx: &mut Context, key: KeyEvent) -> Transition {
+        if self.playing.is_some() {
+            self.handle_play_key(key);
+            return Transition::None;
+        }
+        self.notice.clear();
+        match key.code {
+            KeyCode::Esc => return Transition::Pop,
+            KeyCode::Enter => self.play(self.selected),
+            code => self.selected = menu::step(self.selected, self.puzzles.len(), code),
+        }
+        Transition::None
+    }
+
+    fn own_game(&self) -> Option<Game> {
+        self.playing.as_ref().map(|playing| playing.game.clone())
+    }
+
+    fn draw(&self, ctx: &Context) {
+        let Some(playing) = &self.playing else {
+            return self.draw_list();
+        };
+        playing.game.draw(ctx.config.glyphs);
+        let puzzle = &self.puzzles[playing.index];
+        let status = match playing.attempt.outcome() {
+            None => format!(
+                "{} - {}  r: retry  Esc: back",
+                puzzle.name,
+                puzzle.goal.describe()
+            ),
+            Some(Outcome::Solved) => "Solved!  Enter: next puzzle  r: retry  Esc: back".to_string(),
+            Some(Outcome::Failed(reason)) => format!("{}  r: retry  Esc: back", reason),
+        };
+        let area = Area::terminal();
+        area.centre(area.bottom() - 1, &status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::input::Input;
+    use TetrominoType::{I, O};
+
+    // Four rows with a well on the right, ready for a vertical I.
+    const WELL: [&str; 4] = ["XXXXXXXXX.", "XXXXXXXXX.", "XXXXXXXXX.", "XXXXXXXXX."];
+    // Stands the I up and drops it down the well.
+    const INTO_WELL: [Input; 3] = [Input::RotateCw, Input::DasRight, Input::HardDrop];
+
+    fn puzzle(hold: Option<TetrominoType>, pieces: &[TetrominoType], goal: Goal) -> Puzzle {
+        Puzzle {
+            id: "test".to_string(),
+            name: "Test".to_string(),
+            description: String::new(),
+            board: WELL.iter().map(|row| row.to_string()).collect(),
+            width: None,
+            height: None,
+            pieces: pieces.to_vec(),
+            hold,
+            goal,
+        }
+    }
+
+    // Plays `inputs` into a fresh attempt, checking that it stays open until
+    // the last one, and returns how it ended.
+    fn play(puzzle: &Puzzle, inputs: &[Input]) -> Option<Outcome> {
+        let mut game = puzzle.game();
+        let mut attempt = Attempt::new(puzzle, &game);
+        for (i, &input) in inputs.iter().enumerate() {
+            assert_eq!(attempt.outcome(), None, "ended before input {}", i + 1);
+            game.apply_input(input);
+            attempt.update(&game);
+        }
+        attempt.outcome().cloned()
+    }
+
+    #[test]
+    fn reaching_the_goal_with_the_last_piece_solves_it() {
+        let puzzle = puzzle(None, &[O, I], Goal::Tetris);
+        let inputs = [&[Input::DasLeft, Input::HardDrop][..], &INTO_WELL].concat();
+        assert_eq!(play(&puzzle, &inputs), Some(Outcome::Solved));
+    }
+
+    #[test]
+    fn placing_every_piece_without_the_goal_fails() {
+        let puzzle = puzzle(None, &[O, O], Goal::Tetris);
+        let inputs = [Input::DasLeft, Input::HardDrop, Input::HardDrop];
+        assert_eq!(
+            play(&puzzle, &inputs),
+            Some(Outcome::Failed("Out of pieces"))
+        );
+    }
+
+    #[test]
+    fn holding_into_an_empty_slot_brings_on_the_next_piece() {
+        let puzzle = puzzle(None, &[O, I], Goal::Tetris);
+        let inputs = [&[Input::Hold][..], &INTO_WELL].concat();
+        assert_eq!(play(&puzzle, &inputs), Some(Outcome::Solved));
+
+        // Holding the last piece leaves only pieces from beyond the sequence,
+        // and hold cannot be used again to swap it back.
+        let puzzle = self::puzzle(None, &[I], Goal::Tetris);
+        assert_eq!(
+            play(&puzzle, &[Input::Hold]),
+            Some(Outcome::Failed("Out of pieces"))
+        );
+    }
+
+    #[test]
+    fn the_held_piece_can_be_swapped_in_after_the_sequence_runs_out() {
+        let puzzle = puzzle(Some(I), &[O], Goal::Tetris);
+        let inputs = [
+            &[Input::DasLeft, Input::HardDrop, Input::Hold][..],
+            &INTO_WELL,
+        ]
+        .concat();
+        assert_eq!(play(&puzzle, &inputs), Some(Outcome::Solved));
+
+        // Placing the piece from beyond the sequence instead forfeits it.
+        let inputs = [Input::DasLeft, Input::HardDrop, Input::HardDrop];
+        assert_eq!(
+            play(&puzzle, &inputs),
+            Some(Outcome::Failed("Out of pieces"))
+        );
+    }
+
+    #[test]
+    fn topping_out_fails() {
+        let puzzle = puzzle(None, &[O; 40], Goal::Lines { lines: 1 });
+        let mut game = puzzle.game();
+        let mut attempt = Attempt::new(&puzzle, &game);
+        for _ in 0..puzzle.pieces.len() {
+            game.apply_input(Input::HardDrop);
+            if attempt.update(&game).is_some() {
+                break;
+            }
+        }
+        assert_eq!(attempt.outcome(), Some(&Outcome::Failed("Topped out")));
+    }
+
+    #[test]
+    fn goals_parse_from_their_short_forms() {
+        assert_eq!("pc".parse(), Ok(Goal::PerfectClear));
+        assert_eq!(" Tetris ".parse(), Ok(Goal::Tetris));
+        assert_eq!(
+            "tspin 2".parse(),
+            Ok(Goal::TSpin {
+                lines: 2,
+                mini: false
+            })
+        );
+        assert_eq!(
+            "MINI 1".parse(),
+            Ok(Goal::TSpin {
+                lines: 1,
+                mini: true
+            })
+        );
+        assert_eq!("lines 12".parse(), Ok(Goal::Lines { lines: 12 }));
+
+        assert_eq!(
+            "tspin".parse::<Goal>(),
+            Err("\"tspin\" needs a number of lines".to_string())
+        );
+        assert_eq!(
+            "lines many".parse::<Goal>(),
+            Err("\"lines many\" needs a number of lines".to_string())
+        );
+        assert_eq!(
+            "spin 2".parse::<Goal>(),
+            Err("unknown goal \"spin 2\"".to_string())
+        );
+        assert!("".parse::<Goal>().is_err());
+    }
+
+    #[test]
+    fn the_starting_board_rests_on_the_floor() {
+        let mut puzzle = puzzle(None, &[I], Goal::Tetris);
+        puzzle.board = vec!["X. X".to_string(), "XX.X".to_string()];
+        puzzle.width = Some(4);
+        puzzle.height = Some(6);
+        let board = puzzle.starting_board().unwrap();
+        assert_eq!((board.width(), board.visible_height()), (4, 6));
+        let bottom = board.height() - 1;
+        let filled: Vec<(usize, usize)> = (0..board.height())
+            .flat_map(|y| (0..4).map(move |x| (x, y)))
+            .filter(|&(x, y)| board.get(x, y) == Cell::Filled)
+            .collect();
+        assert_eq!(
+            filled,
+            [
+                (0, bottom - 1),
+                (3, bottom - 1),
+                (0, bottom),
+                (1, bottom),
+                (3, bottom)
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_starting_boards_are_rejected() {
+        let mut puzzle = puzzle(None, &[I], Goal::Tetris);
+        puzzle.board[2] = "XXXXXXXXXX.".to_string();
+        assert_eq!(
+            puzzle.starting_board().err().as_deref(),
+            Some("board row 3 is not 10 cells wide")
+        );
+
+        puzzle.board = vec![".".repeat(10); 41];
+        assert_eq!(
+            puzzle.starting_board().err().as_deref(),
+            Some("the board is taller than 40 rows, buffer included")
+        );
+
+        puzzle.board.clear();
+        puzzle.width = Some(MIN_WIDTH - 1);
+        assert_eq!(
+            puzzle.starting_board().err().as_deref(),
+            Some("a 3x20 board is not supported")
+        );
+        puzzle.width = None;
+        puzzle.height = Some(MAX_HEIGHT + 1);
+        assert_eq!(
+            puzzle.starting_board().err().as_deref(),
+            Some("a 10x101 board is not supported")
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use crate::client::tetromino::TetrominoType;
+use serde::{Deserialize, Serialize};
+
+/// How the next piece is picked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum RandomizerKind {
+    /// Every piece equally likely every time.
+    #[default]
+    Uniform,
+    /// All seven pieces in a shuffled bag, then the next bag.
+    Bag,
+    /// As on the NES: a repeat of the last piece is rolled again once.
+    Classic,
+    /// As in TGM: up to `HISTORY_ROLLS` rolls to find a piece that is not
+    /// among the last four.
+    History,
+}
+
+/// Rolls the history randomizer makes before it settles for a recent piece.
+const HISTORY_ROLLS: u32 = 4;
+
+/// The history a game starts with, so the first piece is never an S or Z.
+const START_HISTORY: [TetrominoType; 4] = [
+    TetrominoType::Z,
+    TetrominoType::S,
+    TetrominoType::S,
+    TetrominoType::Z,
+];
+
+/// Seedable piece generator. The whole state is a few bytes, so games can
+/// be replayed from a seed and snapshots can copy it cheaply.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Randomizer {
+    state: u64,
+    #[serde(default)]
+    kind: RandomizerKind,
+    /// Pieces left in the current bag, one bit per `TetrominoType::ALL`.
+    #[serde(default)]
+    bag: u8,
+    /// The latest pieces dealt, newest first.
+    #[serde(default)]
+    history: [Option<TetrominoType>; 4],
+}
+
+impl Randomizer {
+    pub fn new(seed: u64) -> Self {
+        Self::with_kind(seed, RandomizerKind::Uniform)
+    }
+
+    pub fn with_kind(seed: u64, kind: RandomizerKind) -> Self {
+        let history = match kind {
+            RandomizerKind::History => START_HISTORY.map(Some),
+            _ => [None; 4],
+        };
+        Self {
+            state: seed,
+            kind,
+            bag: 0,
+            history,
+        }
+    }
+
+    // SplitMix64: tiny, fast and good enough for picking pieces.
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..bound`.
+    pub fn next_below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    pub fn next_piece(&mut self) -> TetrominoType {
+        let piece = match self.kind {
+            RandomizerKind::Uniform => self.roll(),
+            RandomizerKind::Bag => self.draw_from_bag(),
+            RandomizerKind::Classic => {
+                // The NES rolls an eighth value to stand for "roll again".
+                let roll = self.next_below(TetrominoType::ALL.len() as u64 + 1) as usize;
+                match TetrominoType::ALL.get(roll) {
+                    Some(&piece) if Some(piece) != self.history[0] => piece,
+                    _ => self.roll(),
+                }
+            }
+            RandomizerKind::History => {
+                let mut piece = self.roll();
+                for _ in 1..HISTORY_ROLLS {
+                    if !self.history.contains(&Some(piece)) {
+                        break;
+                    }
+                    piece = self.roll();
+                }
+                piece
+            }
+        };
+        self.history.rotate_right(1);
+        self.history[0] = Some(piece);
+        piece
+    }
+
+    fn roll(&mut self) -> TetrominoType {
+        let piece_types = TetrominoType::ALL;
+        piece_types[self.next_below(piece_types.len() as u64) as usize]
+    }
+
+    fn draw_from_bag(&mut self) -> TetrominoType {
+        if self.bag == 0 {
+            self.bag = (1 << TetrominoType::ALL.len()) - 1;
+        }
+        let mut pick = self.next_below(self.bag.count_ones() as u64);
+        for (i, &piece) in TetrominoType::ALL.iter().enumerate() {
+            if self.bag & (1 << i) == 0 {
+                continue;
+            }
+            if pick == 0 {
+                self.bag &= !(1 << i);
+                return piece;
+            }
+            pick -= 1;
+        }
+        unreachable!("the bag is refilled before it runs out")
+    }
+}
@@ -0,0 +1,637 @@
+//! The rules a game is played by, gathered in one `RuleSet`: how pieces
+//! turn and are dealt, how fast they fall and lock, what the player sees
+//! and how clears score. Games take theirs from `GameMode::fixed_rules`,
+//! or for most modes from the preset named by the `rules` setting with any
+//! of the per-rule settings applied on top.
+
+use crate::client::board::Board;
+use crate::client::randomizer::RandomizerKind;
+use crate::client::tetromino::{Tetromino, TetrominoType};
+use crate::client::timing::FRAME_RATE;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Most upcoming pieces a rule set may show.
+pub const MAX_PREVIEW: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RuleSet {
+    pub rotation: RotationSystem,
+    pub randomizer: RandomizerKind,
+    pub gravity: Gravity,
+    /// Frames a piece rests on the stack before it locks, counted again
+    /// each time it falls to a row lower than before. With none, a piece
+    /// locks on the first gravity tick it cannot fall.
+    pub lock_delay: u32,
+    pub hold: bool,
+    /// Upcoming pieces shown, up to `MAX_PREVIEW`.
+    pub preview: usize,
+    pub scoring: Scoring,
+    /// Whether the landing position is drawn under the piece.
+    pub ghost: bool,
+    /// Whether a piece locking entirely above the visible field ends the
+    /// game. A piece spawning into the stack, or garbage pushing blocks out
+    /// of the top, always does.
+    pub lock_out: bool,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self::GUIDELINE
+    }
+}
+
+/// Preset names for the `rules` setting and the settings screen.
+pub const PRESETS: [(&str, RuleSet); 3] = [
+    ("guideline", RuleSet::GUIDELINE),
+    ("classic", RuleSet::CLASSIC),
+    ("tgm", RuleSet::TGM),
+];
+
+impl RuleSet {
+    /// Modern rules: wall kicks, a 7-bag, hold, a long preview and a ghost.
+    pub const GUIDELINE: RuleSet = RuleSet {
+        rotation: RotationSystem::Srs,
+        randomizer: RandomizerKind::Bag,
+        gravity: Gravity::Guideline,
+        lock_delay: 30,
+        hold: true,
+        preview: 5,
+        scoring: Scoring::Guideline,
+        ghost: true,
+        lock_out: true,
+    };
+
+    /// In the style of the NES game: no kicks, hold or ghost, one piece of
+    /// preview, and pieces lock as soon as they land.
+    pub const CLASSIC: RuleSet = RuleSet {
+        rotation: RotationSystem::Nintendo,
+        randomizer: RandomizerKind::Classic,
+        gravity: Gravity::Classic,
+        lock_delay: 0,
+        hold: false,
+        preview: 1,
+        scoring: Scoring::Classic,
+        ghost: false,
+        lock_out: false,
+    };
+
+    /// In the style of TGM: simple kicks, a history randomizer and gravity
+    /// that soon reaches a row a frame.
+    pub const TGM: RuleSet = RuleSet {
+        rotation: RotationSystem::Arika,
+        randomizer: RandomizerKind::History,
+        gravity: Gravity::Tgm,
+        lock_delay: 30,
+        hold: false,
+        preview: 1,
+        scoring: Scoring::Tgm,
+        ghost: true,
+        lock_out: false,
+    };
+
+    /// The preset named by the `rules` setting (guideline, classic or tgm;
+    /// guideline if not given) with any of the `rotation`, `randomizer`,
+    /// `gravity`, `lock_delay`, `hold`, `preview`, `scoring`, `ghost` and
+    /// `lock_out` settings that are given replacing its rule.
+    pub fn from_settings<'a>(setting: impl Fn(&str) -> Option<&'a str>) -> Result<RuleSet, String> {
+        let mut rules = match setting("rules") {
+            Some(name) => parse_choice("rules", name, &PRESETS)?,
+            None => RuleSet::default(),
+        };
+        if let Some(value) = setting("rotation") {
+            rules.rotation = parse_choice("rotation", value, &RotationSystem::NAMES)?;
+        }
+        if let Some(value) = setting("randomizer") {
+            rules.randomizer = parse_choice("randomizer", value, &RANDOMIZER_NAMES)?;
+        }
+        if let Some(value) = setting("gravity") {
+            rules.gravity = parse_choice("gravity", value, &Gravity::NAMES)?;
+        }
+        if let Some(value) = setting("lock_delay") {
+            rules.lock_delay = value
+                .trim()
+                .parse()
+                .map_err(|_| "lock_delay must be a number of frames".to_string())?;
+        }
+        if let Some(value) = setting("hold") {
+            rules.hold = parse_choice("hold", value, &SWITCH_NAMES)?;
+        }
+        if let Some(value) = setting("preview") {
+            rules.preview = value
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&preview| preview <= MAX_PREVIEW)
+                .ok_or_else(|| format!("preview must be 0 to {} pieces", MAX_PREVIEW))?;
+        }
+        if let Some(value) = setting("scoring") {
+            rules.scoring = parse_choice("scoring", value, &Scoring::NAMES)?;
+        }
+        if let Some(value) = setting("ghost") {
+            rules.ghost = parse_choice("ghost", value, &SWITCH_NAMES)?;
+        }
+        if let Some(value) = setting("lock_out") {
+            rules.lock_out = parse_choice("lock_out", value, &SWITCH_NAMES)?;
+        }
+        Ok(rules)
+    }
+}
+
+const RANDOMIZER_NAMES: [(&str, RandomizerKind); 4] = [
+    ("bag", RandomizerKind::Bag),
+    ("classic", RandomizerKind::Classic),
+    ("history", RandomizerKind::History),
+    ("uniform", RandomizerKind::Uniform),
+];
+
+const SWITCH_NAMES: [(&str, bool); 2] = [("on", true), ("off", false)];
+
+fn parse_choice<T: Copy>(setting: &str, value: &str, choices: &[(&str, T)]) -> Result<T, String> {
+    let value = value.trim().to_lowercase();
+    choices
+        .iter()
+        .find(|(name, _)| *name == value)
+        .map(|&(_, choice)| choice)
+        .ok_or_else(|| {
+            let names: Vec<&str> = choices.iter().map(|(name, _)| *name).collect();
+            format!(
+                "{} must be one of {}, not {:?}",
+                setting,
+                names.join(", "),
+                value
+            )
+        })
+}
+
+/// How pieces turn, and where they may be nudged to when turning in place
+/// would collide. Pieces always turn about their own centre.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RotationSystem {
+    /// The guideline's Super Rotation System and its wall kick tables.
+    Srs,
+    /// The NES's: no kicks, so a turn that collides does not happen, and
+    /// the I, S and Z have only two states, lying flat in the lower of the
+    /// two rows they can or standing up, which they switch between
+    /// whichever way they are turned.
+    Nintendo,
+    /// TGM's basic kicks: one column right, then one left. The I piece
+    /// does not kick.
+    Arika,
+}
+
+/// Kicks tried turning clockwise, indexed by the rotation turned from,
+/// as `(dx, dy)` with y growing downwards.
+const SRS_KICKS_CW: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+];
+
+const SRS_KICKS_CCW: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+];
+
+const SRS_I_KICKS_CW: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+];
+
+const SRS_I_KICKS_CCW: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+];
+
+const ARIKA_KICKS: [(i32, i32); 3] = [(0, 0), (1, 0), (-1, 0)];
+
+impl RotationSystem {
+    const NAMES: [(&'static str, RotationSystem); 3] = [
+        ("srs", RotationSystem::Srs),
+        ("nintendo", RotationSystem::Nintendo),
+        ("arika", RotationSystem::Arika),
+    ];
+
+    /// Turns `piece` at (`x`, `y`) on `board`, trying each kick in turn.
+    /// Returns the turned piece, where it ended up and the index of the
+    /// kick that fit (0 for none), or `None` if nothing fits.
+    pub fn rotate(
+        self,
+        board: &Board,
+        piece: &Tetromino,
+        x: i32,
+        y: i32,
+        clockwise: bool,
+    ) -> Option<(Tetromino, i32, i32, usize)> {
+        let kind = piece.tetromino_type;
+        let two_state = self == RotationSystem::Nintendo
+            && matches!(kind, TetrominoType::I | TetrominoType::S | TetrominoType::Z);
+        // The spawn state lies a row higher in the piece's box than the
+        // lower flat one, so it turns as the lower one would a row up.
+        let (piece, x, y) = if two_state && piece.rotation == 0 {
+            let mut lower = piece.clone();
+            lower.rotate_clockwise();
+            lower.rotate_clockwise();
+            let (from_x, from_y) = box_origin(kind, 0);
+            let (to_x, to_y) = box_origin(kind, 2);
+            (lower, x + from_x - to_x, y + from_y - to_y - 1)
+        } else {
+            (piece.clone(), x, y)
+        };
+        // Flat turns to state 1 and standing up to state 2.
+        let clockwise = if two_state {
+            piece.rotation == 1
+        } else {
+            clockwise
+        };
+
+        let from = piece.rotation as usize;
+        let mut turned = piece.clone();
+        if clockwise {
+            turned.rotate_clockwise();
+        } else {
+            turned.rotate_anticlockwise();
+        }
+        let (pivot_x, pivot_y) = box_origin(kind, from);
+        let (to_x, to_y) = box_origin(kind, turned.rotation as usize);
+        let (x, y) = (x + pivot_x - to_x, y + pivot_y - to_y);
+
+        let kicks: &[(i32, i32)] = match (self, kind, clockwise) {
+            (RotationSystem::Nintendo, _, _)
+            | (_, TetrominoType::O, _)
+            | (RotationSystem::Arika, TetrominoType::I, _) => &[(0, 0)],
+            (RotationSystem::Arika, _, _) => &ARIKA_KICKS,
+            (RotationSystem::Srs, TetrominoType::I, true) => &SRS_I_KICKS_CW[from],
+            (RotationSystem::Srs, TetrominoType::I, false) => &SRS_I_KICKS_CCW[from],
+            (RotationSystem::Srs, _, true) => &SRS_KICKS_CW[from],
+            (RotationSystem::Srs, _, false) => &SRS_KICKS_CCW[from],
+        };
+        kicks
+            .iter()
+            .position(|&(dx, dy)| !board.collides(&turned, x + dx, y + dy))
+            .map(|kick| {
+                let (dx, dy) = kicks[kick];
+                (turned, x + dx, y + dy, kick)
+            })
+    }
+}
+
+// Top-left of the smallest square box the piece turns within, inside its
+// 4x4 grid at `rotation`. The grid itself turns about its centre, so moving
+// the piece by the change in this corner keeps it turning about its own.
+fn box_origin(piece: TetrominoType, rotation: usize) -> (i32, i32) {
+    const THREE_WIDE: [(i32, i32); 4] = [(0, 0), (1, 0), (1, 1), (0, 1)];
+    match piece {
+        TetrominoType::I => (0, 0),
+        TetrominoType::O => [(1, 0), (2, 1), (1, 2), (0, 1)][rotation],
+        // The T spawns a row lower in its grid than the other three-wide
+        // pieces, which puts its box a quarter turn behind theirs.
+        TetrominoType::T => THREE_WIDE[(rotation + 3) % 4],
+        _ => THREE_WIDE[rotation],
+    }
+}
+
+/// How long a piece takes to fall a row as the level goes up.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Gravity {
+    /// The guideline curve: a second a row at level 1, about a row a frame
+    /// by level 13.
+    Guideline,
+    /// The NES table, counting its level 0 as level 1.
+    Classic,
+    /// Halving every level from a row a second to a row a frame.
+    Tgm,
+}
+
+/// NES frames per row for its levels 0 to 18; from 19 it is two, and one
+/// from 29.
+const CLASSIC_FRAMES: [u32; 19] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3,
+];
+
+impl Gravity {
+    const NAMES: [(&'static str, Gravity); 3] = [
+        ("guideline", Gravity::Guideline),
+        ("classic", Gravity::Classic),
+        ("tgm", Gravity::Tgm),
+    ];
+
+    /// Time between gravity ticks at `level`, never less than a frame.
+    pub fn row_interval(self, level: u32) -> Duration {
+        let frame = Duration::from_secs(1) / FRAME_RATE as u32;
+        let level = level.max(1);
+        let interval = match self {
+            Gravity::Guideline => {
+                let steps = (level - 1).min(19) as f64;
+                Duration::from_secs_f64((0.8 - steps * 0.007).powf(steps))
+            }
+            Gravity::Classic => {
+                let frames = match level - 1 {
+                    nes @ 0..=18 => CLASSIC_FRAMES[nes as usize],
+                    19..=28 => 2,
+                    _ => 1,
+                };
+                frame * frames
+            }
+            Gravity::Tgm => frame * (FRAME_RATE as u32 >> (level - 1).min(6)),
+        };
+        interval.max(frame)
+    }
+}
+
+/// Points for clearing lines.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Scoring {
+    /// 100, 300, 500 and 800 for one to four lines, times the level.
+    Guideline,
+    /// 40, 100, 300 and 1200, times the level, as on the NES.
+    Classic,
+    /// TGM's formula without its soft drop, combo and bravo bonuses: the
+    /// level plus the lines, divided by four and rounded up, times the lines.
+    Tgm,
+}
+
+impl Scoring {
+    const NAMES: [(&'static str, Scoring); 3] = [
+        ("guideline", Scoring::Guideline),
+        ("classic", Scoring::Classic),
+        ("tgm", Scoring::Tgm),
+    ];
+
+    /// Points for clearing `lines` lines at `level`.
+    pub fn points(self, lines: u32, level: u32) -> u32 {
+        let table = match self {
+            Scoring::Guideline => [0, 100, 300, 500, 800],
+            Scoring::Classic => [0, 40, 100, 300, 1200],
+            Scoring::Tgm => return (level + lines).div_ceil(4) * lines,
+        };
+        table[lines.min(4) as usize] * level
+    }
+}
+
+/// Where `piece` spawns on `board`: centred, rounding left, resting on the
+/// visible field in the lowest buffer rows, then moved down a row straight
+/// away if there is room, as in the guideline.
+pub fn spawn_position(board: &Board, piece: &Tetromino) -> (i32, i32) {
+    let bottom = piece.minos().map(|(_, dy)| dy).max().unwrap_or(0);
+    let x = board.width() as i32 / 2 - 2;
+    let y = board.buffer_height() as i32 - 1 - bottom;
+    if board.collides(piece, x, y + 1) {
+        (x, y)
+    } else {
+        (x, y + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::board::{DEFAULT_HEIGHT, DEFAULT_WIDTH};
+    use crate::client::search::piece_cells;
+
+    fn empty() -> Board {
+        Board::with_size(DEFAULT_WIDTH, DEFAULT_HEIGHT)
+    }
+
+    // Turns `piece` at (`x`, `y`) on `board` `turns` times the same way and
+    // returns the cells it ends up on.
+    fn turn(
+        rotation: RotationSystem,
+        board: &Board,
+        kind: TetrominoType,
+        (x, y): (i32, i32),
+        clockwise: bool,
+        turns: usize,
+    ) -> Vec<(i32, i32)> {
+        let (mut piece, mut x, mut y) = (Tetromino::new(kind), x, y);
+        for _ in 0..turns {
+            (piece, x, y, _) = rotation.rotate(board, &piece, x, y, clockwise).unwrap();
+        }
+        piece_cells(&piece, x, y)
+    }
+
+    #[test]
+    fn srs_kicks_back_the_way_they_came() {
+        // Turning back undoes a kick: each table's kicks out of a state are
+        // the other's into it, reversed.
+        for (cw, ccw) in [
+            (SRS_KICKS_CW, SRS_KICKS_CCW),
+            (SRS_I_KICKS_CW, SRS_I_KICKS_CCW),
+        ] {
+            for from in 0..4 {
+                let back = ccw[(from + 1) % 4].map(|(dx, dy)| (-dx, -dy));
+                assert_eq!(cw[from], back);
+            }
+        }
+    }
+
+    #[test]
+    fn a_turn_against_the_wall_kicks_only_where_the_system_does() {
+        // A T standing against the left wall, flat side on the wall.
+        let board = empty();
+        let mut piece = Tetromino::new(TetrominoType::T);
+        piece.rotate_clockwise();
+        let (x, y) = (-1, 10);
+        assert!(!board.collides(&piece, x, y));
+        assert!(board.collides(&piece, x - 1, y));
+
+        // Turned in place it would stick out of the wall by a column.
+        for rotation in [RotationSystem::Srs, RotationSystem::Arika] {
+            let (turned, kicked_x, kicked_y, kick) =
+                rotation.rotate(&board, &piece, x, y, true).unwrap();
+            let cells = piece_cells(&turned, kicked_x, kicked_y);
+            assert_eq!(kick, 1, "{:?}", rotation);
+            assert_eq!(cells.iter().map(|&(x, _)| x).min(), Some(0));
+        }
+        assert!(RotationSystem::Nintendo
+            .rotate(&board, &piece, x, y, true)
+            .is_none());
+
+        // Arika never kicks the I.
+        let mut i = Tetromino::new(TetrominoType::I);
+        i.rotate_clockwise();
+        assert!(RotationSystem::Arika
+            .rotate(&board, &i, -2, 10, true)
+            .is_none());
+        assert_eq!(
+            RotationSystem::Srs
+                .rotate(&board, &i, -2, 10, true)
+                .map(|(_, x, y, kick)| (x, y, kick)),
+            Some((0, 10, 2))
+        );
+    }
+
+    #[test]
+    fn nintendo_pieces_turn_in_place_between_two_states() {
+        let board = empty();
+        for kind in [TetrominoType::I, TetrominoType::S, TetrominoType::Z] {
+            let start = (3, 10);
+            let flat = piece_cells(&Tetromino::new(kind), start.0, start.1);
+            let nintendo = RotationSystem::Nintendo;
+            let standing = turn(nintendo, &board, kind, start, true, 1);
+            assert_ne!(standing, flat);
+            // Either way gives the same states.
+            assert_eq!(turn(nintendo, &board, kind, start, false, 1), standing);
+            for turns in [2, 4, 6] {
+                assert_eq!(turn(nintendo, &board, kind, start, true, turns), flat);
+                assert_eq!(turn(nintendo, &board, kind, start, false, turns), flat);
+                assert_eq!(
+                    turn(nintendo, &board, kind, start, true, turns + 1),
+                    standing
+                );
+            }
+            // Under the guideline the second turn leaves it a row lower.
+            assert_ne!(
+                turn(RotationSystem::Srs, &board, kind, start, true, 2),
+                flat
+            );
+        }
+
+        // Standing up, the S keeps the flat one's bottom row and stands in
+        // its two right-hand columns.
+        assert_eq!(
+            piece_cells(&Tetromino::new(TetrominoType::S), 3, 10),
+            [(3, 11), (4, 10), (4, 11), (5, 10)]
+        );
+        let s = turn(
+            RotationSystem::Nintendo,
+            &board,
+            TetrominoType::S,
+            (3, 10),
+            true,
+            1,
+        );
+        assert_eq!(s, [(4, 9), (4, 10), (5, 10), (5, 11)]);
+    }
+
+    #[test]
+    fn gravity_speeds_up_to_a_row_a_frame() {
+        let frame = Duration::from_secs(1) / FRAME_RATE as u32;
+        let frames =
+            |gravity: Gravity, level: u32| gravity.row_interval(level).as_secs_f64() * 60.0;
+        let close = |a: f64, b: f64| (a - b).abs() < 0.01;
+
+        assert!(close(frames(Gravity::Guideline, 1), 60.0));
+        assert!(close(frames(Gravity::Guideline, 2), 60.0 * 0.793));
+        assert!(close(
+            frames(Gravity::Guideline, 5),
+            60.0 * 0.772_f64.powi(4)
+        ));
+        assert_eq!(Gravity::Guideline.row_interval(15), frame);
+
+        for (level, expected) in [
+            (0, 48),
+            (1, 48),
+            (2, 43),
+            (10, 6),
+            (19, 3),
+            (20, 2),
+            (29, 2),
+            (30, 1),
+        ] {
+            assert_eq!(
+                Gravity::Classic.row_interval(level),
+                frame * expected,
+                "{}",
+                level
+            );
+        }
+        for (level, expected) in [(1, 60), (2, 30), (3, 15), (6, 1), (7, 1), (50, 1)] {
+            assert_eq!(
+                Gravity::Tgm.row_interval(level),
+                frame * expected,
+                "{}",
+                level
+            );
+        }
+
+        for gravity in [Gravity::Guideline, Gravity::Classic, Gravity::Tgm] {
+            let intervals: Vec<Duration> =
+                (1..40).map(|level| gravity.row_interval(level)).collect();
+            assert!(intervals.windows(2).all(|pair| pair[0] >= pair[1]));
+            assert!(intervals.iter().all(|&interval| interval >= frame));
+        }
+    }
+
+    #[test]
+    fn scoring_tables() {
+        let points = |scoring: Scoring, level: u32| {
+            (0..=4)
+                .map(|lines| scoring.points(lines, level))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(points(Scoring::Guideline, 1), [0, 100, 300, 500, 800]);
+        assert_eq!(points(Scoring::Guideline, 3), [0, 300, 900, 1500, 2400]);
+        assert_eq!(points(Scoring::Classic, 1), [0, 40, 100, 300, 1200]);
+        assert_eq!(points(Scoring::Classic, 2), [0, 80, 200, 600, 2400]);
+        // (level + lines) / 4, rounded up, times the lines.
+        assert_eq!(points(Scoring::Tgm, 1), [0, 1, 2, 3, 8]);
+        assert_eq!(points(Scoring::Tgm, 10), [0, 3, 6, 12, 16]);
+        // More than four lines at once, from garbage, score as four.
+        assert_eq!(Scoring::Guideline.points(5, 1), 800);
+    }
+
+    #[test]
+    fn settings_pick_a_preset_and_change_its_rules() {
+        let rules = |settings: &[(&str, &str)]| {
+            RuleSet::from_settings(|name| {
+                settings
+                    .iter()
+                    .find(|(setting, _)| *setting == name)
+                    .map(|&(_, value)| value)
+            })
+        };
+        assert_eq!(rules(&[]), Ok(RuleSet::GUIDELINE));
+        assert_eq!(rules(&[("rules", "classic")]), Ok(RuleSet::CLASSIC));
+        assert_eq!(rules(&[("rules", " TGM ")]), Ok(RuleSet::TGM));
+
+        let changed = rules(&[
+            ("rules", "classic"),
+            ("rotation", "srs"),
+            ("randomizer", "bag"),
+            ("gravity", "tgm"),
+            ("lock_delay", "15"),
+            ("hold", "on"),
+            ("preview", "3"),
+            ("scoring", "guideline"),
+            ("ghost", "On"),
+            ("lock_out", "on"),
+        ]);
+        let expected = RuleSet {
+            rotation: RotationSystem::Srs,
+            randomizer: RandomizerKind::Bag,
+            gravity: Gravity::Tgm,
+            lock_delay: 15,
+            hold: true,
+            preview: 3,
+            scoring: Scoring::Guideline,
+            ghost: true,
+            lock_out: true,
+        };
+        assert_eq!(changed, Ok(expected));
+
+        assert_eq!(
+            rules(&[("rules", "modern")]),
+            Err("rules must be one of guideline, classic, tgm, not \"modern\"".to_string())
+        );
+        assert_eq!(
+            rules(&[("hold", "yes")]),
+            Err("hold must be one of on, off, not \"yes\"".to_string())
+        );
+        assert_eq!(
+            rules(&[("preview", "7")]),
+            Err("preview must be 0 to 6 pieces".to_string())
+        );
+        assert_eq!(
+            rules(&[("lock_delay", "-1")]),
+            Err("lock_delay must be a number of frames".to_string())
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Saving an in-progress game to disk and resuming it later. The whole
+//! `Game`, randomizer state included, is stored as JSON alongside a format
+//! version and a checksum so damaged or foreign files are refused.
+
+use crate::client::board::{BUFFER_HEIGHT, MAX_HEIGHT, MAX_WIDTH, MIN_HEIGHT, MIN_WIDTH};
+use crate::client::game::{Game, GameState};
+use crate::client::tetromino::Tetromino;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever the saved `Game` layout changes incompatibly.
+pub const SAVE_VERSION: u32 = 2;
+
+const SAVE_FILE_NAME: &str = "save.json";
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    checksum: u64,
+    game: serde_json::Value,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    /// The file was written by an incompatible version of the game.
+    Version(u32),
+    /// The file is not a save, or its contents fail the checksum or sanity
+    /// checks.
+    Corrupt(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "{}", err),
+            SaveError::Version(version) => write!(
+                f,
+                "save format {} is not supported (expected {})",
+                version, SAVE_VERSION
+            ),
+            SaveError::Corrupt(reason) => write!(f, "save file is corrupted: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+/// The save in the data directory `dir`.
+pub fn save_path(dir: &Path) -> PathBuf {
+    dir.join(SAVE_FILE_NAME)
+}
+
+pub fn exists(dir: &Path) -> bool {
+    save_path(dir).is_file()
+}
+
+pub fn save(dir: &Path, game: &Game) -> Result<(), SaveError> {
+    let game = serde_json::to_value(game).map_err(io::Error::from)?;
+    let file = SaveFile {
+        version: SAVE_VERSION,
+        checksum: checksum(&game.to_string()),
+        game,
+    };
+    let path = save_path(dir);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Write then rename so a crash mid-save never leaves half a file.
+    let temporary = path.with_extension("json.tmp");
+    fs::write(
+        &temporary,
+        serde_json::to_vec(&file).map_err(io::Error::from)?,
+    )?;
+    fs::rename(temporary, path)?;
+    Ok(())
+}
+
+pub fn load(dir: &Path) -> Result<Game, SaveError> {
+    let text = fs::read_to_string(save_path(dir))?;
+    let file: SaveFile =
+        serde_json::from_str(&text).map_err(|err| SaveError::Corrupt(err.to_string()))?;
+    if file.version != SAVE_VERSION {
+        return Err(SaveError::Version(file.version));
+    }
+    if checksum(&file.game.to_string()) != file.checksum {
+        return Err(SaveError::Corrupt("checksum mismatch".to_string()));
+    }
+    let game: Game =
+        serde_json::from_value(file.game).map_err(|err| SaveError::Corrupt(err.to_string()))?;
+    check(&game).map_err(|reason| SaveError::Corrupt(reason.to_string()))?;
+    Ok(game)
+}
+
+pub fn delete(dir: &Path) -> io::Result<()> {
+    match fs::remove_file(save_path(dir)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+// Rejects states the engine could never reach, which a hand-edited file
+// with a recomputed checksum could still contain.
+fn check(game: &Game) -> Result<(), &'static str> {
+    let board = game.board();
+    let visible = board.height().saturating_sub(board.buffer_height());
+    if !(MIN_WIDTH..=MAX_WIDTH).contains(&board.width())
+        || !(MIN_HEIGHT..=MAX_HEIGHT).contains(&visible)
+        || board.buffer_height() != BUFFER_HEIGHT
+    {
+        return Err("unsupported board size");
+    }
+    if (0..board.height()).any(|y| board.row(y) & !board.full_row() != 0) {
+        return Err("board has cells outside the playfield");
+    }
+
+    let piece = game.current_piece();
+    if piece.rotation > 3 {
+        return Err("invalid piece rotation");
+    }
+    let mut expected = Tetromino::new(piece.tetromino_type);
+    for _ in 0..piece.rotation {
+        expected.rotate_clockwise();
+    }
+    if expected.cells != piece.cells {
+        return Err("piece shape does not match its type");
+    }
+
+    let (x, y) = game.piece_position();
+    let active = matches!(game.get_state(), GameState::Playing | GameState::Paused);
+    if active && game.piece_active() && game.board().collides(piece, x, y) {
+        return Err("active piece overlaps the stack");
+    }
+    Ok(())
+}
+
+// FNV-1a; catches accidental damage, not tampering.
+fn checksum(text: &str) -> u64 {
+    text.bytes().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::board::{Board, DEFAULT_HEIGHT, DEFAULT_WIDTH};
+    use crate::client::game::GameMode;
+    use crate::client::input::Input;
+
+    // An empty directory of its own for each test.
+    fn data_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("tetrust-save-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    // A game a few pieces in, with one held.
+    fn game_in_progress() -> Game {
+        let mut game = Game::with_size(7, DEFAULT_WIDTH, DEFAULT_HEIGHT);
+        game.start(GameMode::Marathon);
+        for input in [Input::DasLeft, Input::HardDrop, Input::Hold] {
+            game.apply_input(input);
+        }
+        for _ in 0..3 {
+            game.apply_input(Input::DasRight);
+            game.apply_input(Input::HardDrop);
+        }
+        game
+    }
+
+    // Saves a game, lets `edit` change the file, optionally with a checksum
+    // that matches the change, and loads it back.
+    fn load_edited(name: &str, rehash: bool, edit: impl FnOnce(&mut SaveFile)) -> SaveError {
+        let dir = data_dir(name);
+        save(&dir, &game_in_progress()).unwrap();
+        let mut file: SaveFile =
+            serde_json::from_str(&fs::read_to_string(save_path(&dir)).unwrap()).unwrap();
+        edit(&mut file);
+        if rehash {
+            file.checksum = checksum(&file.game.to_string());
+        }
+        fs::write(save_path(&dir), serde_json::to_vec(&file).unwrap()).unwrap();
+        let result = load(&dir);
+        fs::remove_dir_all(dir).unwrap();
+        result.err().expect("the edited save loaded")
+    }
+
+    #[test]
+    fn a_saved_game_loads_back_the_same() {
+        let dir = data_dir("round-trip");
+        let mut game = game_in_progress();
+        save(&dir, &game).unwrap();
+        assert!(exists(&dir));
+        let mut loaded = load(&dir).unwrap();
+        delete(&dir).unwrap();
+        assert!(!exists(&dir));
+        fs::remove_dir_all(dir).unwrap();
+
+        assert!(loaded.board() == game.board());
+        assert_eq!(
+            loaded.current_piece().tetromino_type,
+            game.current_piece().tetromino_type
+        );
+        assert_eq!(loaded.piece_position(), game.piece_position());
+        assert_eq!(loaded.held_piece(), game.held_piece());
+        assert_eq!(loaded.preview(), game.preview());
+        assert_eq!(loaded.score(), game.score());
+        assert_eq!(loaded.pieces_dealt(), game.pieces_dealt());
+
+        // The randomizer carries on where it left off.
+        for _ in 0..10 {
+            game.apply_input(Input::HardDrop);
+            loaded.apply_input(Input::HardDrop);
+        }
+        assert!(loaded.board() == game.board());
+        assert_eq!(loaded.preview(), game.preview());
+    }
+
+    #[test]
+    fn a_save_from_another_version_is_refused() {
+        let err = load_edited("version", true, |file| file.version = SAVE_VERSION + 1);
+        assert!(matches!(err, SaveError::Version(version) if version == SAVE_VERSION + 1));
+    }
+
+    #[test]
+    fn a_changed_save_fails_its_checksum() {
+        let err = load_edited("checksum", false, |file| {
+            file.game["score"] = 999_999.into()
+        });
+        assert!(matches!(err, SaveError::Corrupt(reason) if reason == "checksum mismatch"));
+    }
+
+    #[test]
+    fn cells_outside_the_board_are_refused() {
+        let err = load_edited("outside", true, |file| {
+            let rows = file.game["board"]["rows"].as_array_mut().unwrap();
+            let walls = !Board::with_size(DEFAULT_WIDTH, DEFAULT_HEIGHT).full_row();
+            *rows.last_mut().unwrap() = walls.into();
+        });
+        assert!(
+            matches!(err, SaveError::Corrupt(reason) if reason == "board has cells outside the playfield")
+        );
+    }
+
+    #[test]
+    fn an_active_piece_inside_the_stack_is_refused() {
+        let err = load_edited("overlap", true, |file| {
+            let full = Board::with_size(DEFAULT_WIDTH, DEFAULT_HEIGHT).full_row();
+            for row in file.game["board"]["rows"].as_array_mut().unwrap() {
+                *row = full.into();
+            }
+        });
+        assert!(
+            matches!(err, SaveError::Corrupt(reason) if reason == "active piece overlaps the stack")
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Screen navigation as a stack of scenes. Each scene draws itself and
+//! handles its own keys, and asks for a `Transition` when the player moves
+//! on; the stack applies it. Leaving the stack always returns to the
+//! caller, which restores the terminal.
+
+use crate::client::config::Config;
+use crate::client::crash;
+use crate::client::game::{Game, GameState};
+use crate::client::input::Controller;
+use crate::client::save;
+use crate::client::signals;
+use crate::client::spectate::{Broadcaster, Delta};
+use crate::client::timing::PlayClock;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// What the top scene wants to happen next.
+pub enum Transition {
+    None,
+    /// Opens a scene on top of this one.
+    Push(Box<dyn Scene>),
+    /// Closes this scene, returning to the one below.
+    Pop,
+    /// Swaps this scene for another.
+    Replace(Box<dyn Scene>),
+    /// Closes every scene but the title screen.
+    Home,
+    /// Leaves the game.
+    Quit,
+}
+
+pub trait Scene {
+    fn handle_key(&mut self, ctx: &mut Context, key: KeyEvent) -> Transition;
+
+    /// Only scenes that turn on mouse capture receive mouse events.
+    fn handle_mouse(&mut self, _ctx: &mut Context, _event: MouseEvent) -> Transition {
+        Transition::None
+    }
+
+    /// Advances anything that runs on its own, such as gravity or a bot.
+    /// Only the top scene is updated.
+    fn update(&mut self, _ctx: &mut Context) -> Transition {
+        Transition::None
+    }
+
+    /// Called when the player is pulled away: the terminal lost focus or
+    /// the game is about to be stopped. Anything running should pause.
+    fn interrupt(&mut self, _ctx: &mut Context) -> Transition {
+        Transition::None
+    }
+
+    /// Whether this scene is playing the game in the `Context`, which is
+    /// then saved if the process is told to exit.
+    fn holds_game(&self) -> bool {
+        false
+    }
+
+    /// A game this scene plays itself rather than through the `Context`,
+    /// such as a match or a spectated game, for a crash report.
+    fn own_game(&self) -> Option<Game> {
+        None
+    }
+
+    /// Called when another scene opens on top of this one.
+    fn suspend(&mut self, _ctx: &mut Context) {}
+
+    /// Called when this scene is on top again after the one above it closed.
+    fn resume(&mut self, _ctx: &mut Context) {}
+
+    fn draw(&self, ctx: &Context);
+}
+
+/// State shared between scenes: the settings, the game in progress and
+/// what plays and publishes it.
+pub struct Context {
+    pub config: Config,
+    pub game: Game,
+    /// Plays the game in modes where the player only watches.
+    pub controller: Option<Box<dyn Controller>>,
+    /// Spectators and replay recording, if enabled.
+    pub broadcaster: Option<Broadcaster>,
+    /// Seed for the next game started, used once.
+    pub seed: Option<u64>,
+    /// How long the current game has been played, pauses excluded.
+    pub clock: PlayClock,
+    /// The latest deltas applied to the game, for a crash report.
+    pub recent: VecDeque<Delta>,
+}
+
+impl Context {
+    pub fn new(
+        config: Config,
+        game: Game,
+        broadcaster: Option<Broadcaster>,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            config,
+            game,
+            controller: None,
+            broadcaster,
+            seed,
+            clock: PlayClock::new(),
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Forwards a change just made to the game to spectators.
+    pub fn record(&mut self, delta: Delta) {
+        if self.recent.len() == crash::RECENT_DELTAS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(delta);
+        if let Some(broadcaster) = self.broadcaster.as_mut() {
+            broadcaster.record(&self.game, delta);
+        }
+    }
+
+    /// Lets new spectators in and resends the game if it changed outside
+    /// the recorded deltas. Call once per frame while a game is showing.
+    pub fn sync(&mut self) {
+        if let Some(broadcaster) = self.broadcaster.as_mut() {
+            broadcaster.sync(&self.game);
+        }
+    }
+}
+
+pub struct SceneStack {
+    scenes: Vec<Box<dyn Scene>>,
+}
+
+impl SceneStack {
+    /// A stack whose bottom scene, normally the title screen, is `root`.
+    pub fn new(root: Box<dyn Scene>) -> Self {
+        Self { scenes: vec![root] }
+    }
+
+    pub fn push(&mut self, scene: Box<dyn Scene>) {
+        self.scenes.push(scene);
+    }
+
+    /// Runs the top scene until the stack empties, a scene quits or the
+    /// process is told to exit.
+    pub fn run(&mut self, ctx: &mut Context) -> Result<(), Box<dyn Error>> {
+        loop {
+            if signals::terminating() {
+                return self.save_for_exit(ctx);
+            }
+            let Some(scene) = self.scenes.last_mut() else {
+                return Ok(());
+            };
+            let mut transition = Transition::None;
+            let mut suspend = false;
+            if signals::take_resumed() {
+                // Stopped from outside, so nothing was paused beforehand.
+                transition = scene.interrupt(ctx);
+            } else if event::poll(Duration::from_millis(50))? {
+                match event::read()? {
+                    // Raw mode delivers Ctrl-Z as a key rather than a signal.
+                    Event::Key(key)
+                        if key.code == KeyCode::Char('z')
+                            && key.modifiers.contains(KeyModifiers::CONTROL) =>
+                    {
+                        transition = scene.interrupt(ctx);
+                        suspend = true;
+                    }
+                    Event::Key(key) => transition = scene.handle_key(ctx, key),
+                    Event::Mouse(mouse) => transition = scene.handle_mouse(ctx, mouse),
+                    Event::FocusLost => transition = scene.interrupt(ctx),
+                    _ => {}
+                }
+            }
+            if let Transition::None = transition {
+                transition = scene.update(ctx);
+            }
+            if !self.apply(ctx, transition) {
+                return Ok(());
+            }
+
+            if let Some(scene) = self.scenes.last() {
+                scene.draw(ctx);
+            }
+            if suspend {
+                signals::suspend();
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    /// Writes a crash report for the game on screen: the top scene's own
+    /// game if it has one, otherwise the one in `ctx` with its recent
+    /// deltas. Call after catching a panic out of `run`.
+    pub fn write_crash_report(&self, ctx: &Context) -> io::Result<PathBuf> {
+        match self.scenes.last().and_then(|scene| scene.own_game()) {
+            Some(game) => crash::write_report(&ctx.config.home, &game, &VecDeque::new()),
+            None => crash::write_report(&ctx.config.home, &ctx.game, &ctx.recent),
+        }
+    }
+
+    // Saves a game in progress, paused as if the player had saved it from
+    // the pause menu, so Continue picks it up next time.
+    fn save_for_exit(&mut self, ctx: &mut Context) -> Result<(), Box<dyn Error>> {
+        if !self.scenes.iter().any(|scene| scene.holds_game()) {
+            return Ok(());
+        }
+        if ctx.game.get_state() == GameState::Playing {
+            ctx.game.toggle_pause();
+        }
+        if ctx.game.get_state() == GameState::Paused {
+            save::save(&ctx.config.home, &ctx.game)?;
+        }
+        Ok(())
+    }
+
+    // Returns false once the player has quit.
+    fn apply(&mut self, ctx: &mut Context, transition: Transition) -> bool {
+        match transition {
+            Transition::None => return true,
+            Transition::Push(scene) => {
+                if let Some(below) = self.scenes.last_mut() {
+                    below.suspend(ctx);
+                }
+                self.scenes.push(scene);
+                return true;
+            }
+            Transition::Replace(scene) => {
+                self.scenes.pop();
+                self.scenes.push(scene);
+            }
+            Transition::Pop => {
+                self.scenes.pop();
+            }
+            Transition::Home => self.scenes.truncate(1),
+            Transition::Quit => return false,
+        }
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.resume(ctx);
+        }
+        true
+    }
+}
@@ -0,0 +1,232 @@
+use crate::client::board::Board;
+use crate::client::input::Input;
+use crate::client::rules::RotationSystem;
+use crate::client::tetromino::Tetromino;
+use std::collections::{HashSet, VecDeque};
+
+/// A final resting position for a piece, along with the shortest input
+/// sequence (ending in a hard drop) that reaches it from the spawn position.
+#[derive(Clone)]
+pub struct Placement {
+    pub piece: Tetromino,
+    pub x: i32,
+    pub y: i32,
+    pub inputs: Vec<Input>,
+}
+
+impl Placement {
+    /// Board coordinates covered by the piece, sorted so that placements
+    /// which look identical (e.g. the two flat I orientations) compare equal.
+    pub fn cells(&self) -> Vec<(i32, i32)> {
+        piece_cells(&self.piece, self.x, self.y)
+    }
+}
+
+pub fn piece_cells(piece: &Tetromino, x: i32, y: i32) -> Vec<(i32, i32)> {
+    let mut cells: Vec<(i32, i32)> = piece.minos().map(|(dx, dy)| (x + dx, y + dy)).collect();
+    cells.sort_unstable();
+    cells
+}
+
+/// Applies a movement input to a piece position without a `Game`, using the
+/// same rules as `Game::move_piece` and `Game::rotate` under `rotation`.
+/// Returns `None` if the input has no effect.
+pub fn step(
+    rotation: RotationSystem,
+    board: &Board,
+    piece: &Tetromino,
+    x: i32,
+    y: i32,
+    input: Input,
+) -> Option<(Tetromino, i32, i32)> {
+    let shift = |dx: i32, dy: i32| {
+        (!board.collides(piece, x + dx, y + dy)).then(|| (piece.clone(), x + dx, y + dy))
+    };
+    let slide = |dx: i32| {
+        let mut to_x = x;
+        while !board.collides(piece, to_x + dx, y) {
+            to_x += dx;
+        }
+        (to_x != x).then(|| (piece.clone(), to_x, y))
+    };
+
+    match input {
+        Input::Left => shift(-1, 0),
+        Input::Right => shift(1, 0),
+        Input::SoftDrop => shift(0, 1),
+        Input::DasLeft => slide(-1),
+        Input::DasRight => slide(1),
+        Input::RotateCw | Input::RotateCcw => rotation
+            .rotate(board, piece, x, y, input == Input::RotateCw)
+            .map(|(rotated, x, y, _)| (rotated, x, y)),
+        Input::Hold | Input::HardDrop => None,
+    }
+}
+
+/// Row the piece comes to rest on if hard dropped from (`x`, `y`).
+pub fn drop_y(board: &Board, piece: &Tetromino, x: i32, y: i32) -> i32 {
+    let mut landing_y = y;
+    while !board.collides(piece, x, landing_y + 1) {
+        landing_y += 1;
+    }
+    landing_y
+}
+
+/// Breadth-first search over every position the piece can reach from
+/// (`x`, `y`). Each distinct final placement is returned once with its
+/// shortest input sequence. With `allow_soft_drop` off only placements
+/// reachable by shifting and rotating above the stack are considered,
+/// which is what finesse is measured against.
+pub fn find_placements(
+    rotation: RotationSystem,
+    board: &Board,
+    piece: &Tetromino,
+    x: i32,
+    y: i32,
+    allow_soft_drop: bool,
+) -> Vec<Placement> {
+    let mut inputs = vec![
+        Input::Left,
+        Input::Right,
+        Input::DasLeft,
+        Input::DasRight,
+        Input::RotateCw,
+        Input::RotateCcw,
+    ];
+    if allow_soft_drop {
+        inputs.push(Input::SoftDrop);
+    }
+
+    let mut placements = Vec::new();
+    let mut seen_positions = HashSet::new();
+    let mut seen_cells = HashSet::new();
+    let mut queue = VecDeque::new();
+
+    if board.collides(piece, x, y) {
+        return placements;
+    }
+    seen_positions.insert((x, y, piece.rotation));
+    queue.push_back((piece.clone(), x, y, Vec::new()));
+
+    while let Some((current, cx, cy, path)) = queue.pop_front() {
+        let landing_y = drop_y(board, &current, cx, cy);
+        let mut final_inputs = path.clone();
+        final_inputs.push(Input::HardDrop);
+        let placement = Placement {
+            piece: current.clone(),
+            x: cx,
+            y: landing_y,
+            inputs: final_inputs,
+        };
+        if seen_cells.insert(placement.cells()) {
+            placements.push(placement);
+        }
+
+        for &input in &inputs {
+            if let Some((next, nx, ny)) = step(rotation, board, &current, cx, cy, input) {
+                if seen_positions.insert((nx, ny, next.rotation)) {
+                    let mut next_path = path.clone();
+                    next_path.push(input);
+                    queue.push_back((next, nx, ny, next_path));
+                }
+            }
+        }
+    }
+
+    placements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::board::{DEFAULT_HEIGHT, DEFAULT_WIDTH};
+    use crate::client::rules;
+    use crate::client::tetromino::TetrominoType;
+
+    // Keypresses, hard drop left out, to drop each of the first `rotations`
+    // orientations of `kind` at every column of an empty board, by leftmost
+    // column.
+    fn presses(kind: TetrominoType, rotations: u8) -> Vec<Vec<usize>> {
+        let board = Board::with_size(DEFAULT_WIDTH, DEFAULT_HEIGHT);
+        let spawned = Tetromino::new(kind);
+        let (x, y) = rules::spawn_position(&board, &spawned);
+        let placements = find_placements(RotationSystem::Srs, &board, &spawned, x, y, false);
+        let mut piece = spawned.clone();
+        (0..rotations)
+            .map(|_| {
+                let left = piece.minos().map(|(dx, _)| dx).min().unwrap();
+                let columns = (-left..board.width() as i32)
+                    .filter(|&x| !board.collides(&piece, x, y))
+                    .map(|x| {
+                        let cells = piece_cells(&piece, x, drop_y(&board, &piece, x, y));
+                        let placement = placements
+                            .iter()
+                            .find(|placement| placement.cells() == cells)
+                            .expect("every column is reachable");
+                        placement.inputs.len() - 1
+                    })
+                    .collect();
+                piece.rotate_clockwise();
+                columns
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_shortest_sequences_use_das_for_the_outer_columns() {
+        use TetrominoType::*;
+        assert_eq!(presses(O, 1), [vec![1, 2, 2, 1, 0, 1, 2, 2, 1]]);
+        assert_eq!(
+            presses(I, 2),
+            [
+                vec![1, 2, 1, 0, 1, 2, 1],
+                vec![2, 2, 2, 2, 1, 1, 2, 2, 2, 2]
+            ]
+        );
+        for kind in [S, Z] {
+            assert_eq!(
+                presses(kind, 2),
+                [
+                    vec![1, 2, 1, 0, 1, 2, 2, 1],
+                    vec![2, 2, 2, 1, 1, 2, 3, 2, 2]
+                ],
+                "{:?}",
+                kind
+            );
+        }
+        // Without a 180 rotation, pointing the flat side up takes two.
+        for kind in [T, J, L] {
+            assert_eq!(
+                presses(kind, 4),
+                [
+                    vec![1, 2, 1, 0, 1, 2, 2, 1],
+                    vec![2, 2, 3, 2, 1, 2, 3, 3, 2],
+                    vec![3, 4, 3, 2, 3, 4, 4, 3],
+                    vec![2, 3, 2, 1, 2, 3, 3, 2, 2]
+                ],
+                "{:?}",
+                kind
+            );
+        }
+    }
+
+    #[test]
+    fn every_sequence_reaches_its_placement() {
+        let board = Board::with_size(DEFAULT_WIDTH, DEFAULT_HEIGHT);
+        for kind in TetrominoType::ALL {
+            let spawned = Tetromino::new(kind);
+            let (x, y) = rules::spawn_position(&board, &spawned);
+            for placement in find_placements(RotationSystem::Srs, &board, &spawned, x, y, false) {
+                let (mut piece, mut px, mut py) = (spawned.clone(), x, y);
+                let (last, moves) = placement.inputs.split_last().unwrap();
+                assert_eq!(*last, Input::HardDrop);
+                for &input in moves {
+                    (piece, px, py) = step(RotationSystem::Srs, &board, &piece, px, py, input)
+                        .expect("every input moves the piece");
+                }
+                let landed = piece_cells(&piece, px, drop_y(&board, &piece, px, py));
+                assert_eq!(landed, placement.cells());
+            }
+        }
+    }
+}
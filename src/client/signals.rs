@@ -0,0 +1,80 @@
+//! Signals the full-screen interface has to answer. Stopping the game
+//! (Ctrl-Z or `kill -TSTP`) hands the shell a normal terminal until it is
+//! continued; `SIGTERM` and `SIGHUP` give the scene stack a moment to save
+//! the game before the process exits.
+//!
+//! The signals are handled on a thread of their own, so they are answered
+//! even while the game is blocked waiting for a key.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set once the process has been asked to exit.
+static TERMINATING: AtomicBool = AtomicBool::new(false);
+
+/// Set when the process continues after being stopped, until taken.
+static RESUMED: AtomicBool = AtomicBool::new(false);
+
+/// Starts handling the signals. Call once the terminal has been taken over.
+#[cfg(unix)]
+pub fn install() -> io::Result<()> {
+    use crate::client::terminal;
+    use signal_hook::consts::{SIGCONT, SIGHUP, SIGTERM, SIGTSTP};
+    use signal_hook::iterator::Signals;
+    use signal_hook::low_level;
+    use std::time::Duration;
+
+    // How long the main thread gets to save and restore the terminal
+    // itself before the process exits regardless.
+    const EXIT_GRACE: Duration = Duration::from_secs(1);
+
+    let mut signals = Signals::new([SIGTSTP, SIGCONT, SIGTERM, SIGHUP])?;
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            match signal {
+                SIGTSTP => {
+                    terminal::restore();
+                    // Stops the process; returns once it is continued.
+                    let _ = low_level::emulate_default_handler(SIGTSTP);
+                    let _ = terminal::take_over();
+                    RESUMED.store(true, Ordering::SeqCst);
+                }
+                // Stopped by SIGSTOP, which cannot be caught, so the
+                // terminal was never given back.
+                SIGCONT => {
+                    let _ = terminal::take_over();
+                    RESUMED.store(true, Ordering::SeqCst);
+                }
+                _ => {
+                    TERMINATING.store(true, Ordering::SeqCst);
+                    std::thread::sleep(EXIT_GRACE);
+                    terminal::restore();
+                    low_level::exit(128 + signal);
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn install() -> io::Result<()> {
+    Ok(())
+}
+
+/// Stops the process as Ctrl-Z would, for when raw mode swallowed the key
+/// instead of the terminal sending `SIGTSTP`.
+pub fn suspend() {
+    #[cfg(unix)]
+    let _ = signal_hook::low_level::raise(signal_hook::consts::SIGTSTP);
+}
+
+/// Whether the process has been asked to exit.
+pub fn terminating() -> bool {
+    TERMINATING.load(Ordering::SeqCst)
+}
+
+/// Whether the process has been stopped and continued since the last call.
+pub fn take_resumed() -> bool {
+    RESUMED.swap(false, Ordering::SeqCst)
+}
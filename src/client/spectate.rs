@@ -0,0 +1,600 @@
+//! Read-only live view of a running game. The player's instance publishes a
+//! stream of events on a local socket; spectators receive a full snapshot
+//! when they connect and then replay the inputs, gravity ticks, delay
+//! frames and garbage that follow, which keeps their copy of the `Game` in
+//! step because the engine is deterministic.
+//!
+//! The same stream, with the time of each event, can be written to a file
+//! and played back later as a replay.
+
+use crate::client::events::GameEvent;
+use crate::client::game::Game;
+use crate::client::input::Input;
+use crate::client::layout::{Area, Glyphs};
+use crate::client::menu::{MessageScene, PromptScene};
+use crate::client::net;
+use crate::client::scene::{Context, Scene, Transition};
+use crate::client::tetromino::TetrominoType;
+use crossterm::event::{KeyCode, KeyEvent};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_SPECTATE_PORT: u16 = 7879;
+
+/// Events queued for a spectator before it counts as stalled and is
+/// dropped.
+const SPECTATOR_QUEUE: usize = 1024;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SpectateEvent {
+    /// The full game. Sent first to every spectator, and again whenever the
+    /// game changes outside the replayed events (menus, pausing, restarts).
+    Snapshot {
+        game: Box<Game>,
+    },
+    Input {
+        input: Input,
+    },
+    Tick,
+    /// One engine frame of a line clear or entry delay.
+    Frame,
+    Garbage {
+        lines: u32,
+    },
+    /// Where the active piece locked. Derived from the events above, for
+    /// observers that do not run the engine themselves.
+    Lock {
+        piece: TetrominoType,
+        x: i32,
+        y: i32,
+        rotation: u8,
+    },
+    Clear {
+        lines: usize,
+        kind: String,
+    },
+    Spawn {
+        piece: TetrominoType,
+    },
+}
+
+/// Something that was just applied to the published game.
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Delta {
+    Input(Input),
+    Tick,
+    /// An engine frame that advanced a delay (`Game::frame` returned true).
+    Frame,
+    Garbage(u32),
+}
+
+/// One line of a replay file: an event and when it was sent, in
+/// milliseconds since recording started.
+#[derive(Serialize, Deserialize)]
+struct Timed<E> {
+    ms: u64,
+    event: E,
+}
+
+/// A connected spectator. Its events are written from a thread of its own,
+/// so a spectator that stops reading never holds up the game.
+struct Feed {
+    stream: TcpStream,
+    lines: SyncSender<Arc<str>>,
+}
+
+impl Feed {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(false)?;
+        let mut writer = stream.try_clone()?;
+        let (lines, queue) = mpsc::sync_channel::<Arc<str>>(SPECTATOR_QUEUE);
+        thread::spawn(move || {
+            for line in queue {
+                if writeln!(writer, "{}", line)
+                    .and_then(|()| writer.flush())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            let _ = writer.shutdown(Shutdown::Both);
+        });
+        Ok(Self { stream, lines })
+    }
+
+    /// Queues a line of the stream. Returns false, and closes the connection,
+    /// if the spectator has left or fallen too far behind.
+    fn send(&self, line: &Arc<str>) -> bool {
+        let sent = self.lines.try_send(Arc::clone(line)).is_ok();
+        if !sent {
+            let _ = self.stream.shutdown(Shutdown::Both);
+        }
+        sent
+    }
+}
+
+/// Publishes a game to any number of spectators, and to a replay file if
+/// recording.
+pub struct Broadcaster {
+    listener: Option<TcpListener>,
+    spectators: Vec<Feed>,
+    /// The replay file and when recording started.
+    recording: Option<(File, Instant)>,
+    /// The game as the spectators currently see it.
+    mirror: Option<Game>,
+}
+
+impl Broadcaster {
+    /// Listens on the loopback interface only.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener: Some(listener),
+            ..Self::offline()
+        })
+    }
+
+    /// A broadcaster that takes no spectators, for recording only.
+    pub fn offline() -> Self {
+        Self {
+            listener: None,
+            spectators: Vec::new(),
+            recording: None,
+            mirror: None,
+        }
+    }
+
+    pub fn port(&self) -> io::Result<u16> {
+        match &self.listener {
+            Some(listener) => Ok(listener.local_addr()?.port()),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "not listening for spectators",
+            )),
+        }
+    }
+
+    /// Also writes the stream to a replay file at `path`, replacing it.
+    pub fn record_to(&mut self, path: &Path) -> io::Result<()> {
+        self.recording = Some((File::create(path)?, Instant::now()));
+        // The replay has to start with a snapshot.
+        self.mirror = None;
+        Ok(())
+    }
+
+    pub fn spectators(&self) -> usize {
+        self.spectators.len()
+    }
+
+    // Whether anyone, or any file, is receiving the stream.
+    fn publishing(&self) -> bool {
+        !self.spectators.is_empty() || self.recording.is_some()
+    }
+
+    /// Streams `delta`, which has just been applied to `game`, along with the
+    /// lock, clear and spawn it caused.
+    pub fn record(&mut self, game: &Game, delta: Delta) {
+        if !self.publishing() {
+            self.mirror = None;
+            return;
+        }
+        let Some(mirror) = self.mirror.as_mut() else {
+            self.sync(game);
+            return;
+        };
+
+        // Only what this delta causes is streamed.
+        mirror.drain_events().for_each(drop);
+        match delta {
+            Delta::Input(input) => mirror.apply_input(input),
+            Delta::Tick => mirror.tick(),
+            Delta::Frame => {
+                mirror.frame();
+            }
+            Delta::Garbage(lines) => mirror.receive_garbage(lines),
+        }
+        if !same_game(mirror, game) {
+            self.send_snapshot(game);
+            return;
+        }
+
+        let mut events = vec![match delta {
+            Delta::Input(input) => SpectateEvent::Input { input },
+            Delta::Tick => SpectateEvent::Tick,
+            Delta::Frame => SpectateEvent::Frame,
+            Delta::Garbage(lines) => SpectateEvent::Garbage { lines },
+        }];
+        events.extend(mirror.drain_events().filter_map(|event| match event {
+            GameEvent::Locked {
+                piece,
+                x,
+                y,
+                rotation,
+            } => Some(SpectateEvent::Lock {
+                piece,
+                x,
+                y,
+                rotation,
+            }),
+            GameEvent::LinesCleared { kind, .. } => Some(SpectateEvent::Clear {
+                lines: kind.lines(),
+                kind: kind.label().to_string(),
+            }),
+            GameEvent::PieceSpawned { piece, .. } => Some(SpectateEvent::Spawn { piece }),
+            _ => None,
+        }));
+        for event in &events {
+            self.broadcast(event);
+        }
+    }
+
+    /// Accepts new spectators and resends the whole game if the stream has
+    /// fallen out of step with it. Call once per frame.
+    pub fn sync(&mut self, game: &Game) {
+        let mut joined = false;
+        while let Some(Ok((stream, _))) = self.listener.as_ref().map(TcpListener::accept) {
+            if let Ok(feed) = Feed::new(stream) {
+                self.spectators.push(feed);
+                joined = true;
+            }
+        }
+        let stale = self
+            .mirror
+            .as_ref()
+            .is_none_or(|mirror| !same_game(mirror, game));
+        if self.publishing() && (joined || stale) {
+            self.send_snapshot(game);
+        }
+    }
+
+    fn send_snapshot(&mut self, game: &Game) {
+        self.broadcast(&SpectateEvent::Snapshot {
+            game: Box::new(game.clone()),
+        });
+        self.mirror = Some(game.clone());
+    }
+
+    fn broadcast(&mut self, event: &SpectateEvent) {
+        if !self.spectators.is_empty() {
+            match serde_json::to_string(event) {
+                Ok(line) => {
+                    let line: Arc<str> = line.into();
+                    self.spectators.retain(|feed| feed.send(&line));
+                }
+                // Spectators cannot follow a stream with an event missing.
+                Err(_) => self.spectators.clear(),
+            }
+        }
+        if let Some((file, start)) = &mut self.recording {
+            let line = Timed {
+                ms: start.elapsed().as_millis() as u64,
+                event,
+            };
+            // A failed write ends the recording rather than the game.
+            if net::write_message(file, &line).is_err() {
+                self.recording = None;
+            }
+        }
+    }
+}
+
+// Whether two games are in the same position. Runs on every delta, so it
+// compares what play changes instead of the whole state, cheapest first.
+fn same_game(a: &Game, b: &Game) -> bool {
+    let (piece, other) = (a.current_piece(), b.current_piece());
+    a.pieces_dealt() == b.pieces_dealt()
+        && a.get_state() == b.get_state()
+        && a.mode() == b.mode()
+        && a.piece_active() == b.piece_active()
+        && a.piece_position() == b.piece_position()
+        && piece.tetromino_type == other.tetromino_type
+        && piece.rotation == other.rotation
+        && a.held_piece() == b.held_piece()
+        && a.score() == b.score()
+        && a.lines() == b.lines()
+        && a.pending_garbage() == b.pending_garbage()
+        && a.board() == b.board()
+}
+
+/// Follows a broadcast game, or plays back a replay, and renders it
+/// read-only.
+pub struct Spectator {
+    events: Receiver<SpectateEvent>,
+    game: Option<Game>,
+    connected: bool,
+    replay: bool,
+}
+
+impl Spectator {
+    pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || {
+            net::read_messages(stream, |event| sender.send(event).is_ok());
+        });
+        Ok(Self {
+            events,
+            game: None,
+            connected: true,
+            replay: false,
+        })
+    }
+
+    /// Plays back the replay file at `path` at the speed it was recorded.
+    pub fn replay(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || {
+            let start = Instant::now();
+            net::read_messages(file, |line: Timed<SpectateEvent>| {
+                let due = Duration::from_millis(line.ms);
+                if let Some(wait) = due.checked_sub(start.elapsed()) {
+                    thread::sleep(wait);
+                }
+                sender.send(line.event).is_ok()
+            });
+        });
+        Ok(Self {
+            events,
+            game: None,
+            connected: true,
+            replay: true,
+        })
+    }
+
+    pub fn game(&self) -> Option<&Game> {
+        self.game.as_ref()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Applies everything received since the last call.
+    pub fn update(&mut self) {
+        loop {
+            let event = match self.events.try_recv() {
+                Ok(event) => event,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    self.connected = false;
+                    return;
+                }
+            };
+            apply(&mut self.game, event);
+        }
+    }
+
+    pub fn draw(&self, glyphs: Option<Glyphs>) {
+        let area = Area::terminal();
+        let message = match (&self.game, self.connected) {
+            (Some(game), true) => {
+                game.draw(glyphs);
+                if self.replay {
+                    "Replay - Esc: leave"
+                } else {
+                    "Spectating - Esc: leave"
+                }
+            }
+            (_, false) if self.replay => {
+                print!("\x1B[2J");
+                "The replay has ended. Press Esc to leave"
+            }
+            (_, false) => {
+                print!("\x1B[2J");
+                "The game has ended. Press Esc to leave"
+            }
+            (None, true) => {
+                print!("\x1B[2J");
+                "Waiting for the game..."
+            }
+        };
+        area.centre(area.bottom() - 1, message);
+    }
+}
+
+/// Asks for the address of a broadcast game, then watches it.
+pub fn watch() -> Box<dyn Scene> {
+    let default = format!("127.0.0.1:{}", DEFAULT_SPECTATE_PORT);
+    Box::new(PromptScene::new(
+        "Address of the game to watch:",
+        default,
+        |_ctx, address| match Spectator::connect(address.trim()) {
+            Ok(spectator) => Transition::Replace(Box::new(WatchScene::new(spectator))),
+            Err(err) => Transition::Replace(Box::new(MessageScene::new(format!(
+                "Connection failed: {}",
+                err
+            )))),
+        },
+    ))
+}
+
+/// Follows a spectated game or replay until Esc is pressed.
+pub struct WatchScene {
+    spectator: Spectator,
+}
+
+impl WatchScene {
+    pub fn new(spectator: Spectator) -> Self {
+        Self { spectator }
+    }
+}
+
+impl Scene for WatchScene {
+    fn handle_key(&mut self, _ctx: &mut Context, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Esc => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+
+    fn update(&mut self, _ctx: &mut Context) -> Transition {
+        self.spectator.update();
+        Transition::None
+    }
+
+    fn own_game(&self) -> Option<Game> {
+        self.spectator.game().cloned()
+    }
+
+    fn draw(&self, ctx: &Context) {
+        self.spectator.draw(ctx.config.glyphs);
+    }
+}
+
+/// Plays the replay file at `path` straight through, without waiting or
+/// drawing, and returns the game as it ends.
+pub fn replay_to_end(path: &Path) -> io::Result<Option<Game>> {
+    let mut game = None;
+    net::read_messages(File::open(path)?, |line: Timed<SpectateEvent>| {
+        apply(&mut game, line.event);
+        true
+    });
+    Ok(game)
+}
+
+// Applies one event of the stream to a follower's copy of the game.
+fn apply(game: &mut Option<Game>, event: SpectateEvent) {
+    if let SpectateEvent::Snapshot { game: snapshot } = event {
+        *game = Some(*snapshot);
+        return;
+    }
+    // Deltas before the first snapshot have nothing to apply to.
+    let Some(game) = game.as_mut() else {
+        return;
+    };
+    match event {
+        SpectateEvent::Input { input } => game.apply_input(input),
+        SpectateEvent::Tick => game.tick(),
+        SpectateEvent::Frame => {
+            game.frame();
+        }
+        SpectateEvent::Garbage { lines } => game.receive_garbage(lines),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::game::GameMode;
+    use crate::client::rules::RuleSet;
+
+    #[test]
+    fn a_stalled_spectator_is_dropped_without_holding_up_the_game() {
+        let mut broadcaster = Broadcaster::bind(0).unwrap();
+        let port = broadcaster.port().unwrap();
+        let mut game = Game::with_rules(1, RuleSet::default());
+        game.start(GameMode::Marathon);
+
+        // Connects but never reads.
+        let _stalled = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        while broadcaster.spectators() == 0 {
+            broadcaster.sync(&game);
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // Changes the game behind the broadcaster's back, so every sync
+        // sends a full snapshot, until the spectator's queue overflows.
+        let started = Instant::now();
+        for i in 0..SPECTATOR_QUEUE * 2 {
+            game.apply_input(if i % 2 == 0 {
+                Input::Left
+            } else {
+                Input::Right
+            });
+            broadcaster.sync(&game);
+            if broadcaster.spectators() == 0 {
+                break;
+            }
+            assert!(started.elapsed() < Duration::from_secs(5));
+        }
+        assert_eq!(broadcaster.spectators(), 0);
+    }
+
+    // Applies `delta` to `game` and publishes it, then runs out any delay it
+    // started a frame at a time.
+    fn play(broadcaster: &mut Broadcaster, game: &mut Game, delta: Delta) {
+        match delta {
+            Delta::Input(input) => game.apply_input(input),
+            Delta::Tick => game.tick(),
+            Delta::Frame => {
+                game.frame();
+            }
+            Delta::Garbage(lines) => game.receive_garbage(lines),
+        }
+        broadcaster.record(game, delta);
+        while game.frame() {
+            broadcaster.record(game, Delta::Frame);
+        }
+    }
+
+    #[test]
+    fn a_spectator_joining_mid_game_catches_up() {
+        let mut broadcaster = Broadcaster::bind(0).unwrap();
+        let port = broadcaster.port().unwrap();
+        let mut game = Game::with_rules(1, RuleSet::default());
+        game.start(GameMode::Marathon);
+
+        let mut early = Spectator::connect(("127.0.0.1", port)).unwrap();
+        while broadcaster.spectators() < 1 {
+            broadcaster.sync(&game);
+            thread::sleep(Duration::from_millis(10));
+        }
+        for input in [
+            Input::DasLeft,
+            Input::HardDrop,
+            Input::RotateCw,
+            Input::HardDrop,
+        ] {
+            play(&mut broadcaster, &mut game, Delta::Input(input));
+        }
+
+        // Reads the raw stream, to see what the late spectator is sent.
+        let late = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || {
+            net::read_messages(late, |event: SpectateEvent| sender.send(event).is_ok());
+        });
+        while broadcaster.spectators() < 2 {
+            broadcaster.sync(&game);
+            thread::sleep(Duration::from_millis(10));
+        }
+        play(&mut broadcaster, &mut game, Delta::Garbage(2));
+        play(&mut broadcaster, &mut game, Delta::Tick);
+        for input in [Input::Hold, Input::DasRight, Input::HardDrop, Input::Left] {
+            play(&mut broadcaster, &mut game, Delta::Input(input));
+        }
+        broadcaster.sync(&game);
+
+        let mut followed = None;
+        let mut snapshots = 0;
+        let started = Instant::now();
+        while !followed.as_ref().is_some_and(|copy| same_game(copy, &game))
+            || !early.game().is_some_and(|copy| same_game(copy, &game))
+        {
+            assert!(started.elapsed() < Duration::from_secs(5), "fell behind");
+            early.update();
+            while let Ok(event) = events.try_recv() {
+                if let SpectateEvent::Snapshot { .. } = event {
+                    snapshots += 1;
+                }
+                apply(&mut followed, event);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        // One snapshot on joining, then only the deltas.
+        assert_eq!(snapshots, 1);
+        assert!(game.pieces_dealt() > 4);
+    }
+}
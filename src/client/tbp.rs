@@ -0,0 +1,861 @@
+//! Frontend side of the Tetris Bot Protocol (TBP), plus a small bot that
+//! speaks it for end-to-end testing.
+//!
+//! Messages are single-line JSON objects exchanged over the bot process's
+//! stdin and stdout. TBP describes pieces by their SRS centre and
+//! orientation with `y` counting up from the floor, so locations are
+//! translated to and from board cells rather than this game's 4x4 boxes.
+
+use crate::client::ai::{self, Plan, Weights};
+use crate::client::attack;
+use crate::client::board::{
+    Board, Cell, BUFFER_HEIGHT, DEFAULT_WIDTH, MAX_HEIGHT, MAX_WIDTH, MIN_HEIGHT, MIN_WIDTH,
+};
+use crate::client::game::Game;
+use crate::client::input::{Controller, Input};
+use crate::client::rules::{self, RotationSystem};
+use crate::client::search::{self, Placement};
+use crate::client::tetromino::{Tetromino, TetrominoType};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+/// Rows in a TBP board, including the area above the visible field.
+pub const TBP_BOARD_HEIGHT: usize = 40;
+
+/// Executable name of the bundled bot, expected next to the game binary.
+pub const DUMMY_BOT_NAME: &str = "tetrust-dummy-bot";
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules {},
+    Start(Start),
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        mv: Move,
+    },
+    NewPiece {
+        piece: TetrominoType,
+    },
+    Stop,
+    Quit,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Info {
+        name: String,
+        version: String,
+        author: String,
+        features: Vec<String>,
+    },
+    Ready,
+    Suggestion {
+        moves: Vec<Move>,
+    },
+    Error {
+        reason: String,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Start {
+    pub hold: Option<TetrominoType>,
+    pub queue: Vec<TetrominoType>,
+    pub combo: u32,
+    pub back_to_back: bool,
+    /// Rows from the floor up, one cell per column, each `null` or a piece
+    /// letter.
+    pub board: Vec<Vec<Option<String>>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Move {
+    pub location: PieceLocation,
+    pub spin: Spin,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PieceLocation {
+    #[serde(rename = "type")]
+    pub kind: TetrominoType,
+    pub orientation: Orientation,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+const ORIENTATIONS: [Orientation; 4] = [
+    Orientation::North,
+    Orientation::East,
+    Orientation::South,
+    Orientation::West,
+];
+
+/// SRS mino offsets from the piece centre, `y` pointing up.
+pub fn srs_minos(kind: TetrominoType, orientation: Orientation) -> [(i32, i32); 4] {
+    let north = match kind {
+        TetrominoType::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+        TetrominoType::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        TetrominoType::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+        TetrominoType::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+        TetrominoType::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+        TetrominoType::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+        TetrominoType::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+    };
+    north.map(|(x, y)| match orientation {
+        Orientation::North => (x, y),
+        Orientation::East => (y, -x),
+        Orientation::South => (-x, -y),
+        Orientation::West => (-y, x),
+    })
+}
+
+/// Cells covered by a TBP location on a board `height` rows tall, sorted
+/// like `Placement::cells`.
+pub fn location_cells(location: &PieceLocation, height: usize) -> Vec<(i32, i32)> {
+    let mut cells: Vec<(i32, i32)> = srs_minos(location.kind, location.orientation)
+        .iter()
+        .map(|&(dx, dy)| (location.x + dx, height as i32 - 1 - (location.y + dy)))
+        .collect();
+    cells.sort_unstable();
+    cells
+}
+
+/// Finds the TBP location whose cells match a placement on a board
+/// `height` rows tall.
+pub fn placement_location(placement: &Placement, height: usize) -> Option<PieceLocation> {
+    let cells = placement.cells();
+    let kind = placement.piece.tetromino_type;
+    let (anchor_x, anchor_row) = cells[0];
+    let anchor_y = height as i32 - 1 - anchor_row;
+
+    ORIENTATIONS.iter().find_map(|&orientation| {
+        srs_minos(kind, orientation).iter().find_map(|&(dx, dy)| {
+            let location = PieceLocation {
+                kind,
+                orientation,
+                x: anchor_x - dx,
+                y: anchor_y - dy,
+            };
+            (location_cells(&location, height) == cells).then_some(location)
+        })
+    })
+}
+
+/// Rows from the floor up: at least `TBP_BOARD_HEIGHT`, or the whole board
+/// if it is taller.
+pub fn encode_board(board: &Board) -> Vec<Vec<Option<String>>> {
+    let height = board.height();
+    (0..TBP_BOARD_HEIGHT.max(height))
+        .map(|y| {
+            (0..board.width())
+                .map(|x| {
+                    let filled = y < height && board.get(x, height - 1 - y) == Cell::Filled;
+                    filled.then(|| "G".to_string())
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The board is as wide as the rows, and tall enough that its visible rows
+/// and hidden buffer together hold every row.
+pub fn decode_board(rows: &[Vec<Option<String>>]) -> Board {
+    let width = rows.first().map_or(DEFAULT_WIDTH, Vec::len);
+    let width = width.clamp(MIN_WIDTH, MAX_WIDTH);
+    let visible = rows.len().saturating_sub(BUFFER_HEIGHT);
+    let mut board = Board::with_size(width, visible.clamp(MIN_HEIGHT, MAX_HEIGHT));
+    let height = board.height();
+    for (y, row) in rows.iter().enumerate().take(height) {
+        for (x, cell) in row.iter().enumerate().take(width) {
+            if cell.is_some() {
+                board.set(x, height - 1 - y, Cell::Filled);
+            }
+        }
+    }
+    board
+}
+
+fn start_message(game: &Game) -> FrontendMessage {
+    FrontendMessage::Start(Start {
+        hold: game.held_piece(),
+        queue: vec![
+            game.current_piece().tetromino_type,
+            game.next_piece().tetromino_type,
+        ],
+        combo: game.combo(),
+        back_to_back: game.back_to_back(),
+        board: encode_board(game.board()),
+    })
+}
+
+/// A running bot process. Replies are read on a background thread so the
+/// game loop never blocks on a slow bot.
+pub struct BotProcess {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<BotMessage>,
+}
+
+impl BotProcess {
+    /// Launches `command`, a program followed by its arguments.
+    pub fn spawn(command: &[String]) -> io::Result<Self> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty bot command"))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if let Ok(message) = serde_json::from_str(&line) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            messages,
+        })
+    }
+
+    pub fn send(&mut self, message: &FrontendMessage) -> io::Result<()> {
+        let line = serde_json::to_string(message)?;
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::BrokenPipe => self.gone(),
+                _ => err,
+            })
+    }
+
+    // Describes a bot that can no longer be reached, most likely because
+    // it is exiting.
+    fn gone(&mut self) -> io::Error {
+        if !matches!(self.child.try_wait(), Ok(Some(_))) {
+            thread::sleep(Duration::from_millis(50));
+        }
+        let reason = match self.child.try_wait() {
+            Ok(Some(status)) => format!("bot exited ({})", status),
+            _ => "bot disconnected".to_string(),
+        };
+        io::Error::new(io::ErrorKind::BrokenPipe, reason)
+    }
+
+    /// The next reply if one has arrived, or an error once the bot has
+    /// exited or closed its output.
+    pub fn try_recv(&mut self) -> io::Result<Option<BotMessage>> {
+        match self.messages.try_recv() {
+            Ok(message) => Ok(Some(message)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(self.gone()),
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<BotMessage> {
+        self.messages.recv_timeout(timeout).ok()
+    }
+}
+
+impl Drop for BotProcess {
+    fn drop(&mut self) {
+        let _ = self.send(&FrontendMessage::Quit);
+        if !matches!(self.child.try_wait(), Ok(Some(_))) {
+            thread::sleep(Duration::from_millis(50));
+            let _ = self.child.kill();
+        }
+        let _ = self.child.wait();
+    }
+}
+
+/// The bundled dummy bot installed alongside this executable, which "Watch
+/// Bot" runs unless the `bot` setting names another.
+pub fn default_bot_command() -> Vec<String> {
+    let program = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(DUMMY_BOT_NAME)))
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| DUMMY_BOT_NAME.to_string());
+    vec![program]
+}
+
+/// Splits the `bot` setting into a program and its arguments as a shell
+/// would: on whitespace, except within single or double quotes or after a
+/// backslash. Outside quotes a backslash only escapes whitespace, quotes
+/// and itself, so Windows paths can be written as they are.
+pub fn split_command(command: &str) -> Result<Vec<String>, String> {
+    let unterminated = || format!("unterminated quote in bot command {:?}", command);
+    let mut words = Vec::new();
+    // `None` between words, so that `''` still makes an empty argument.
+    let mut word: Option<String> = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\\' => {
+                let word = word.get_or_insert_with(String::new);
+                match chars.next_if(|&next| next.is_whitespace() || "'\"\\".contains(next)) {
+                    Some(escaped) => word.push(escaped),
+                    None => word.push('\\'),
+                }
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next().ok_or_else(unterminated)? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next().ok_or_else(unterminated)? {
+                        '"' => break,
+                        '\\' => match chars.next_if(|&next| next == '"' || next == '\\') {
+                            Some(escaped) => word.push(escaped),
+                            None => word.push('\\'),
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Drives a `Game` from an external TBP bot's suggestions.
+pub struct TbpBot {
+    process: BotProcess,
+    pub name: String,
+    started: bool,
+    awaiting_suggestion: bool,
+    pieces_dealt: u64,
+    /// The move the bot was last told was played, and the inputs left to
+    /// play it.
+    playing: Option<Move>,
+    plan: Plan,
+    /// Set once the bot has failed; it plays no further.
+    error: Option<String>,
+}
+
+impl TbpBot {
+    /// Starts the bot `command`, a program followed by its arguments, and
+    /// performs the info/rules/ready handshake.
+    pub fn launch(command: &[String]) -> io::Result<Self> {
+        let mut process = BotProcess::spawn(command)?;
+        let timed_out = || io::Error::new(io::ErrorKind::TimedOut, "bot did not respond");
+
+        let name = match process.recv_timeout(HANDSHAKE_TIMEOUT) {
+            Some(BotMessage::Info { name, version, .. }) => format!("{} {}", name, version),
+            _ => return Err(timed_out()),
+        };
+        process.send(&FrontendMessage::Rules {})?;
+        match process.recv_timeout(HANDSHAKE_TIMEOUT) {
+            Some(BotMessage::Ready) => {}
+            Some(BotMessage::Error { reason }) => {
+                return Err(io::Error::new(io::ErrorKind::Unsupported, reason))
+            }
+            _ => return Err(timed_out()),
+        }
+
+        Ok(Self {
+            process,
+            name,
+            started: false,
+            awaiting_suggestion: false,
+            pieces_dealt: 0,
+            playing: None,
+            plan: Plan::default(),
+            error: None,
+        })
+    }
+
+    // Finds a new way to the move being played after gravity moved the
+    // piece off the plan. If the piece locked or the move is now out of
+    // reach, the bot's board no longer matches and it is restarted.
+    fn replan(&mut self, game: &Game) -> io::Result<()> {
+        let inputs = self
+            .playing
+            .as_ref()
+            .filter(|_| game.pieces_dealt() == self.pieces_dealt)
+            .and_then(|mv| self.plan_move(game, mv));
+        match inputs {
+            Some(inputs) => self.plan = Plan::new(game, inputs),
+            None => {
+                if self.started {
+                    self.process.send(&FrontendMessage::Stop)?;
+                }
+                self.started = false;
+                self.playing = None;
+                self.plan.clear();
+            }
+        }
+        Ok(())
+    }
+
+    // Keeps the bot's view of the queue in step with the game, restarting
+    // it from a fresh snapshot if more than one piece went by unseen.
+    fn sync(&mut self, game: &Game) -> io::Result<()> {
+        let dealt = game.pieces_dealt();
+        if self.started && dealt == self.pieces_dealt + 1 {
+            self.process.send(&FrontendMessage::NewPiece {
+                piece: game.next_piece().tetromino_type,
+            })?;
+        } else if !self.started || dealt != self.pieces_dealt {
+            if self.started {
+                self.process.send(&FrontendMessage::Stop)?;
+            }
+            self.process.send(&start_message(game))?;
+            self.started = true;
+            self.awaiting_suggestion = false;
+            self.playing = None;
+            self.plan.clear();
+        }
+        self.pieces_dealt = dealt;
+        Ok(())
+    }
+
+    // Matches a suggested location against the placements this game can
+    // actually reach, so bots cannot teleport pieces.
+    fn plan_move(&self, game: &Game, mv: &Move) -> Option<Vec<Input>> {
+        let target = location_cells(&mv.location, game.board().height());
+        ai::generate_moves(game)
+            .into_iter()
+            .find(|placement| {
+                placement.piece.tetromino_type == mv.location.kind && placement.cells() == target
+            })
+            .map(|placement| placement.inputs)
+    }
+
+    // Plays the next input of the current move, asking the bot for a new
+    // one once the last is done.
+    fn step(&mut self, game: &Game) -> io::Result<Option<Input>> {
+        if !self.plan.is_empty() && !self.plan.is_current(game) {
+            self.replan(game)?;
+        }
+        self.sync(game)?;
+
+        if self.plan.is_empty() {
+            if !self.awaiting_suggestion {
+                self.process.send(&FrontendMessage::Suggest)?;
+                self.awaiting_suggestion = true;
+            }
+            let moves = match self.process.try_recv()? {
+                Some(BotMessage::Suggestion { moves }) => moves,
+                Some(BotMessage::Error { reason }) => {
+                    return Err(io::Error::other(format!("bot error: {}", reason)))
+                }
+                _ => return Ok(None),
+            };
+            self.awaiting_suggestion = false;
+
+            let chosen = moves
+                .iter()
+                .find_map(|mv| Some((mv.clone(), self.plan_move(game, mv)?)));
+            match chosen {
+                Some((mv, inputs)) => {
+                    self.process
+                        .send(&FrontendMessage::Play { mv: mv.clone() })?;
+                    self.playing = Some(mv);
+                    self.plan = Plan::new(game, inputs);
+                }
+                None => {
+                    // Nothing usable was suggested; drop the piece and resync.
+                    self.process.send(&FrontendMessage::Stop)?;
+                    self.started = false;
+                    self.playing = None;
+                    self.plan = Plan::new(game, vec![Input::HardDrop]);
+                }
+            }
+        }
+
+        Ok(self.plan.next(game))
+    }
+}
+
+impl Controller for TbpBot {
+    fn next_input(&mut self, game: &Game) -> Option<Input> {
+        if self.error.is_some() {
+            return None;
+        }
+        self.step(game).unwrap_or_else(|err| {
+            self.error = Some(err.to_string());
+            None
+        })
+    }
+
+    fn reset(&mut self) {
+        if self.started {
+            let _ = self.process.send(&FrontendMessage::Stop);
+        }
+        self.started = false;
+        self.awaiting_suggestion = false;
+        self.playing = None;
+        self.plan.clear();
+    }
+
+    fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+// The spin a `Game` would score `placement` as: only a T piece whose last
+// input rotated it into the place it locks in can spin. The inputs are
+// replayed from the piece's spawn position to find where that rotation
+// left it.
+fn placement_spin(board: &Board, placement: &Placement) -> Spin {
+    let mut piece = Tetromino::new(placement.piece.tetromino_type);
+    let (mut x, mut y) = rules::spawn_position(board, &piece);
+    let mut rotated = false;
+    for &input in &placement.inputs {
+        if let Some(moved) = search::step(RotationSystem::Srs, board, &piece, x, y, input) {
+            (piece, x, y) = moved;
+            rotated = matches!(input, Input::RotateCw | Input::RotateCcw);
+        }
+    }
+    if !rotated || (x, y) != (placement.x, placement.y) {
+        return Spin::None;
+    }
+    match attack::detect_spin(board, &piece, x, y) {
+        attack::Spin::None => Spin::None,
+        attack::Spin::Mini => Spin::Mini,
+        attack::Spin::Full => Spin::Full,
+    }
+}
+
+/// Bot side of the protocol, backed by the built-in heuristic AI. Used by
+/// the bundled `tetrust-dummy-bot` binary.
+pub struct DummyBot {
+    weights: Weights,
+    board: Board,
+    queue: VecDeque<TetrominoType>,
+    hold: Option<TetrominoType>,
+}
+
+impl Default for DummyBot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DummyBot {
+    pub fn new() -> Self {
+        Self {
+            weights: Weights::default(),
+            board: Board::new(),
+            queue: VecDeque::new(),
+            hold: None,
+        }
+    }
+
+    pub fn info() -> BotMessage {
+        BotMessage::Info {
+            name: "TetRust Dummy Bot".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            author: "TetRust".to_string(),
+            features: Vec::new(),
+        }
+    }
+
+    /// Handles one frontend message, returning the reply if one is due.
+    pub fn handle(&mut self, message: FrontendMessage) -> Option<BotMessage> {
+        match message {
+            FrontendMessage::Rules {} => Some(BotMessage::Ready),
+            FrontendMessage::Start(start) => {
+                self.board = decode_board(&start.board);
+                self.queue = start.queue.into();
+                self.hold = start.hold;
+                None
+            }
+            FrontendMessage::Suggest => Some(BotMessage::Suggestion {
+                moves: self.suggest().into_iter().collect(),
+            }),
+            FrontendMessage::Play { mv } => {
+                self.play(&mv);
+                None
+            }
+            FrontendMessage::NewPiece { piece } => {
+                self.queue.push_back(piece);
+                None
+            }
+            FrontendMessage::Stop | FrontendMessage::Quit => {
+                self.queue.clear();
+                None
+            }
+        }
+    }
+
+    fn suggest(&self) -> Option<Move> {
+        let current = Tetromino::new(*self.queue.front()?);
+        let swapped = self.hold.or_else(|| self.queue.get(1).copied());
+        // Frontends speaking the protocol play by SRS.
+        let placements = ai::generate_placements(
+            RotationSystem::Srs,
+            &self.board,
+            &current,
+            rules::spawn_position(&self.board, &current),
+            swapped.map(Tetromino::new).as_ref(),
+        );
+        let placement = ai::choose(&self.board, placements, &self.weights)?;
+        Some(Move {
+            location: placement_location(&placement, self.board.height())?,
+            spin: placement_spin(&self.board, &placement),
+        })
+    }
+
+    fn play(&mut self, mv: &Move) {
+        let Some(current) = self.queue.pop_front() else {
+            return;
+        };
+        if mv.location.kind != current {
+            // The move used hold: an empty hold also consumes the next piece.
+            if self.hold.is_none() {
+                self.queue.pop_front();
+            }
+            self.hold = Some(current);
+        }
+
+        for (x, y) in location_cells(&mv.location, self.board.height()) {
+            if self.board.contains(x, y) {
+                self.board.set(x as usize, y as usize, Cell::Filled);
+            }
+        }
+        self.board.clear_full_lines();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::board::DEFAULT_HEIGHT;
+    use crate::client::game::GameMode;
+
+    fn words(command: &str) -> Vec<String> {
+        split_command(command).unwrap()
+    }
+
+    #[test]
+    fn bot_commands_split_like_a_shell() {
+        assert_eq!(words("  bot --fast  1 "), ["bot", "--fast", "1"]);
+        assert_eq!(
+            words("'/opt/my bots/bot' \"two words\" three\\ words"),
+            ["/opt/my bots/bot", "two words", "three words"]
+        );
+        assert_eq!(
+            words(r#"say "a \"quote\"" '' "it's""#),
+            ["say", "a \"quote\"", "", "it's"]
+        );
+        assert_eq!(words(r"C:\Games\bot.exe"), [r"C:\Games\bot.exe"]);
+        assert!(words("").is_empty());
+        assert!(split_command("bot 'open").is_err());
+        assert!(split_command("bot \"open").is_err());
+    }
+
+    fn sorted(mut cells: [(i32, i32); 4]) -> [(i32, i32); 4] {
+        cells.sort_unstable();
+        cells
+    }
+
+    #[test]
+    fn srs_minos_turn_clockwise_about_the_centre() {
+        // The T's nub points up, then right, down and left.
+        let nubs: Vec<(i32, i32)> = ORIENTATIONS
+            .iter()
+            .map(|&orientation| {
+                let minos = srs_minos(TetrominoType::T, orientation);
+                *minos
+                    .iter()
+                    .find(|&&(x, y)| (x, y) != (0, 0) && !minos.contains(&(-x, -y)))
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(nubs, [(0, 1), (1, 0), (0, -1), (-1, 0)]);
+        assert_eq!(
+            sorted(srs_minos(TetrominoType::I, Orientation::East)),
+            [(0, -2), (0, -1), (0, 0), (0, 1)]
+        );
+        assert_eq!(
+            sorted(srs_minos(TetrominoType::L, Orientation::South)),
+            [(-1, -1), (-1, 0), (0, 0), (1, 0)]
+        );
+    }
+
+    #[test]
+    fn locations_count_up_from_the_floor() {
+        let location = PieceLocation {
+            kind: TetrominoType::T,
+            orientation: Orientation::North,
+            x: 4,
+            y: 0,
+        };
+        assert_eq!(
+            location_cells(&location, 40),
+            [(3, 39), (4, 38), (4, 39), (5, 39)]
+        );
+        assert_eq!(
+            location_cells(&location, 24),
+            [(3, 23), (4, 22), (4, 23), (5, 23)]
+        );
+    }
+
+    #[test]
+    fn every_placement_translates_to_a_location_and_back() {
+        let board = Board::new();
+        for kind in TetrominoType::ALL {
+            let piece = Tetromino::new(kind);
+            let (x, y) = rules::spawn_position(&board, &piece);
+            let placements =
+                search::find_placements(RotationSystem::Srs, &board, &piece, x, y, false);
+            for placement in placements {
+                let location = placement_location(&placement, board.height()).unwrap();
+                assert_eq!(location_cells(&location, board.height()), placement.cells());
+                // Pieces with no symmetry keep their orientation.
+                if matches!(kind, TetrominoType::T | TetrominoType::L | TetrominoType::J) {
+                    let rotation = placement.piece.rotation as usize;
+                    assert_eq!(location.orientation, ORIENTATIONS[rotation]);
+                }
+                // Everything rests on the floor of an empty board.
+                let lowest = srs_minos(kind, location.orientation)
+                    .iter()
+                    .map(|&(_, dy)| location.y + dy)
+                    .min();
+                assert_eq!(lowest, Some(0));
+            }
+        }
+    }
+
+    #[test]
+    fn boards_are_sent_from_the_floor_up() {
+        let mut board = Board::new();
+        let bottom = board.height() - 1;
+        board.set(0, bottom, Cell::Filled);
+        board.set(9, bottom - 2, Cell::Filled);
+
+        let rows = encode_board(&board);
+        assert_eq!(rows.len(), TBP_BOARD_HEIGHT);
+        assert!(rows.iter().all(|row| row.len() == board.width()));
+        assert_eq!(rows[0][0].as_deref(), Some("G"));
+        assert_eq!(rows[2][9].as_deref(), Some("G"));
+        let filled = rows.iter().flatten().filter(|cell| cell.is_some()).count();
+        assert_eq!(filled, 2);
+        assert!(decode_board(&rows) == board);
+    }
+
+    #[test]
+    fn a_t_rotated_into_a_slot_is_suggested_as_a_spin() {
+        // A T-spin double slot under an overhang at column 3.
+        let mut board = Board::new();
+        let bottom = board.height() - 1;
+        for x in 0..board.width() {
+            if x != 4 {
+                board.set(x, bottom, Cell::Filled);
+            }
+            if !(3..=5).contains(&x) {
+                board.set(x, bottom - 1, Cell::Filled);
+            }
+        }
+        for x in 0..=3 {
+            board.set(x, bottom - 2, Cell::Filled);
+        }
+        let bottom = bottom as i32;
+        let slot = vec![
+            (3, bottom - 1),
+            (4, bottom - 1),
+            (4, bottom),
+            (5, bottom - 1),
+        ];
+
+        let t = Tetromino::new(TetrominoType::T);
+        let placements = ai::generate_placements(
+            RotationSystem::Srs,
+            &board,
+            &t,
+            rules::spawn_position(&board, &t),
+            None,
+        );
+        let spin_of = |cells: &[(i32, i32)]| {
+            let placement = placements
+                .iter()
+                .find(|placement| placement.cells() == cells)
+                .expect("the placement is reachable");
+            placement_spin(&board, placement)
+        };
+        assert_eq!(spin_of(&slot), Spin::Full);
+        // Dropped flat on the right, it is no spin.
+        let flat = vec![
+            (7, bottom - 2),
+            (8, bottom - 3),
+            (8, bottom - 2),
+            (9, bottom - 2),
+        ];
+        assert_eq!(spin_of(&flat), Spin::None);
+    }
+
+    #[test]
+    fn the_start_message_carries_the_combo_and_back_to_back() {
+        let mut game = Game::with_size(1, DEFAULT_WIDTH, DEFAULT_HEIGHT);
+        game.start(GameMode::WatchBot);
+        // Four rows to clear down the left wall, and one to keep.
+        let mut board = game.board().cleared();
+        for y in board.height() - 5..board.height() {
+            for x in 1..board.width() {
+                board.set(x, y, Cell::Filled);
+            }
+        }
+        board.set(1, board.height() - 5, Cell::Empty);
+        game.load_position(board, None, &[TetrominoType::I, TetrominoType::O]);
+        for input in [Input::RotateCw, Input::DasLeft, Input::HardDrop] {
+            game.apply_input(input);
+        }
+        assert_eq!(game.lines(), 4);
+
+        let FrontendMessage::Start(start) = start_message(&game) else {
+            panic!("not a start message");
+        };
+        assert_eq!(start.combo, 1);
+        assert!(start.back_to_back);
+    }
+}
@@ -0,0 +1,57 @@
+//! Taking over the terminal for the full-screen interface and giving it
+//! back. Restoring is idempotent, so the panic hook and the guard can both
+//! do it.
+
+use crossterm::{
+    cursor::{Hide, Show},
+    event::{DisableFocusChange, DisableMouseCapture, EnableFocusChange},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io::{self, stdout};
+
+/// Raw mode, the alternate screen, a hidden cursor and focus events, for
+/// as long as the guard lives. Dropping it, including while unwinding from
+/// a panic, puts the terminal back the way the shell expects it.
+pub struct TerminalGuard {
+    _private: (),
+}
+
+impl TerminalGuard {
+    pub fn enter() -> io::Result<Self> {
+        // Built first, so a failure part way still restores what was done.
+        let guard = Self { _private: () };
+        take_over()?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Sets the terminal up for the game, again after it was restored for a
+/// suspended process.
+pub fn take_over() -> io::Result<()> {
+    // Raw mode is only set if crossterm thinks it is off, but a shell may
+    // have reset the terminal while the process was stopped.
+    disable_raw_mode()?;
+    enable_raw_mode()?;
+    execute!(stdout(), EnterAlternateScreen, Hide, EnableFocusChange)
+}
+
+/// Leaves raw mode and the alternate screen and shows the cursor. Errors
+/// are ignored: this runs when something has already gone wrong or the
+/// process is being stopped.
+pub fn restore() {
+    let _ = execute!(
+        stdout(),
+        DisableMouseCapture,
+        DisableFocusChange,
+        Show,
+        LeaveAlternateScreen
+    );
+    let _ = disable_raw_mode();
+}
@@ -1,4 +1,6 @@
-#[derive(Clone, Copy)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TetrominoType {
     I,
     O,
@@ -9,82 +11,178 @@ pub enum TetrominoType {
     Z,
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Tetromino {
     pub tetromino_type: TetrominoType,
     pub cells: [[bool; 4]; 4],
+    /// Number of clockwise quarter turns from the spawn orientation (0-3).
+    pub rotation: u8,
+}
+
+impl TetrominoType {
+    pub const ALL: [TetrominoType; 7] = [
+        TetrominoType::I,
+        TetrominoType::O,
+        TetrominoType::T,
+        TetrominoType::L,
+        TetrominoType::J,
+        TetrominoType::S,
+        TetrominoType::Z,
+    ];
+
+    /// The piece's usual one-letter name.
+    pub fn letter(self) -> char {
+        match self {
+            TetrominoType::I => 'I',
+            TetrominoType::O => 'O',
+            TetrominoType::T => 'T',
+            TetrominoType::L => 'L',
+            TetrominoType::J => 'J',
+            TetrominoType::S => 'S',
+            TetrominoType::Z => 'Z',
+        }
+    }
+
+    /// The piece named by `c`, in either case.
+    pub fn from_letter(c: char) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|piece| piece.letter() == c.to_ascii_uppercase())
+    }
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Row bitmasks (bit `x` set for column `x` of the 4x4 box) for every piece
+/// type and rotation, computed at compile time from the spawn shapes.
+const ROW_MASKS: [[[u16; 4]; 4]; 7] = {
+    let mut table = [[[0; 4]; 4]; 7];
+    let mut t = 0;
+    while t < 7 {
+        let mut cells = spawn_cells(TetrominoType::ALL[t]);
+        let mut rotation = 0;
+        while rotation < 4 {
+            let mut y = 0;
+            while y < 4 {
+                let mut x = 0;
+                while x < 4 {
+                    if cells[y][x] {
+                        table[t][rotation][y] |= 1 << x;
+                    }
+                    x += 1;
+                }
+                y += 1;
+            }
+            cells = rotated_clockwise(cells);
+            rotation += 1;
+        }
+        t += 1;
+    }
+    table
+};
+
+const fn rotated_clockwise(cells: [[bool; 4]; 4]) -> [[bool; 4]; 4] {
+    let mut new_cells = [[false; 4]; 4];
+    let mut y = 0;
+    while y < 4 {
+        let mut x = 0;
+        while x < 4 {
+            new_cells[x][3 - y] = cells[y][x];
+            x += 1;
+        }
+        y += 1;
+    }
+    new_cells
+}
+
+const fn spawn_cells(tetromino_type: TetrominoType) -> [[bool; 4]; 4] {
+    match tetromino_type {
+        TetrominoType::I => [
+            [false, false, false, false],
+            [true, true, true, true],
+            [false, false, false, false],
+            [false, false, false, false],
+        ],
+        TetrominoType::O => [
+            [false, true, true, false],
+            [false, true, true, false],
+            [false, false, false, false],
+            [false, false, false, false],
+        ],
+        TetrominoType::T => [
+            [false, false, false, false],
+            [false, true, false, false],
+            [true, true, true, false],
+            [false, false, false, false],
+        ],
+        TetrominoType::L => [
+            [false, false, true, false],
+            [true, true, true, false],
+            [false, false, false, false],
+            [false, false, false, false],
+        ],
+        TetrominoType::J => [
+            [true, false, false, false],
+            [true, true, true, false],
+            [false, false, false, false],
+            [false, false, false, false],
+        ],
+        TetrominoType::S => [
+            [false, true, true, false],
+            [true, true, false, false],
+            [false, false, false, false],
+            [false, false, false, false],
+        ],
+        TetrominoType::Z => [
+            [true, true, false, false],
+            [false, true, true, false],
+            [false, false, false, false],
+            [false, false, false, false],
+        ],
+    }
 }
 
 impl Tetromino {
     pub fn new(tetromino_type: TetrominoType) -> Self {
-        let cells = match tetromino_type {
-            TetrominoType::I => [
-                [false, false, false, false],
-                [true, true, true, true],
-                [false, false, false, false],
-                [false, false, false, false],
-            ],
-            TetrominoType::O => [
-                [false, true, true, false],
-                [false, true, true, false],
-                [false, false, false, false],
-                [false, false, false, false],
-            ],
-            TetrominoType::T => [
-                [false, false, false, false],
-                [false, true, false, false],
-                [true, true, true, false],
-                [false, false, false, false],
-            ],
-            TetrominoType::L => [
-                [false, false, true, false],
-                [true, true, true, false],
-                [false, false, false, false],
-                [false, false, false, false],
-            ],
-            TetrominoType::J => [
-                [true, false, false, false],
-                [true, true, true, false],
-                [false, false, false, false],
-                [false, false, false, false],
-            ],
-            TetrominoType::S => [
-                [false, true, true, false],
-                [true, true, false, false],
-                [false, false, false, false],
-                [false, false, false, false],
-            ],
-            TetrominoType::Z => [
-                [true, true, false, false],
-                [false, true, true, false],
-                [false, false, false, false],
-                [false, false, false, false],
-            ],
-        };
+        let cells = spawn_cells(tetromino_type);
 
         Self {
             tetromino_type,
             cells,
+            rotation: 0,
         }
     }
 
     pub fn rotate_clockwise(&mut self) {
-        let mut new_cells = [[false; 4]; 4];
-        for y in 0..4 {
-            for x in 0..4 {
-                new_cells[x][3 - y] = self.cells[y][x];
-            }
-        }
-        self.cells = new_cells;
+        self.cells = rotated_clockwise(self.cells);
+        self.rotation = (self.rotation + 1) % 4;
     }
 
     pub fn rotate_anticlockwise(&mut self) {
         let mut new_cells = [[false; 4]; 4];
-        for y in 0..4 {
-            for x in 0..4 {
-                new_cells[3 - x][y] = self.cells[y][x];
+        for (y, row) in self.cells.iter().enumerate() {
+            for (x, &filled) in row.iter().enumerate() {
+                new_cells[3 - x][y] = filled;
             }
         }
         self.cells = new_cells;
+        self.rotation = (self.rotation + 3) % 4;
+    }
+
+    /// The piece as four row bitmasks, looked up from a precomputed table.
+    pub fn row_masks(&self) -> [u16; 4] {
+        ROW_MASKS[self.tetromino_type.index()][self.rotation as usize]
+    }
+
+    /// Offsets of the four minos relative to the piece's top-left corner.
+    pub fn minos(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.cells.iter().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, &filled)| filled)
+                .map(move |(x, _)| (x as i32, y as i32))
+        })
     }
 }
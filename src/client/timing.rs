@@ -0,0 +1,166 @@
+//! Engine delays between one piece locking and the next appearing: the
+//! line clear animation and the entry delay (ARE). Both are counted in
+//! engine frames rather than wall-clock time, so a game replayed from its
+//! inputs plays out exactly the same.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Engine frames per second.
+pub const FRAME_RATE: u64 = 60;
+
+/// Most gravity ticks `TickClock::due` catches up on at once.
+const MAX_TICKS_DUE: u32 = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Timing {
+    /// Frames the cleared rows stay on screen before they collapse.
+    pub clear_delay: u32,
+    /// Frames after a lock, or after the rows collapse, before the next
+    /// piece spawns.
+    pub are: u32,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            clear_delay: 24,
+            are: 6,
+        }
+    }
+}
+
+impl Timing {
+    /// No delays: rows vanish and the next piece spawns as a piece locks.
+    pub const INSTANT: Timing = Timing {
+        clear_delay: 0,
+        are: 0,
+    };
+
+    /// The `clear_delay` and `are` settings, in frames, where given;
+    /// otherwise the defaults. Set both to 0 to turn the delays off.
+    pub fn from_settings<'a>(setting: impl Fn(&str) -> Option<&'a str>) -> Result<Timing, String> {
+        let mut timing = Timing::default();
+        for (name, value) in [
+            ("clear_delay", &mut timing.clear_delay),
+            ("are", &mut timing.are),
+        ] {
+            if let Some(text) = setting(name) {
+                *value = text
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("{} must be a number of frames", name))?;
+            }
+        }
+        Ok(timing)
+    }
+}
+
+/// Counts engine frames against the wall clock, for loops that run slower
+/// than `FRAME_RATE`.
+pub struct FrameClock {
+    start: Instant,
+    frames: u64,
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            frames: 0,
+        }
+    }
+
+    /// Frames that have come due since the last call.
+    pub fn due(&mut self) -> u64 {
+        let total = self.start.elapsed().as_micros() as u64 * FRAME_RATE / 1_000_000;
+        let due = total - self.frames;
+        self.frames = total;
+        due
+    }
+}
+
+/// Counts gravity ticks against the wall clock, at an interval that may
+/// change from one call to the next as the level goes up.
+pub struct TickClock {
+    last: Instant,
+}
+
+impl Default for TickClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TickClock {
+    pub fn new() -> Self {
+        Self {
+            last: Instant::now(),
+        }
+    }
+
+    /// Ticks that have come due since the last call, `interval` apart.
+    /// After a long stall the clock skips ahead rather than catching up.
+    pub fn due(&mut self, interval: Duration) -> u32 {
+        let mut due = 0;
+        while self.last.elapsed() >= interval {
+            if due == MAX_TICKS_DUE {
+                self.last = Instant::now();
+                break;
+            }
+            self.last += interval;
+            due += 1;
+        }
+        due
+    }
+}
+
+/// Time spent playing a game, which stands still while it is paused or
+/// covered by another screen.
+pub struct PlayClock {
+    played: Duration,
+    /// When the clock last started, if it is running.
+    since: Option<Instant>,
+}
+
+impl Default for PlayClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlayClock {
+    /// A clock at zero, already running.
+    pub fn new() -> Self {
+        Self {
+            played: Duration::ZERO,
+            since: Some(Instant::now()),
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if let Some(since) = self.since.take() {
+            self.played += since.elapsed();
+        }
+    }
+
+    pub fn resume(&mut self) {
+        self.since.get_or_insert_with(Instant::now);
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.played + self.since.map_or(Duration::ZERO, |since| since.elapsed())
+    }
+}
+
+/// Formats a play time as `m:ss.s`.
+pub fn format_time(time: Duration) -> String {
+    let tenths = time.as_millis() / 100;
+    format!("{}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
+}
@@ -0,0 +1,319 @@
+use crate::client::config::Config;
+use crate::client::game::{Game, GameMode, GameState};
+use crate::client::input::Input;
+use crate::client::layout::{Area, Glyphs, TooSmall};
+use crate::client::scene::{Context, Scene, Transition};
+use crate::client::timing::{FrameClock, TickClock};
+use crossterm::event::{KeyCode, KeyEvent};
+use std::time::Duration;
+
+/// Per-player key bindings for sharing one keyboard.
+pub struct Keymap {
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub soft_drop: KeyCode,
+    pub hard_drop: KeyCode,
+    pub rotate_cw: KeyCode,
+    pub rotate_ccw: KeyCode,
+    pub hold: KeyCode,
+}
+
+pub const PLAYER_ONE_KEYS: Keymap = Keymap {
+    left: KeyCode::Char('a'),
+    right: KeyCode::Char('d'),
+    soft_drop: KeyCode::Char('s'),
+    hard_drop: KeyCode::Char('w'),
+    rotate_cw: KeyCode::Char('e'),
+    rotate_ccw: KeyCode::Char('q'),
+    hold: KeyCode::Char('c'),
+};
+
+pub const PLAYER_TWO_KEYS: Keymap = Keymap {
+    left: KeyCode::Left,
+    right: KeyCode::Right,
+    soft_drop: KeyCode::Down,
+    hard_drop: KeyCode::Up,
+    rotate_cw: KeyCode::Char('.'),
+    rotate_ccw: KeyCode::Char(','),
+    hold: KeyCode::Char('/'),
+};
+
+impl Keymap {
+    pub fn input_for(&self, key: KeyCode) -> Option<Input> {
+        [
+            (self.left, Input::Left),
+            (self.right, Input::Right),
+            (self.soft_drop, Input::SoftDrop),
+            (self.hard_drop, Input::HardDrop),
+            (self.rotate_cw, Input::RotateCw),
+            (self.rotate_ccw, Input::RotateCcw),
+            (self.hold, Input::Hold),
+        ]
+        .into_iter()
+        .find(|&(bound, _)| bound == key)
+        .map(|(_, input)| input)
+    }
+
+    fn describe(&self) -> String {
+        let name = |key: KeyCode| match key {
+            KeyCode::Char(c) => c.to_ascii_uppercase().to_string(),
+            KeyCode::Left => "←".to_string(),
+            KeyCode::Right => "→".to_string(),
+            KeyCode::Up => "↑".to_string(),
+            KeyCode::Down => "↓".to_string(),
+            other => format!("{:?}", other),
+        };
+        format!(
+            "{}{} move {} soft {} hard {}{} rotate {} hold",
+            name(self.left),
+            name(self.right),
+            name(self.soft_drop),
+            name(self.hard_drop),
+            name(self.rotate_ccw),
+            name(self.rotate_cw),
+            name(self.hold)
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VersusResult {
+    Winner(usize),
+    Draw,
+}
+
+/// Two games side by side, exchanging garbage through the attack table.
+pub struct Versus {
+    players: [Game; 2],
+    keymaps: [Keymap; 2],
+    result: Option<VersusResult>,
+}
+
+impl Versus {
+    /// Both players receive the same piece sequence from `seed`, on the
+    /// board from `config`.
+    pub fn new(seed: u64, config: &Config) -> Self {
+        let mut players = [
+            Game::with_config(seed, config, GameMode::Versus),
+            Game::with_config(seed, config, GameMode::Versus),
+        ];
+        for player in players.iter_mut() {
+            player.start(GameMode::Versus);
+        }
+        Self {
+            players,
+            keymaps: [PLAYER_ONE_KEYS, PLAYER_TWO_KEYS],
+            result: None,
+        }
+    }
+
+    pub fn players(&self) -> &[Game; 2] {
+        &self.players
+    }
+
+    pub fn result(&self) -> Option<VersusResult> {
+        self.result
+    }
+
+    pub fn handle_key(&mut self, key: KeyCode) {
+        if self.result.is_some() {
+            return;
+        }
+        for (player, keymap) in self.players.iter_mut().zip(&self.keymaps) {
+            if let Some(input) = keymap.input_for(key) {
+                player.apply_input(input);
+            }
+        }
+        self.update();
+    }
+
+    /// Time between gravity ticks. Both boards fall together, at the pace
+    /// of whichever player has reached the higher level.
+    pub fn gravity_interval(&self) -> Duration {
+        self.players
+            .iter()
+            .map(Game::gravity_interval)
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Applies one step of gravity to both boards.
+    pub fn tick(&mut self) {
+        if self.result.is_some() {
+            return;
+        }
+        for player in self.players.iter_mut() {
+            player.tick();
+        }
+        self.update();
+    }
+
+    /// Advances both players' line clear, entry and lock delays by one
+    /// frame.
+    pub fn frame(&mut self) {
+        if self.result.is_some() {
+            return;
+        }
+        for player in self.players.iter_mut() {
+            player.frame();
+        }
+        self.update();
+    }
+
+    // Delivers attacks to the opponent and ends the match on a top out.
+    fn update(&mut self) {
+        let sent = [
+            self.players[0].take_outgoing_attack(),
+            self.players[1].take_outgoing_attack(),
+        ];
+        self.players[1].receive_garbage(sent[0]);
+        self.players[0].receive_garbage(sent[1]);
+
+        let topped_out = self
+            .players
+            .each_ref()
+            .map(|player| player.get_state() == GameState::GameOver);
+        self.result = match topped_out {
+            [true, true] => Some(VersusResult::Draw),
+            [true, false] => Some(VersusResult::Winner(1)),
+            [false, true] => Some(VersusResult::Winner(0)),
+            [false, false] => None,
+        };
+    }
+
+    pub fn draw(&self, glyphs: Option<Glyphs>) {
+        let area = Area::terminal();
+        print!("\x1B[2J");
+
+        // A title row above each board and the message row at the bottom.
+        let players_area = Area {
+            height: area.height - 1,
+            ..area
+        };
+        let mut layouts = Vec::new();
+        for (player, player_area) in self.players.iter().zip(players_area.columns(2)) {
+            match player.layout(player_area.below(1), glyphs) {
+                Ok(layout) => layouts.push(layout),
+                Err(too_small) => {
+                    let need = TooSmall {
+                        width: too_small.width * 2,
+                        height: too_small.height + 2,
+                    };
+                    return need.draw(area);
+                }
+            }
+        }
+
+        let mut bottom = 0;
+        for (i, ((player, keymap), layout)) in self
+            .players
+            .iter()
+            .zip(&self.keymaps)
+            .zip(&layouts)
+            .enumerate()
+        {
+            layout.centre(layout.board_y - 1, &format!("Player {}", i + 1));
+            let status_y = player.draw_playfield(layout);
+            layout.centre(status_y, &keymap.describe());
+            bottom = bottom.max(status_y + 1);
+        }
+
+        let message = match self.result {
+            Some(VersusResult::Winner(winner)) => {
+                format!(
+                    "Player {} wins! Press 'r' for a rematch or Esc to leave",
+                    winner + 1
+                )
+            }
+            Some(VersusResult::Draw) => "Draw! Press 'r' for a rematch or Esc to leave".to_string(),
+            None => "Esc: leave match".to_string(),
+        };
+        area.centre(bottom.min(area.bottom() - 1), &message);
+    }
+}
+
+/// Plays a local match until the players leave, with rematches on 'r'.
+pub struct VersusScene {
+    versus: Versus,
+    ticks: TickClock,
+    frames: FrameClock,
+}
+
+impl VersusScene {
+    /// The first match is dealt from `seed` if given.
+    pub fn new(config: &Config, seed: Option<u64>) -> Self {
+        Self {
+            versus: Versus::new(seed.unwrap_or_else(rand::random), config),
+            ticks: TickClock::new(),
+            frames: FrameClock::new(),
+        }
+    }
+}
+
+impl Scene for VersusScene {
+    fn handle_key(&mut self, ctx: &mut Context, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Esc => return Transition::Pop,
+            KeyCode::Char('r') if self.versus.result().is_some() => {
+                self.versus = Versus::new(rand::random(), &ctx.config);
+            }
+            code => self.versus.handle_key(code),
+        }
+        Transition::None
+    }
+
+    fn update(&mut self, _ctx: &mut Context) -> Transition {
+        for _ in 0..self.ticks.due(self.versus.gravity_interval()) {
+            self.versus.tick();
+        }
+        for _ in 0..self.frames.due() {
+            self.versus.frame();
+        }
+        Transition::None
+    }
+
+    // Player one's game; a report holds a single game.
+    fn own_game(&self) -> Option<Game> {
+        Some(self.versus.players()[0].clone())
+    }
+
+    fn draw(&self, ctx: &Context) {
+        self.versus.draw(ctx.config.glyphs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::tetromino::TetrominoType;
+
+    #[test]
+    fn both_players_open_with_the_seeded_piece() {
+        let firsts: Vec<TetrominoType> = (0..20)
+            .map(|seed| {
+                let versus = Versus::new(seed, &Config::default());
+                let [one, two] = versus.players().each_ref().map(|player| {
+                    (
+                        player.current_piece().tetromino_type,
+                        player.next_piece().tetromino_type,
+                    )
+                });
+                assert_eq!(one, two);
+                one.0
+            })
+            .collect();
+        assert!(firsts.iter().any(|&piece| piece != TetrominoType::I));
+    }
+
+    #[test]
+    fn a_frame_without_attack_emits_no_events() {
+        let mut versus = Versus::new(1, &Config::default());
+        for player in versus.players.iter_mut() {
+            player.drain_events().for_each(drop);
+        }
+        versus.frame();
+        for player in versus.players.iter_mut() {
+            assert_eq!(player.drain_events().count(), 0);
+        }
+    }
+}
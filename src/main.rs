@@ -1,91 +1,173 @@
-mod client;
-
-use crate::client::game::{Game, GameState};
-use crossterm::{
-    cursor::{Hide, Show},
-    event::{read, Event, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
-use std::{io::stdout, thread, time::Duration};
-
-const TICK_RATE: u64 = 500;
+use std::error::Error;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use tetrust::client::bench;
+use tetrust::client::cli::{self, Command, Options, Renderer};
+use tetrust::client::config::Config;
+use tetrust::client::crash;
+use tetrust::client::fumen;
+use tetrust::client::game::{Game, GameMode};
+use tetrust::client::menu::{self, TitleScene};
+use tetrust::client::play::{self, GameScene};
+use tetrust::client::scene::{Context, SceneStack};
+use tetrust::client::signals;
+use tetrust::client::spectate::{self, Broadcaster, Spectator, WatchScene};
+use tetrust::client::terminal::TerminalGuard;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    enable_raw_mode()?;
-    let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen, Hide)?;
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(Command::Version) => {
+            println!("tetrust {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Err(err) => {
+            eprintln!("tetrust: {}\nTry 'tetrust --help' for the options.", err);
+            std::process::exit(2);
+        }
+    };
+    // Report a bad config file, variable or --glyphs here rather than
+    // quietly playing with the defaults.
+    let config = match options.config() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("tetrust: {}", err);
+            std::process::exit(2);
+        }
+    };
+    let game = match initial_game(&options, &config) {
+        Ok(game) => game,
+        Err(err) => {
+            eprintln!("tetrust: {}", err);
+            std::process::exit(2);
+        }
+    };
 
-    let mut game = Game::new();
-    game.spawn_piece();
+    // Benchmarks and headless replays never take over the terminal.
+    if let Some(games) = options.bench {
+        return run_bench(&options, &config, games);
+    }
+    if let (Some(path), Renderer::Headless) = (&options.replay, options.renderer) {
+        return print_replay(path);
+    }
 
-    let mut last_tick = std::time::Instant::now();
+    crash::install_hook();
+    let _terminal = TerminalGuard::enter()?;
+    signals::install()?;
+    match &options.replay {
+        Some(path) => run_replay(path, config),
+        None => run(game, &options, config),
+    }
+}
 
-    loop {
-        if crossterm::event::poll(Duration::from_millis(50))? {
-            match read()? {
-                Event::Key(key_event) => match &game.get_state() {
-                    GameState::TitleScreen { .. } => {
-                        game.handle_title_input(key_event.code);
-                    }
-                    GameState::Playing => match key_event.code {
-                        KeyCode::Left => {
-                            game.move_piece(-1, 0);
-                        }
-                        KeyCode::Right => {
-                            game.move_piece(1, 0);
-                        }
-                        KeyCode::Down => {
-                            game.move_piece(0, 1);
-                        }
-                        KeyCode::Char('a') => {
-                            game.rotate(false);
-                        }
-                        KeyCode::Char('s') => {
-                            game.rotate(true);
-                        }
-                        KeyCode::Char('r') => {
-                            game.restart();
-                        }
-                        KeyCode::Char('c') => {
-                            game.hold_piece();
-                        }
-                        KeyCode::Char(' ') => {
-                            game.hard_drop();
-                        }
-                        KeyCode::Esc | KeyCode::Char('p') => {
-                            game.toggle_pause();
-                        }
-                        _ => {}
-                    },
-                    GameState::Paused => match key_event.code {
-                        KeyCode::Esc | KeyCode::Char('p') => game.toggle_pause(),
-                        _ => {}
-                    },
-                    GameState::GameOver => match key_event.code {
-                        KeyCode::Char('r') => game = Game::new(),
-                        KeyCode::Char('q') => break,
-                        _ => {}
-                    },
-                },
-                _ => {}
-            }
+// The game `--fumen` asks for, or an unstarted one otherwise. A fumen game
+// starts from the diagram's first page, using its quiz comment (or its
+// piece) for the hold and queue.
+fn initial_game(options: &Options, config: &Config) -> Result<Game, Box<dyn std::error::Error>> {
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let mut game = Game::with_config(seed, config, GameMode::Marathon);
+    if let Some(data) = &options.fumen {
+        let pages = fumen::decode(data)?;
+        let page = pages.first().ok_or("the fumen has no pages")?;
+        let mut queue = page.queue().unwrap_or_default();
+        // Without a quiz comment, start with the page's own piece.
+        if let (true, Some(piece)) = (queue.pieces.is_empty(), &page.piece) {
+            queue.pieces.push(piece.kind);
         }
+        game.load_position(page.board.clone(), queue.hold, &queue.pieces);
+        game.start(GameMode::Marathon);
+    }
+    Ok(game)
+}
 
-        if matches!(game.get_state(), GameState::Playing)
-            && last_tick.elapsed() >= Duration::from_millis(TICK_RATE)
-        {
-            if !game.move_piece(0, 1) {
-                game.lock_piece();
-            }
-            last_tick = std::time::Instant::now();
+// Plays `games` benchmark games with the bot `--mode` picks and prints how
+// each went.
+fn run_bench(
+    options: &Options,
+    config: &Config,
+    games: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mode = options.mode.unwrap_or(GameMode::WatchAi);
+    let mut controller =
+        play::launch_controller(mode, config)?.ok_or("--bench needs a bot mode")?;
+    let first_seed = options.seed.unwrap_or_else(rand::random);
+    let mut results = Vec::new();
+    for game in 0..games as u64 {
+        let result = bench::play(
+            controller.as_mut(),
+            config,
+            mode,
+            first_seed.wrapping_add(game),
+            options.pieces(),
+        );
+        println!("{}", result);
+        results.push(result);
+        if let Some(err) = controller.error() {
+            return Err(err.into());
         }
-
-        game.draw();
-        thread::sleep(Duration::from_millis(50));
     }
+    println!("{}", bench::summary(&results));
+    Ok(())
+}
 
-    execute!(stdout, Show, LeaveAlternateScreen)?;
-    disable_raw_mode()?;
+// Plays a replay through without drawing it and prints how the game ended.
+fn print_replay(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let game = spectate::replay_to_end(path)?.ok_or("the replay is empty")?;
+    println!(
+        "score {}, {} lines, {} pieces, {} attack{}",
+        game.score(),
+        game.lines(),
+        game.pieces_dealt(),
+        game.attack_sent(),
+        match game.top_out_reason() {
+            Some(reason) => format!(", {}", reason.label()),
+            None => String::new(),
+        }
+    );
     Ok(())
 }
+
+// Plays back a replay file, showing it like a spectated game.
+fn run_replay(path: &Path, config: Config) -> Result<(), Box<dyn Error>> {
+    let scenes = SceneStack::new(Box::new(WatchScene::new(Spectator::replay(path)?)));
+    run_scenes(scenes, Context::new(config, Game::new(), None, None))
+}
+
+fn run(game: Game, options: &Options, config: Config) -> Result<(), Box<dyn Error>> {
+    let mut broadcaster = config.spectate.map(Broadcaster::bind).transpose()?;
+    if let Some(path) = &options.record {
+        broadcaster
+            .get_or_insert_with(Broadcaster::offline)
+            .record_to(path)?;
+    }
+
+    let mut scenes = SceneStack::new(Box::new(TitleScene::new(&config.home)));
+    let mut ctx = Context::new(config, game, broadcaster, options.seed);
+    // A game asked for on the command line skips the title screen.
+    if options.fumen.is_some() {
+        scenes.push(Box::new(GameScene::new()));
+    } else if let Some(mode) = options.mode {
+        scenes.push(menu::launch(&mut ctx, mode));
+    }
+    run_scenes(scenes, ctx)
+}
+
+// Runs `scenes` until the player leaves, writing a crash report if one of
+// them panics. The panic hook has already restored the terminal and
+// printed the panic by the time it is caught here.
+fn run_scenes(mut scenes: SceneStack, mut ctx: Context) -> Result<(), Box<dyn Error>> {
+    match panic::catch_unwind(AssertUnwindSafe(|| scenes.run(&mut ctx))) {
+        Ok(result) => result,
+        Err(payload) => {
+            match scenes.write_crash_report(&ctx) {
+                Ok(path) => eprintln!("tetrust: crash report written to {}", path.display()),
+                Err(err) => eprintln!("tetrust: could not write a crash report: {}", err),
+            }
+            panic::resume_unwind(payload)
+        }
+    }
+}
@@ -0,0 +1,437 @@
+//! Room bookkeeping for the lobby server, kept free of sockets: every call
+//! returns the messages to send and to whom.
+
+use crate::client::net::{NetMessage, PlayerId, RoomId, RoomInfo, RoomMember};
+use rand::seq::SliceRandom;
+use std::collections::{BTreeMap, HashMap};
+
+/// Messages addressed to individual players.
+pub type Outgoing = Vec<(PlayerId, NetMessage)>;
+
+struct Room {
+    name: String,
+    members: Vec<RoomMember>,
+    game: Option<RoomMatch>,
+}
+
+struct RoomMatch {
+    alive: Vec<PlayerId>,
+    /// Players in the order they topped out.
+    eliminated: Vec<PlayerId>,
+}
+
+impl Room {
+    fn info(&self, id: RoomId) -> RoomInfo {
+        RoomInfo {
+            id,
+            name: self.name.clone(),
+            players: self.members.len(),
+            in_game: self.game.is_some(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Lobby {
+    /// Every connected player and the room they are in, if any.
+    players: HashMap<PlayerId, Option<RoomId>>,
+    rooms: BTreeMap<RoomId, Room>,
+    next_room: RoomId,
+}
+
+impl Lobby {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a player who completed the handshake.
+    pub fn connect(&mut self, player: PlayerId) -> Outgoing {
+        self.players.insert(player, None);
+        vec![
+            (player, NetMessage::Welcome { player }),
+            (player, self.room_list()),
+        ]
+    }
+
+    /// Removes a player, counting them as topped out if mid-match.
+    pub fn disconnect(&mut self, player: PlayerId) -> Outgoing {
+        let outgoing = self.leave_room(player);
+        self.players.remove(&player);
+        outgoing
+    }
+
+    pub fn handle(&mut self, player: PlayerId, message: NetMessage) -> Outgoing {
+        let Some(&room) = self.players.get(&player) else {
+            return Vec::new();
+        };
+        match message {
+            NetMessage::ListRooms => {
+                let mut outgoing = vec![(player, self.room_list())];
+                if let Some(room) = room {
+                    outgoing.push((player, self.room_state(room)));
+                }
+                outgoing
+            }
+            NetMessage::CreateRoom { name } => {
+                let mut outgoing = self.leave_room(player);
+                let id = self.next_room;
+                self.next_room += 1;
+                let name = match name.trim() {
+                    "" => format!("Room {}", id),
+                    name => name.to_string(),
+                };
+                self.rooms.insert(
+                    id,
+                    Room {
+                        name,
+                        members: Vec::new(),
+                        game: None,
+                    },
+                );
+                outgoing.extend(self.enter_room(player, id));
+                outgoing
+            }
+            NetMessage::JoinRoom { room: id } => match self.rooms.get(&id) {
+                None => reject(player, "no such room"),
+                Some(target) if target.game.is_some() => {
+                    reject(player, "that room is already playing")
+                }
+                Some(_) if room == Some(id) => Vec::new(),
+                Some(_) => {
+                    let mut outgoing = self.leave_room(player);
+                    outgoing.extend(self.enter_room(player, id));
+                    outgoing
+                }
+            },
+            NetMessage::LeaveRoom => {
+                let mut outgoing = self.leave_room(player);
+                outgoing.push((player, self.room_list()));
+                outgoing
+            }
+            NetMessage::Ready { ready } => {
+                let Some(id) = room else {
+                    return Vec::new();
+                };
+                let Some(room) = self.rooms.get_mut(&id) else {
+                    return Vec::new();
+                };
+                if room.game.is_some() {
+                    return Vec::new();
+                }
+                for member in room.members.iter_mut().filter(|m| m.player == player) {
+                    member.ready = ready;
+                }
+                let everyone_ready =
+                    room.members.len() >= 2 && room.members.iter().all(|m| m.ready);
+                if everyone_ready {
+                    self.start_match(id)
+                } else {
+                    self.broadcast_room(id)
+                }
+            }
+            NetMessage::Attack { lines } => {
+                let Some(game) = room.and_then(|id| self.rooms[&id].game.as_ref()) else {
+                    return Vec::new();
+                };
+                if !game.alive.contains(&player) {
+                    return Vec::new();
+                }
+                // Free-for-all garbage goes to one random surviving opponent.
+                let targets: Vec<PlayerId> = game
+                    .alive
+                    .iter()
+                    .copied()
+                    .filter(|&id| id != player)
+                    .collect();
+                match targets.choose(&mut rand::thread_rng()) {
+                    Some(&target) => vec![(target, NetMessage::Attack { lines })],
+                    None => Vec::new(),
+                }
+            }
+            NetMessage::Snapshot(snapshot) => {
+                let Some(id) = room else {
+                    return Vec::new();
+                };
+                self.rooms[&id]
+                    .members
+                    .iter()
+                    .filter(|m| m.player != player)
+                    .map(|m| {
+                        (
+                            m.player,
+                            NetMessage::PlayerSnapshot {
+                                player,
+                                snapshot: snapshot.clone(),
+                            },
+                        )
+                    })
+                    .collect()
+            }
+            NetMessage::TopOut => match room {
+                Some(id) => self.eliminate(id, player),
+                None => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    fn room_list(&self) -> NetMessage {
+        NetMessage::RoomList {
+            rooms: self.rooms.iter().map(|(&id, room)| room.info(id)).collect(),
+        }
+    }
+
+    fn room_state(&self, id: RoomId) -> NetMessage {
+        let room = &self.rooms[&id];
+        NetMessage::RoomState {
+            room: room.info(id),
+            members: room.members.clone(),
+        }
+    }
+
+    // Sends the room's state to its members and the updated room list to
+    // everyone browsing.
+    fn broadcast_room(&self, id: RoomId) -> Outgoing {
+        let mut outgoing = Vec::new();
+        if let Some(room) = self.rooms.get(&id) {
+            let state = self.room_state(id);
+            outgoing.extend(room.members.iter().map(|m| (m.player, state.clone())));
+        }
+        outgoing.extend(self.broadcast_room_list());
+        outgoing
+    }
+
+    fn broadcast_room_list(&self) -> Outgoing {
+        let list = self.room_list();
+        self.players
+            .iter()
+            .filter(|(_, room)| room.is_none())
+            .map(|(&player, _)| (player, list.clone()))
+            .collect()
+    }
+
+    fn enter_room(&mut self, player: PlayerId, id: RoomId) -> Outgoing {
+        if let Some(room) = self.rooms.get_mut(&id) {
+            room.members.push(RoomMember {
+                player,
+                ready: false,
+            });
+            self.players.insert(player, Some(id));
+        }
+        self.broadcast_room(id)
+    }
+
+    fn leave_room(&mut self, player: PlayerId) -> Outgoing {
+        let Some(Some(id)) = self.players.insert(player, None) else {
+            return Vec::new();
+        };
+        let mut outgoing = self.eliminate(id, player);
+        let Some(room) = self.rooms.get_mut(&id) else {
+            return outgoing;
+        };
+        room.members.retain(|m| m.player != player);
+        if room.members.is_empty() {
+            self.rooms.remove(&id);
+        }
+        outgoing.extend(self.broadcast_room(id));
+        outgoing
+    }
+
+    fn start_match(&mut self, id: RoomId) -> Outgoing {
+        let room = self.rooms.get_mut(&id).expect("room exists");
+        let seed = rand::random();
+        let players: Vec<PlayerId> = room.members.iter().map(|m| m.player).collect();
+        room.game = Some(RoomMatch {
+            alive: players.clone(),
+            eliminated: Vec::new(),
+        });
+        let mut outgoing: Outgoing = players
+            .iter()
+            .map(|&player| (player, NetMessage::Start { seed }))
+            .collect();
+        outgoing.extend(self.broadcast_room_list());
+        outgoing
+    }
+
+    // Records a top out and, once one player is left, ends the match and
+    // announces the standings.
+    fn eliminate(&mut self, id: RoomId, player: PlayerId) -> Outgoing {
+        let Some(room) = self.rooms.get_mut(&id) else {
+            return Vec::new();
+        };
+        let Some(game) = room.game.as_mut() else {
+            return Vec::new();
+        };
+        let Some(index) = game.alive.iter().position(|&p| p == player) else {
+            return Vec::new();
+        };
+        game.alive.remove(index);
+        game.eliminated.push(player);
+        if game.alive.len() > 1 {
+            return Vec::new();
+        }
+
+        let standings: Vec<PlayerId> = game
+            .alive
+            .iter()
+            .chain(game.eliminated.iter().rev())
+            .copied()
+            .collect();
+        room.game = None;
+        for member in room.members.iter_mut() {
+            member.ready = false;
+        }
+        let mut outgoing: Outgoing = room
+            .members
+            .iter()
+            .map(|m| {
+                (
+                    m.player,
+                    NetMessage::Results {
+                        standings: standings.clone(),
+                    },
+                )
+            })
+            .collect();
+        outgoing.extend(self.broadcast_room(id));
+        outgoing
+    }
+}
+
+fn reject(player: PlayerId, reason: &str) -> Outgoing {
+    vec![(
+        player,
+        NetMessage::Reject {
+            reason: reason.to_string(),
+        },
+    )]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::net::BoardSnapshot;
+
+    // The messages `outgoing` sends to `player`, leaving out the room lists
+    // and states that accompany most changes.
+    fn to(outgoing: &Outgoing, player: PlayerId) -> Vec<NetMessage> {
+        outgoing
+            .iter()
+            .filter(|(to, message)| {
+                *to == player
+                    && !matches!(
+                        message,
+                        NetMessage::RoomList { .. } | NetMessage::RoomState { .. }
+                    )
+            })
+            .map(|(_, message)| message.clone())
+            .collect()
+    }
+
+    // Puts `players` in a new room and starts a match there.
+    fn start_match(lobby: &mut Lobby, players: &[PlayerId]) -> RoomId {
+        for &player in players {
+            lobby.connect(player);
+        }
+        let name = String::new();
+        lobby.handle(players[0], NetMessage::CreateRoom { name });
+        let room = lobby.players[&players[0]].unwrap();
+        for &player in &players[1..] {
+            lobby.handle(player, NetMessage::JoinRoom { room });
+        }
+        let mut outgoing = Vec::new();
+        for &player in players {
+            outgoing = lobby.handle(player, NetMessage::Ready { ready: true });
+        }
+        let seed = match to(&outgoing, players[0])[..] {
+            [NetMessage::Start { seed }] => seed,
+            ref other => panic!("the match did not start: {:?}", other),
+        };
+        for &player in players {
+            assert_eq!(to(&outgoing, player), [NetMessage::Start { seed }]);
+        }
+        room
+    }
+
+    fn results(standings: &[PlayerId]) -> NetMessage {
+        NetMessage::Results {
+            standings: standings.to_vec(),
+        }
+    }
+
+    #[test]
+    fn attacks_go_to_an_opponent_in_the_same_room() {
+        let mut lobby = Lobby::new();
+        start_match(&mut lobby, &[1, 2]);
+        start_match(&mut lobby, &[3, 4, 5]);
+        let attack = NetMessage::Attack { lines: 2 };
+
+        assert_eq!(lobby.handle(1, attack.clone()), [(2, attack.clone())]);
+        for _ in 0..20 {
+            let outgoing = lobby.handle(3, attack.clone());
+            assert!(matches!(outgoing[..], [(4 | 5, _)]), "{:?}", outgoing);
+        }
+
+        // Once out, a player neither sends nor receives garbage.
+        lobby.handle(4, NetMessage::TopOut);
+        assert_eq!(lobby.handle(4, attack.clone()), []);
+        for _ in 0..20 {
+            assert_eq!(lobby.handle(3, attack.clone()), [(5, attack.clone())]);
+        }
+    }
+
+    #[test]
+    fn snapshots_reach_the_rest_of_the_room() {
+        let mut lobby = Lobby::new();
+        start_match(&mut lobby, &[1, 2, 3]);
+        start_match(&mut lobby, &[4, 5]);
+        let snapshot = BoardSnapshot {
+            width: 10,
+            rows: vec![0; 20],
+            piece: Vec::new(),
+            score: 0,
+            lines: 0,
+            pending_garbage: 0,
+        };
+        let outgoing = lobby.handle(1, NetMessage::Snapshot(snapshot.clone()));
+        let relayed = NetMessage::PlayerSnapshot {
+            player: 1,
+            snapshot,
+        };
+        assert_eq!(outgoing, [(2, relayed.clone()), (3, relayed)]);
+    }
+
+    #[test]
+    fn standings_list_the_winner_then_the_latest_out() {
+        let mut lobby = Lobby::new();
+        let room = start_match(&mut lobby, &[1, 2, 3]);
+        assert_eq!(to(&lobby.handle(2, NetMessage::TopOut), 1), []);
+
+        let outgoing = lobby.handle(3, NetMessage::TopOut);
+        for player in [1, 2, 3] {
+            assert_eq!(to(&outgoing, player), [results(&[1, 3, 2])]);
+        }
+        // The room is open again, with nobody ready.
+        let room = &lobby.rooms[&room];
+        assert!(room.game.is_none());
+        assert!(room.members.iter().all(|m| !m.ready));
+        assert_eq!(lobby.handle(1, NetMessage::TopOut), []);
+    }
+
+    #[test]
+    fn leaving_mid_match_counts_as_topping_out() {
+        let mut lobby = Lobby::new();
+        start_match(&mut lobby, &[1, 2, 3]);
+        assert_eq!(to(&lobby.disconnect(2), 1), []);
+        let outgoing = lobby.handle(1, NetMessage::TopOut);
+        assert_eq!(to(&outgoing, 3), [results(&[3, 1, 2])]);
+        assert_eq!(to(&outgoing, 2), []);
+
+        // With only two playing, the one left wins straight away.
+        let mut lobby = Lobby::new();
+        let room = start_match(&mut lobby, &[1, 2]);
+        let outgoing = lobby.disconnect(1);
+        assert_eq!(to(&outgoing, 2), [results(&[2, 1])]);
+        assert_eq!(lobby.rooms[&room].members.len(), 1);
+    }
+}
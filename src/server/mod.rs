@@ -0,0 +1,210 @@
+//! Headless lobby server. Clients speak the `client::net` protocol; the
+//! server groups them into rooms, starts matches with a seed it picks and
+//! relays garbage and boards between the players.
+
+pub mod lobby;
+
+use crate::client::net::{self, NetMessage, PlayerId, PROTOCOL_VERSION};
+use lobby::{Lobby, Outgoing};
+use std::collections::HashMap;
+use std::io;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Sender, SyncSender};
+use std::thread;
+use std::time::Duration;
+
+/// Pause after a failed accept, so running out of file descriptors does not
+/// turn into a busy loop.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Messages queued for a client before it counts as not reading and is cut
+/// off, so it cannot hold up everyone else.
+const OUTGOING_QUEUE: usize = 256;
+
+/// How long a single write to a client may block before giving up on it.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+enum ServerEvent {
+    Connected(PlayerId, TcpStream),
+    Message(PlayerId, NetMessage),
+    Disconnected(PlayerId),
+}
+
+struct Client {
+    stream: TcpStream,
+    /// Feeds the client's writer thread, so the lobby never waits on a
+    /// socket.
+    outgoing: SyncSender<NetMessage>,
+    /// Set once the client has sent a compatible `Hello`.
+    greeted: bool,
+}
+
+impl Client {
+    fn new(stream: &TcpStream) -> io::Result<Self> {
+        let stream = stream.try_clone()?;
+        let mut writer = stream.try_clone()?;
+        writer.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let (outgoing, queue) = mpsc::sync_channel(OUTGOING_QUEUE);
+        thread::spawn(move || {
+            for message in queue {
+                if net::write_message(&mut writer, &message).is_err() {
+                    break;
+                }
+            }
+            // Once the queue is dropped or a write fails, closing the stream
+            // ends the reader thread, which reports the disconnect.
+            let _ = writer.shutdown(Shutdown::Both);
+        });
+        Ok(Self {
+            stream,
+            outgoing,
+            greeted: false,
+        })
+    }
+
+    /// Queues a message, cutting the client off if its queue is full.
+    fn send(&self, message: NetMessage) {
+        if self.outgoing.try_send(message).is_err() {
+            let _ = self.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+pub struct Server {
+    listener: TcpListener,
+}
+
+impl Server {
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(address)?,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves clients for as long as the process runs. Each connection gets
+    /// a reader thread; all lobby state lives on the calling thread.
+    pub fn run(self) -> io::Result<()> {
+        let (sender, events) = mpsc::channel();
+        let listener = self.listener;
+        let accept_thread = thread::spawn(move || accept_loop(listener, sender));
+
+        let mut lobby = Lobby::new();
+        let mut clients: HashMap<PlayerId, Client> = HashMap::new();
+        for event in events {
+            let outgoing = match event {
+                ServerEvent::Connected(player, stream) => {
+                    match Client::new(&stream) {
+                        Ok(client) => {
+                            clients.insert(player, client);
+                        }
+                        Err(_) => {
+                            let _ = stream.shutdown(Shutdown::Both);
+                        }
+                    }
+                    Vec::new()
+                }
+                ServerEvent::Message(player, message) => {
+                    let Some(client) = clients.get_mut(&player) else {
+                        continue;
+                    };
+                    match message {
+                        _ if client.greeted && message == NetMessage::Bye => {
+                            drop_client(&mut clients, player);
+                            lobby.disconnect(player)
+                        }
+                        _ if client.greeted => lobby.handle(player, message),
+                        NetMessage::Hello { version } if version == PROTOCOL_VERSION => {
+                            client.greeted = true;
+                            let hello = NetMessage::Hello {
+                                version: PROTOCOL_VERSION,
+                            };
+                            let mut outgoing = vec![(player, hello)];
+                            outgoing.extend(lobby.connect(player));
+                            outgoing
+                        }
+                        NetMessage::Hello { version } => {
+                            let reason = format!(
+                                "protocol version {} is not supported (server uses {})",
+                                version, PROTOCOL_VERSION
+                            );
+                            client.send(NetMessage::Reject { reason });
+                            drop_client(&mut clients, player);
+                            Vec::new()
+                        }
+                        _ => Vec::new(),
+                    }
+                }
+                ServerEvent::Disconnected(player) => {
+                    if drop_client(&mut clients, player).is_some_and(|client| client.greeted) {
+                        lobby.disconnect(player)
+                    } else {
+                        Vec::new()
+                    }
+                }
+            };
+            deliver(&clients, outgoing);
+        }
+
+        accept_thread
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("accept loop panicked")))
+    }
+}
+
+// Accept errors are usually passing, such as a peer hanging up before it
+// was accepted or running out of file descriptors, so they are logged and
+// the server keeps listening.
+fn accept_loop(listener: TcpListener, sender: Sender<ServerEvent>) -> io::Result<()> {
+    let mut next_player: PlayerId = 1;
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("accept failed: {}", err);
+                thread::sleep(ACCEPT_RETRY_DELAY);
+                continue;
+            }
+        };
+        let reader = match stream.set_nodelay(true).and_then(|()| stream.try_clone()) {
+            Ok(reader) => reader,
+            Err(err) => {
+                eprintln!("could not set up a connection: {}", err);
+                continue;
+            }
+        };
+        let player = next_player;
+        next_player += 1;
+        if sender.send(ServerEvent::Connected(player, stream)).is_err() {
+            break;
+        }
+        let sender = sender.clone();
+        thread::spawn(move || {
+            net::read_messages(reader, |message| {
+                sender.send(ServerEvent::Message(player, message)).is_ok()
+            });
+            let _ = sender.send(ServerEvent::Disconnected(player));
+        });
+    }
+    Ok(())
+}
+
+// Forgets a client. Its writer thread sends what is still queued, such as
+// a `Reject`, and then closes the connection.
+fn drop_client(clients: &mut HashMap<PlayerId, Client>, player: PlayerId) -> Option<Client> {
+    clients.remove(&player)
+}
+
+// Messages to players who have since left are dropped; a client that has
+// stopped reading is cut off and counted as a disconnect when the reader
+// thread notices.
+fn deliver(clients: &HashMap<PlayerId, Client>, outgoing: Outgoing) {
+    for (player, message) in outgoing {
+        if let Some(client) = clients.get(&player) {
+            client.send(message);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Runs the lobby server on a free port and plays two clients through it.
+
+use std::thread;
+use std::time::Duration;
+use tetrust::client::net::{self, Connection, NetMessage};
+use tetrust::server::Server;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Reads messages until one matches `wanted`, skipping the room lists and
+// states the server sends along the way.
+fn expect<T>(connection: &mut Connection, wanted: impl Fn(NetMessage) -> Option<T>) -> T {
+    loop {
+        let message = connection.recv_timeout(TIMEOUT).unwrap();
+        if let Some(found) = wanted(message) {
+            return found;
+        }
+    }
+}
+
+#[test]
+fn two_clients_create_join_and_start_a_match() {
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+
+    let (mut alice, alice_id) = net::join_lobby(address).unwrap();
+    let (mut bob, bob_id) = net::join_lobby(address).unwrap();
+    assert_ne!(alice_id, bob_id);
+
+    alice
+        .send(&NetMessage::CreateRoom {
+            name: "Test".to_string(),
+        })
+        .unwrap();
+    let room = expect(&mut alice, |message| match message {
+        NetMessage::RoomState { room, .. } => Some(room),
+        _ => None,
+    });
+    assert_eq!(room.name, "Test");
+
+    bob.send(&NetMessage::JoinRoom { room: room.id }).unwrap();
+    let members = expect(&mut bob, |message| match message {
+        NetMessage::RoomState { members, .. } => Some(members),
+        _ => None,
+    });
+    assert_eq!(members.len(), 2);
+
+    for connection in [&mut alice, &mut bob] {
+        connection.send(&NetMessage::Ready { ready: true }).unwrap();
+    }
+    let start = |message| match message {
+        NetMessage::Start { seed } => Some(seed),
+        _ => None,
+    };
+    assert_eq!(expect(&mut alice, start), expect(&mut bob, start));
+}
+
+#[test]
+fn a_client_that_stops_reading_does_not_stall_the_others() {
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+
+    // Never reads: once its socket buffers fill with room lists, writes to
+    // it would block.
+    let stalled = std::net::TcpStream::connect(address).unwrap();
+    net::write_message(
+        &mut &stalled,
+        &NetMessage::Hello {
+            version: net::PROTOCOL_VERSION,
+        },
+    )
+    .unwrap();
+    let (mut active, _) = net::join_lobby(address).unwrap();
+    let name = "x".repeat(4000);
+    for _ in 0..3_000 {
+        active
+            .send(&NetMessage::CreateRoom { name: name.clone() })
+            .unwrap();
+        expect(&mut active, |message| match message {
+            NetMessage::RoomState { .. } => Some(()),
+            _ => None,
+        });
+    }
+    drop(stalled);
+}
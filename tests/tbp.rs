@@ -0,0 +1,98 @@
+//! Drives `TbpBot` against the bundled dummy bot, and against scripted bots
+//! that fail partway through a game.
+
+use std::thread;
+use std::time::{Duration, Instant};
+use tetrust::client::board::{DEFAULT_HEIGHT, DEFAULT_WIDTH};
+use tetrust::client::game::{Game, GameMode, GameState};
+use tetrust::client::input::Controller;
+use tetrust::client::tbp::TbpBot;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn new_game() -> Game {
+    let mut game = Game::with_size(1, DEFAULT_WIDTH, DEFAULT_HEIGHT);
+    game.start(GameMode::WatchBot);
+    game
+}
+
+#[test]
+fn the_dummy_bot_plays_through_several_pieces() {
+    let command = [env!("CARGO_BIN_EXE_tetrust-dummy-bot").to_string()];
+    let mut bot = TbpBot::launch(&command).unwrap();
+    assert!(bot.name.starts_with("TetRust Dummy Bot"));
+
+    let mut game = new_game();
+    let start = Instant::now();
+    while game.pieces_dealt() <= 20 {
+        assert!(start.elapsed() < TIMEOUT, "the bot stalled");
+        assert!(game.get_state() == GameState::Playing, "the bot topped out");
+        if let Some(input) = bot.next_input(&game) {
+            game.apply_input(input);
+        }
+        assert_eq!(bot.error(), None);
+        thread::sleep(Duration::from_millis(1));
+    }
+    // Twenty pieces on an empty board need some lines cleared.
+    assert!(game.lines() > 0);
+}
+
+#[cfg(unix)]
+mod scripted {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    const HANDSHAKE: &str = r#"echo '{"type":"info","name":"Scripted","version":"1","author":"test","features":[]}'
+read rules
+echo '{"type":"ready"}'
+"#;
+
+    // Writes a bot that completes the handshake and then runs `rest`.
+    fn scripted_bot(name: &str, rest: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("tetrust-{}-{}.sh", name, std::process::id()));
+        fs::write(&path, format!("#!/bin/sh\n{}{}", HANDSHAKE, rest)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    // Asks the bot for inputs until it reports an error.
+    fn play_until_error(bot: &mut TbpBot) -> String {
+        let mut game = new_game();
+        let start = Instant::now();
+        while start.elapsed() < TIMEOUT {
+            if let Some(input) = bot.next_input(&game) {
+                game.apply_input(input);
+            }
+            if let Some(err) = bot.error().map(str::to_string) {
+                // A failed bot stays stopped.
+                assert_eq!(bot.next_input(&game), None);
+                return err;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("the bot never reported an error");
+    }
+
+    #[test]
+    fn a_bot_that_exits_is_reported() {
+        let path = scripted_bot("exits", "read start\nexit 3\n");
+        let mut bot = TbpBot::launch(&[path.display().to_string()]).unwrap();
+        let err = play_until_error(&mut bot);
+        fs::remove_file(path).unwrap();
+        assert_eq!(err, "bot exited (exit status: 3)");
+    }
+
+    #[test]
+    fn an_error_reply_is_reported() {
+        let path = scripted_bot(
+            "refuses",
+            "read start\nread suggest\necho '{\"type\":\"error\",\"reason\":\"no thanks\"}'\nread quit\n",
+        );
+        let mut bot = TbpBot::launch(&[path.display().to_string()]).unwrap();
+        let err = play_until_error(&mut bot);
+        fs::remove_file(path).unwrap();
+        assert_eq!(err, "bot error: no thanks");
+    }
+}